@@ -1,4 +1,7 @@
-use jalm_syntax::{SyntaxKind, SyntaxNode};
+use jalm_syntax::{SyntaxKind, SyntaxNode, SyntaxToken};
+
+/// Version of this crate, reported by `jalmt version --verbose`.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
 pub trait AstNode: Sized {
     fn can_cast(kind: SyntaxKind) -> bool;
@@ -34,6 +37,7 @@ macro_rules! impl_ast_node {
     };
 }
 
+impl_ast_node!(Root, SyntaxKind::Root);
 impl_ast_node!(Module, SyntaxKind::ModuleDecl);
 impl_ast_node!(Import, SyntaxKind::UseDecl);
 impl_ast_node!(FnDecl, SyntaxKind::FnDecl);
@@ -42,12 +46,145 @@ impl_ast_node!(Block, SyntaxKind::Block);
 impl_ast_node!(Let, SyntaxKind::LetStmt);
 impl_ast_node!(Struct, SyntaxKind::StructDecl);
 impl_ast_node!(Enum, SyntaxKind::EnumDecl);
+impl_ast_node!(EffectAlias, SyntaxKind::EffectAliasDecl);
+impl_ast_node!(Const, SyntaxKind::ConstDecl);
+impl_ast_node!(TypeAlias, SyntaxKind::TypeAlias);
+impl_ast_node!(Impl, SyntaxKind::ImplBlock);
+impl_ast_node!(ModuleAttribute, SyntaxKind::ModuleAttribute);
 impl_ast_node!(IfExpr, SyntaxKind::IfExpr);
 impl_ast_node!(MatchExpr, SyntaxKind::MatchExpr);
 impl_ast_node!(CallExpr, SyntaxKind::CallExpr);
 impl_ast_node!(Ident, SyntaxKind::IdentNode);
 impl_ast_node!(Literal, SyntaxKind::LiteralNode);
 
-pub fn children<T: AstNode>(node: &SyntaxNode) -> impl Iterator<Item = T> + '_ {
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Item {
+    Fn(FnDecl),
+    Struct(Struct),
+    Enum(Enum),
+    Module(Module),
+    Use(Import),
+    EffectAlias(EffectAlias),
+    Const(Const),
+    TypeAlias(TypeAlias),
+    Impl(Impl),
+    ModuleAttribute(ModuleAttribute),
+}
+
+impl Item {
+    pub fn cast(node: SyntaxNode) -> Option<Self> {
+        match node.kind() {
+            SyntaxKind::FnDecl => FnDecl::cast(node).map(Item::Fn),
+            SyntaxKind::StructDecl => Struct::cast(node).map(Item::Struct),
+            SyntaxKind::EnumDecl => Enum::cast(node).map(Item::Enum),
+            SyntaxKind::ModuleDecl => Module::cast(node).map(Item::Module),
+            SyntaxKind::UseDecl => Import::cast(node).map(Item::Use),
+            SyntaxKind::EffectAliasDecl => EffectAlias::cast(node).map(Item::EffectAlias),
+            SyntaxKind::ConstDecl => Const::cast(node).map(Item::Const),
+            SyntaxKind::TypeAlias => TypeAlias::cast(node).map(Item::TypeAlias),
+            SyntaxKind::ImplBlock => Impl::cast(node).map(Item::Impl),
+            SyntaxKind::ModuleAttribute => ModuleAttribute::cast(node).map(Item::ModuleAttribute),
+            _ => None,
+        }
+    }
+
+    pub fn syntax(&self) -> &SyntaxNode {
+        match self {
+            Item::Fn(it) => it.syntax(),
+            Item::Struct(it) => it.syntax(),
+            Item::Enum(it) => it.syntax(),
+            Item::Module(it) => it.syntax(),
+            Item::Use(it) => it.syntax(),
+            Item::EffectAlias(it) => it.syntax(),
+            Item::Const(it) => it.syntax(),
+            Item::TypeAlias(it) => it.syntax(),
+            Item::Impl(it) => it.syntax(),
+            Item::ModuleAttribute(it) => it.syntax(),
+        }
+    }
+}
+
+impl Impl {
+    /// The methods declared in the block, in source order.
+    pub fn methods(&self) -> impl Iterator<Item = FnDecl> + '_ {
+        children(&self.syntax)
+    }
+}
+
+impl Import {
+    /// The name nodes the `use` binds: each group member's alias or name, else the `as` alias
+    /// or the path's last segment.
+    pub fn bound_names(&self) -> Vec<SyntaxNode> {
+        let Some(path) = self.syntax.children().find(|n| n.kind() == SyntaxKind::UsePath) else {
+            return Vec::new();
+        };
+        if let Some(group) = path.children().find(|n| n.kind() == SyntaxKind::UseGroup) {
+            return group
+                .children()
+                .filter(|n| n.kind() == SyntaxKind::UseGroupItem)
+                .filter_map(|item| item.children().filter(|n| n.kind() == SyntaxKind::IdentNode).last())
+                .collect();
+        }
+        let alias = self.syntax.children().find(|n| n.kind() == SyntaxKind::IdentNode);
+        alias.or_else(|| path.children().filter(|n| n.kind() == SyntaxKind::IdentNode).last()).into_iter().collect()
+    }
+}
+
+impl Root {
+    /// Top-level items in source order; trivia and error nodes are skipped.
+    pub fn items(&self) -> impl Iterator<Item = Item> + '_ {
+        self.syntax.children().filter_map(Item::cast)
+    }
+}
+
+pub fn children<'a, T: AstNode + 'a>(node: &'a SyntaxNode) -> impl Iterator<Item = T> + 'a {
     node.children().filter_map(T::cast)
 }
+
+/// Whitespace and comment tokens directly before `node` in its parent, in source order.
+pub fn leading_trivia(node: &SyntaxNode) -> Vec<SyntaxToken> {
+    let mut trivia: Vec<_> = std::iter::successors(node.prev_sibling_or_token(), |el| el.prev_sibling_or_token())
+        .map_while(|el| el.into_token().filter(|t| t.kind().is_trivia()))
+        .collect();
+    trivia.reverse();
+    trivia
+}
+
+/// Text of the `///` comments that open `node` (a struct field or enum variant), one
+/// line each with the `///` and a single following space removed.
+pub fn doc_comment(node: &SyntaxNode) -> Option<String> {
+    let lines: Vec<String> = node
+        .children_with_tokens()
+        .filter_map(|el| el.into_token())
+        .filter(|t| t.kind() == SyntaxKind::DocComment)
+        .map(|t| {
+            let text = &t.text()[3..];
+            text.strip_prefix(' ').unwrap_or(text).trim_end().to_string()
+        })
+        .collect();
+    (!lines.is_empty()).then(|| lines.join("\n"))
+}
+
+/// The feature named by an item's `@cfg(feature = "name")` attribute, if it has one.
+pub fn cfg_feature(item: &SyntaxNode) -> Option<String> {
+    let ident_text = |node: &SyntaxNode| {
+        node.children()
+            .find(|n| n.kind() == SyntaxKind::IdentNode)
+            .and_then(|n| n.first_token())
+            .map(|t| t.text().to_string())
+    };
+    item.children()
+        .filter(|n| n.kind() == SyntaxKind::Attribute && ident_text(n).as_deref() == Some("cfg"))
+        .flat_map(|attr| attr.children().filter(|n| n.kind() == SyntaxKind::AttrKeyValue))
+        .find(|arg| ident_text(arg).as_deref() == Some("feature"))
+        .and_then(|arg| arg.children().find(|n| n.kind() == SyntaxKind::LiteralNode))
+        .and_then(|lit| lit.first_token())
+        .and_then(|t| t.text().strip_prefix('"')?.strip_suffix('"').map(str::to_string))
+}
+
+/// Whitespace and comment tokens directly after `node` in its parent, in source order.
+pub fn trailing_trivia(node: &SyntaxNode) -> Vec<SyntaxToken> {
+    std::iter::successors(node.next_sibling_or_token(), |el| el.next_sibling_or_token())
+        .map_while(|el| el.into_token().filter(|t| t.kind().is_trivia()))
+        .collect()
+}
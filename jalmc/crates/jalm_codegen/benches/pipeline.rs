@@ -0,0 +1,49 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use jalm_codegen::compile_to_wasm;
+use jalm_parser::parse;
+
+/// Functions in the generated program.
+const FUNCTIONS: usize = 500;
+
+/// A program of `count` functions, each calling the one before it, ending in `main`.
+/// The output depends only on `count`, so every run measures the same input.
+fn generate_program(count: usize) -> String {
+    let mut source = String::from("fn f0(a: i64, b: i64) -> i64 {\n  return a + b;\n}\n");
+    for i in 1..count {
+        source.push_str(&format!(
+            "\nfn f{i}(a: i64, b: i64) -> i64 {{\n  let x: i64 = f{prev}(a, b);\n  let y: i64 = x * {i} - b / 2;\n  if y > a {{\n    return y - a;\n  }}\n  return x + y;\n}}\n",
+            prev = i - 1,
+        ));
+    }
+    source.push_str(&format!("\nfn main() -> i64 {{\n  return f{}(1, 2);\n}}\n", count - 1));
+    source
+}
+
+fn bench_pipeline(c: &mut Criterion) {
+    let source = generate_program(FUNCTIONS);
+    let parsed = parse(&source);
+    assert!(parsed.is_ok(), "generated program should parse");
+    assert!(!jalm_typecheck::check_parsed(&parsed).has_errors(), "generated program should type-check");
+
+    let mut group = c.benchmark_group("pipeline");
+    group.bench_function(BenchmarkId::new("parse", FUNCTIONS), |b| b.iter(|| parse(&source)));
+    group.bench_function(BenchmarkId::new("check", FUNCTIONS), |b| {
+        b.iter(|| (jalm_typecheck::check_parsed(&parsed), jalm_effectcheck::check_parsed(&parsed)))
+    });
+    // `compile_to_wasm` takes source text, so this stage includes its own parse.
+    group.bench_function(BenchmarkId::new("compile_to_wasm", FUNCTIONS), |b| {
+        b.iter(|| compile_to_wasm(&source).expect("compile ok"))
+    });
+    group.bench_function(BenchmarkId::new("end_to_end", FUNCTIONS), |b| {
+        b.iter(|| {
+            let parsed = parse(&source);
+            let _ = jalm_typecheck::check_parsed(&parsed);
+            let _ = jalm_effectcheck::check_parsed(&parsed);
+            compile_to_wasm(&source).expect("compile ok")
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_pipeline);
+criterion_main!(benches);
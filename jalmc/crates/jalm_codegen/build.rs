@@ -0,0 +1,18 @@
+//! Exposes the wasm-encoder requirement from Cargo.toml as `WASM_ENCODER_VERSION`, for `jalmt version --verbose`.
+
+use std::fs;
+
+fn main() {
+    println!("cargo:rerun-if-changed=Cargo.toml");
+    let manifest = fs::read_to_string("Cargo.toml").expect("read Cargo.toml");
+    // `wasm-encoder = "x"` or `wasm-encoder = { version = "x", ... }` under `[dependencies]`.
+    let version = manifest
+        .lines()
+        .skip_while(|line| line.trim() != "[dependencies]")
+        .skip(1)
+        .take_while(|line| !line.starts_with('['))
+        .find_map(|line| line.strip_prefix("wasm-encoder = "))
+        .and_then(|spec| spec.split('"').nth(1))
+        .expect("wasm-encoder dependency in Cargo.toml");
+    println!("cargo:rustc-env=WASM_ENCODER_VERSION={version}");
+}
@@ -1,74 +1,536 @@
-use jalm_parser::parse;
-use jalm_syntax::{SyntaxElement, SyntaxKind, SyntaxNode};
-use wasm_encoder::{CodeSection, ExportKind, ExportSection, Function, FunctionSection, Instruction, Module, TypeSection, ValType};
+use jalm_ast::{AstNode, Item, Root};
+use jalm_parser::{parse, parse_with_features, Parse};
+use jalm_syntax::{decode_byte_string, decode_string, int_literal_value, literal_value, SyntaxElement, SyntaxKind, SyntaxNode};
+use rowan::TextRange;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use wasm_encoder::{
+    CodeSection, ConstExpr, CustomSection, DataSection, EntityType, ExportKind, ExportSection, Function, FunctionSection, ImportSection,
+    Instruction, MemorySection, MemoryType, Module, TypeSection, ValType,
+};
+
+/// Version of this crate, reported by `jalmt version --verbose`.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// `wasm-encoder` requirement this crate is built against, read from Cargo.toml by build.rs.
+pub const WASM_ENCODER_VERSION: &str = env!("WASM_ENCODER_VERSION");
+
+/// WebAssembly proposals beyond the MVP that generated modules use: tuple results need
+/// multi-value, and the memory builtins lower to `memory.copy` and `memory.fill`.
+pub const TARGET_FEATURES: &[&str] = &["multi-value", "bulk-memory"];
 
 #[derive(Debug, Clone)]
 pub struct Diagnostic {
     pub code: String,
     pub message: String,
+    pub span: Option<Span>,
 }
 
-pub fn compile_to_wasm(source: &str) -> Result<Vec<u8>, Vec<Diagnostic>> {
-    let parsed = parse(source);
-    if !parsed.errors.is_empty() {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Offset of the first data segment; address 0 is left unused.
+const DATA_BASE: i64 = 8;
+const WASM_PAGE_SIZE: u64 = 65536;
+/// Path prefixes of effectful host builtins, rejected in a `@no_host;` module.
+const HOST_MODULES: [&str; 7] = ["fs", "net", "http", "time", "rand", "log", "ffi"];
+/// Builtins lowered to bulk-memory instructions unless the program defines a function of the same name.
+const MEMORY_BUILTINS: [&str; 2] = ["memcpy", "memset"];
+/// Builtin that traps; like the memory builtins, a program-defined function of this name wins.
+const PANIC_BUILTIN: &str = "panic";
+/// Builtin that traps when its `bool` operand is false.
+const ASSERT_BUILTIN: &str = "assert";
+/// Code passed to the imported `env.jalm_panic` by a checked integer division by zero.
+pub const PANIC_DIVIDE_BY_ZERO: i32 = 1;
+
+/// Name of the custom section written by a debug build; see [`read_source_map`].
+pub const SOURCE_MAP_SECTION: &str = "jalm.sourcemap";
+
+/// Settings for [`compile_to_wasm_with_options`].
+#[derive(Debug, Clone, Default)]
+pub struct CompileOptions {
+    /// Emit a `jalm.sourcemap` custom section mapping instructions back to source spans.
+    pub debug: bool,
+    /// Check every integer `/` and `%` divisor for zero and call the imported
+    /// `env.jalm_panic(PANIC_DIVIDE_BY_ZERO)` instead of relying on the opaque wasm trap.
+    pub checked_arithmetic: bool,
+    /// Features enabled for `@cfg(feature = "name")` items; other gated items are left out.
+    pub features: Vec<String>,
+}
+
+/// One instruction of a debug build, located by function index and byte offset within
+/// that function's body (locals included), with the source span it was lowered from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SourceMapEntry {
+    pub func_index: u32,
+    pub offset: u32,
+    pub span: Span,
+}
+
+/// Exports and imports of a compiled module, with their wasm signatures.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ModuleInterface {
+    pub exports: Vec<ExportedFn>,
+    pub imports: Vec<ImportedFn>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ExportedFn {
+    pub name: String,
+    pub params: Vec<String>,
+    pub result: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ImportedFn {
+    pub module: String,
+    pub field: String,
+    pub params: Vec<String>,
+    pub result: String,
+}
+
+pub fn module_interface(source: &str) -> Result<ModuleInterface, Vec<Diagnostic>> {
+    let Lowered { functions, .. } = lower_parsed(&parse(source))?;
+    let mut diags = Vec::new();
+    let mut interface = ModuleInterface { exports: Vec::new(), imports: Vec::new() };
+    for f in &functions {
+        let (params, result) = signature_from_fn(f, &mut diags);
+        let params: Vec<String> = params.iter().map(|ty| val_type_name(*ty).to_string()).collect();
+        let result = match result.as_slice() {
+            [single] => val_type_name(*single).to_string(),
+            many => format!("({})", many.iter().map(|ty| val_type_name(*ty)).collect::<Vec<_>>().join(", ")),
+        };
+        if let Some((module, field)) = &f.import {
+            interface.imports.push(ImportedFn { module: module.clone(), field: field.clone(), params, result });
+        } else if let Some(name) = export_name(f) {
+            interface.exports.push(ExportedFn { name: name.to_string(), params, result });
+        }
+    }
+    if diags.is_empty() {
+        Ok(interface)
+    } else {
+        Err(diags)
+    }
+}
+
+/// A parsed program ready for emission.
+struct Lowered {
+    /// In function-index order: imports first, then defined functions, then the unreachable
+    /// ones, each group in source order.
+    functions: Vec<FnDef>,
+    /// Defined functions no export calls. They are checked like the rest but left out of the module.
+    unreachable: BTreeSet<String>,
+    /// `Enum::Variant` to its `i32` value, for enums whose variants are all fieldless.
+    discriminants: BTreeMap<String, i32>,
+}
+
+/// Collects the functions and enum discriminants of a parsed source file.
+fn lower_parsed(parsed: &Parse) -> Result<Lowered, Vec<Diagnostic>> {
+    if !parsed.is_ok() {
         return Err(parsed
-            .errors
-            .into_iter()
-            .map(|e| Diagnostic { code: "E2000".to_string(), message: e.message })
+            .errors()
+            .iter()
+            .map(|e| Diagnostic {
+                code: "E2000".to_string(),
+                message: e.message.clone(),
+                span: Some(Span { start: e.span.start, end: e.span.end }),
+            })
             .collect());
     }
-    let root = parsed.syntax();
-    let functions = collect_functions(&root);
-    let mut diags = Vec::new();
+    let root = parsed.ast();
+    let wide: Vec<Diagnostic> = root
+        .syntax()
+        .descendants()
+        .filter(|n| n.kind() == SyntaxKind::Type && matches!(n.text().to_string().trim(), "i128" | "u128"))
+        .map(|n| Diagnostic {
+            code: "E2007".to_string(),
+            message: "128-bit integers not supported by the wasm backend".to_string(),
+            span: Some(span_of(n.text_range())),
+        })
+        .collect();
+    if !wide.is_empty() {
+        return Err(wide);
+    }
+    let arrays: Vec<Diagnostic> = root
+        .syntax()
+        .descendants()
+        .filter(|n| matches!(n.kind(), SyntaxKind::ArrayType | SyntaxKind::ArrayExpr))
+        .map(|n| Diagnostic {
+            code: "E2010".to_string(),
+            message: "arrays not yet supported".to_string(),
+            span: Some(span_of(n.text_range())),
+        })
+        .collect();
+    if !arrays.is_empty() {
+        return Err(arrays);
+    }
+    // Each instantiation of a generic function is a separate wasm function, so no single one
+    // could take the export name.
+    let generic_exports: Vec<Diagnostic> = root
+        .items()
+        .filter_map(|item| match item {
+            Item::Fn(node) if generic_params(node.syntax()).is_some() => Some(node),
+            _ => None,
+        })
+        .flat_map(|node| node.syntax().children().filter(|n| n.kind() == SyntaxKind::Attribute).collect::<Vec<_>>())
+        .filter(|attr| attr.children().find(|n| n.kind() == SyntaxKind::IdentNode).and_then(find_ident_text).as_deref() == Some("export"))
+        .map(|attr| Diagnostic {
+            code: "E2015".to_string(),
+            message: "generic functions cannot be exported".to_string(),
+            span: Some(span_of(attr.text_range())),
+        })
+        .collect();
+    if !generic_exports.is_empty() {
+        return Err(generic_exports);
+    }
+    if has_module_attribute(&root, "no_host") {
+        let host_calls: Vec<Diagnostic> = root
+            .syntax()
+            .descendants()
+            .filter(|n| n.kind() == SyntaxKind::CallExpr)
+            .filter_map(|call| call.children().next())
+            .filter(|callee| callee.kind() == SyntaxKind::PathExpr)
+            .filter(|path| path.children().next().and_then(find_ident_text).is_some_and(|m| HOST_MODULES.contains(&m.as_str())))
+            .map(|path| Diagnostic {
+                code: "E2009".to_string(),
+                message: "host calls disallowed by @no_host".to_string(),
+                span: Some(span_of(path.text_range())),
+            })
+            .collect();
+        if !host_calls.is_empty() {
+            return Err(host_calls);
+        }
+    }
+    let discriminants = enum_discriminants(&root);
+    let mut aliases = type_aliases(&root);
+    // A fieldless enum is passed and returned as its `i32` discriminant.
+    for path in discriminants.keys() {
+        if let Some((name, _)) = path.split_once("::") {
+            aliases.insert(name.to_string(), "i32".to_string());
+        }
+    }
+    let structs = struct_fields(&root);
+    let mut functions = collect_functions(&root, &aliases, &structs);
+    monomorphize(&root, &aliases, &structs, &mut functions);
+    let wrappers = default_wrappers(&functions);
+    functions.extend(wrappers);
     if functions.is_empty() {
-        diags.push(Diagnostic { code: "E2001".to_string(), message: "no functions found".to_string() });
-        return Err(diags);
+        return Err(vec![Diagnostic { code: "E2001".to_string(), message: "no functions found".to_string(), span: None }]);
+    }
+    // Imported functions occupy the first indices of the function index space and unreachable
+    // ones the last, past everything the module defines; the sort is stable, so source order
+    // is kept within each group.
+    let unreachable = unreachable_functions(&functions);
+    functions.sort_by_key(|f| (f.import.is_none(), unreachable.contains(&f.name)));
+    Ok(Lowered { functions, discriminants, unreachable })
+}
+
+/// `type` alias names mapped to the text of the type they finally stand for.
+type TypeAliases = BTreeMap<String, String>;
+
+/// The aliases in `root`, each followed through other aliases. Cyclic aliases are left out;
+/// the type checker reports them.
+fn type_aliases(root: &Root) -> TypeAliases {
+    let direct: TypeAliases = root
+        .items()
+        .filter_map(|item| {
+            let Item::TypeAlias(decl) = item else {
+                return None;
+            };
+            let name = decl.syntax().children().find(|n| n.kind() == SyntaxKind::IdentNode).and_then(find_ident_text)?;
+            let target = decl.syntax().children().find(|n| n.kind() == SyntaxKind::Type)?;
+            Some((name, target.text().to_string().trim().to_string()))
+        })
+        .collect();
+    let mut out = TypeAliases::new();
+    for name in direct.keys() {
+        let mut seen = vec![name.as_str()];
+        let mut target = direct[name].as_str();
+        while let Some(next) = direct.get(target) {
+            if seen.contains(&target) {
+                break;
+            }
+            seen.push(target);
+            target = next;
+        }
+        if !direct.contains_key(target) {
+            out.insert(name.clone(), target.to_string());
+        }
+    }
+    out
+}
+
+/// `text`, or the type it stands for when it names an alias.
+fn resolve_alias<'a>(text: &'a str, aliases: &'a TypeAliases) -> &'a str {
+    let text = text.trim();
+    aliases.get(text).map(String::as_str).unwrap_or(text)
+}
+
+/// Each `struct`'s fields in declaration order, with the text of their types.
+type Structs = BTreeMap<String, Vec<(String, String)>>;
+
+fn struct_fields(root: &Root) -> Structs {
+    root.items()
+        .filter_map(|item| {
+            let Item::Struct(decl) = item else {
+                return None;
+            };
+            let name = decl.syntax().children().find(|n| n.kind() == SyntaxKind::IdentNode).and_then(find_ident_text)?;
+            let fields = decl
+                .syntax()
+                .children()
+                .filter(|n| n.kind() == SyntaxKind::StructField)
+                .filter_map(|field| {
+                    let field_name = field.children().find(|n| n.kind() == SyntaxKind::IdentNode).and_then(find_ident_text)?;
+                    let ty = field.children().find(|n| n.kind() == SyntaxKind::Type)?;
+                    Some((field_name, ty.text().to_string().trim().to_string()))
+                })
+                .collect();
+            Some((name, fields))
+        })
+        .collect()
+}
+
+/// Discriminants of the fieldless enums in `root`. A variant without `= N` takes the previous
+/// value plus one, starting from 0.
+fn enum_discriminants(root: &Root) -> BTreeMap<String, i32> {
+    let mut out = BTreeMap::new();
+    for item in root.items() {
+        let Item::Enum(decl) = item else {
+            continue;
+        };
+        let Some(name) = decl.syntax().children().find(|n| n.kind() == SyntaxKind::IdentNode).and_then(find_ident_text) else {
+            continue;
+        };
+        let variants: Vec<_> = decl.syntax().children().filter(|n| n.kind() == SyntaxKind::EnumVariant).collect();
+        if variants.iter().any(|v| v.children().any(|n| n.kind() == SyntaxKind::Type)) {
+            continue;
+        }
+        let mut next: i128 = 0;
+        for variant in variants {
+            let value = variant
+                .children()
+                .find(|n| n.kind() == SyntaxKind::LiteralNode)
+                .and_then(|n| literal_value(&n))
+                .and_then(|(_, text)| int_literal_value(&text))
+                .unwrap_or(next);
+            let variant_name = variant.children().find(|n| n.kind() == SyntaxKind::IdentNode).and_then(find_ident_text);
+            if let (Some(variant_name), Ok(value)) = (variant_name, i32::try_from(value)) {
+                out.insert(format!("{name}::{variant_name}"), value);
+            }
+            next = value + 1;
+        }
+    }
+    out
+}
+
+/// Decodes the `jalm.sourcemap` section of a module built with `debug: true`; `None` when
+/// the module has no such section or is malformed.
+pub fn read_source_map(wasm: &[u8]) -> Option<Vec<SourceMapEntry>> {
+    let mut pos = 8;
+    while pos < wasm.len() {
+        let id = wasm[pos];
+        pos += 1;
+        let size = read_leb_u32(wasm, &mut pos)? as usize;
+        let section = wasm.get(pos..pos + size)?;
+        pos += size;
+        if id != 0 {
+            continue;
+        }
+        let mut name_pos = 0;
+        let name_len = read_leb_u32(section, &mut name_pos)? as usize;
+        if section.get(name_pos..name_pos + name_len)? != SOURCE_MAP_SECTION.as_bytes() {
+            continue;
+        }
+        let words: Vec<u32> = section[name_pos + name_len..]
+            .chunks_exact(4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        return Some(
+            words
+                .chunks_exact(4)
+                .map(|w| SourceMapEntry {
+                    func_index: w[0],
+                    offset: w[1],
+                    span: Span { start: w[2] as usize, end: w[3] as usize },
+                })
+                .collect(),
+        );
+    }
+    None
+}
+
+fn read_leb_u32(bytes: &[u8], pos: &mut usize) -> Option<u32> {
+    let mut value = 0u32;
+    for shift in (0..35).step_by(7) {
+        let byte = *bytes.get(*pos)?;
+        *pos += 1;
+        value |= u32::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
     }
+    None
+}
+
+pub fn compile_to_wasm(source: &str) -> Result<Vec<u8>, Vec<Diagnostic>> {
+    compile_to_wasm_with_options(source, &CompileOptions::default())
+}
+
+pub fn compile_to_wasm_with_options(source: &str, options: &CompileOptions) -> Result<Vec<u8>, Vec<Diagnostic>> {
+    compile_parsed(&parse_with_features(source, &options.features), options)
+}
+
+/// Compiles an already parsed file, such as the one a caller has just checked. The parse
+/// decides which `@cfg` items are present, so `options.features` is not consulted.
+pub fn compile_parsed(parsed: &Parse, options: &CompileOptions) -> Result<Vec<u8>, Vec<Diagnostic>> {
+    let Lowered { mut functions, discriminants, unreachable } = lower_parsed(parsed)?;
+    let mut diags = Vec::new();
+    let mut source_map = Vec::new();
 
     let mut types = TypeSection::new();
+    let mut imports = ImportSection::new();
     let mut funcs = FunctionSection::new();
     let mut code = CodeSection::new();
     let mut exports = ExportSection::new();
 
-    let mut func_indices = std::collections::HashMap::new();
+    let mut func_indices = BTreeMap::new();
+    let signatures: Signatures = functions
+        .iter()
+        .map(|f| (f.name.clone(), (f.params.iter().map(|p| p.ty.unwrap_or(ValType::I64)).collect(), f.ret.clone())))
+        .collect();
+    for f in &mut functions {
+        resolve_let_types(f, &signatures);
+    }
 
+    let unsigned_fns: BTreeSet<String> = functions.iter().filter(|f| f.ret_unsigned).map(|f| f.name.clone()).collect();
+    let never_fns: BTreeSet<String> = functions.iter().filter(|f| f.diverges).map(|f| f.name.clone()).collect();
+
+    let mut data = Vec::new();
+    let mut uses_memory = false;
+    // Checked division imports `jalm_panic` after the program's own imports, shifting the
+    // defined functions up by one.
+    let import_count = functions.iter().filter(|f| f.import.is_some()).count() as u32;
+    let divides = functions
+        .iter()
+        .filter(|f| !unreachable.contains(&f.name))
+        .any(|f| f.body.iter().any(stmt_divides) || f.params.iter().filter_map(|p| p.default.as_ref()).any(expr_divides));
+    let panic_import = (options.checked_arithmetic && divides).then_some(import_count);
     for (idx, f) in functions.iter().enumerate() {
-        func_indices.insert(f.name.clone(), idx as u32);
+        let idx = idx as u32;
+        let shift = u32::from(panic_import.is_some() && idx >= import_count);
+        func_indices.insert(f.name.clone(), idx + shift);
     }
 
     for f in &functions {
         let (params, result) = signature_from_fn(f, &mut diags);
+        let live = !unreachable.contains(&f.name);
         let type_index = types.len();
-        types.function(params.clone(), result.clone());
-        funcs.function(type_index);
+        if live {
+            types.function(params.clone(), result.clone());
+        }
+        if let Some((module, field)) = &f.import {
+            imports.import(module, field, EntityType::Function(type_index));
+            continue;
+        }
+        if !f.has_body {
+            diags.push(Diagnostic {
+                code: "E2006".to_string(),
+                message: format!("function {} has no body; bodiless functions need @extern", f.name),
+                span: Some(f.span),
+            });
+            continue;
+        }
+        if live {
+            funcs.function(type_index);
+        }
 
         let mut locals = Vec::new();
         for (_, ty) in &f.locals {
             locals.push((1, *ty));
         }
+        // A scratch local holds the divisor while it is compared with zero.
+        let div_check = panic_import.map(|panic| {
+            locals.push((1, ValType::I64));
+            (panic, (f.params.len() + f.locals.len()) as u32)
+        });
         let mut body = Function::new(locals);
-        let mut ctx = EmitCtx { func_indices: &func_indices, locals: &f.locals, params: &f.params, diagnostics: &mut diags };
+        let mut ctx = EmitCtx {
+            func_indices: &func_indices,
+            signatures: &signatures,
+            discriminants: &discriminants,
+            data: &mut data,
+            uses_memory: &mut uses_memory,
+            locals: &f.locals,
+            params: &f.params,
+            ret: &f.ret,
+            unsigned: f.params.iter().filter(|p| p.unsigned).map(|p| p.name.clone()).collect(),
+            unsigned_fns: &unsigned_fns,
+            never_fns: &never_fns,
+            div_check,
+            spans: Vec::new(),
+            struct_locals: Vec::new(),
+            diagnostics: &mut diags,
+        };
         for stmt in &f.body {
             emit_stmt(&mut body, &mut ctx, stmt);
         }
-        if !matches!(f.ret, Some(ValType::I64)) {
-            // default return 0 for now
-            body.instruction(&Instruction::I64Const(0));
+        if !live {
+            // Emitted only for its diagnostics; its calls may name indices past the module's.
+            continue;
         }
+        let func_index = func_indices[&f.name];
+        source_map.extend(ctx.spans.into_iter().map(|(offset, span)| SourceMapEntry { func_index, offset, span }));
         body.instruction(&Instruction::End);
         code.function(&body);
 
-        if f.name == "main" {
-            exports.export("main", ExportKind::Func, func_indices["main"]);
+        if let Some(export) = export_name(f) {
+            exports.export(export, ExportKind::Func, func_indices[&f.name]);
         }
     }
 
+    if panic_import.is_some() {
+        let type_index = types.len();
+        types.function(vec![ValType::I32], vec![]);
+        imports.import("env", "jalm_panic", EntityType::Function(type_index));
+    }
+
+    let mut memories = MemorySection::new();
+    let mut data_section = DataSection::new();
+    if !data.is_empty() || uses_memory {
+        let pages = (DATA_BASE as u64 + data.len() as u64).div_ceil(WASM_PAGE_SIZE);
+        memories.memory(MemoryType { minimum: pages, maximum: None, memory64: false, shared: false });
+        exports.export("memory", ExportKind::Memory, 0);
+        data_section.active(0, &ConstExpr::i32_const(DATA_BASE as i32), data);
+    }
+
     if diags.is_empty() {
         let mut module = Module::new();
         module.section(&types);
+        if !imports.is_empty() {
+            module.section(&imports);
+        }
         module.section(&funcs);
+        if !memories.is_empty() {
+            module.section(&memories);
+        }
         module.section(&exports);
         module.section(&code);
+        if !data_section.is_empty() {
+            module.section(&data_section);
+        }
+        if options.debug {
+            let mut data = Vec::new();
+            for entry in &source_map {
+                for field in [entry.func_index, entry.offset, entry.span.start as u32, entry.span.end as u32] {
+                    data.extend_from_slice(&field.to_le_bytes());
+                }
+            }
+            module.section(&CustomSection { name: SOURCE_MAP_SECTION.into(), data: data.into() });
+        }
         Ok(module.finish())
     } else {
         Err(diags)
@@ -78,223 +540,814 @@ pub fn compile_to_wasm(source: &str) -> Result<Vec<u8>, Vec<Diagnostic>> {
 #[derive(Debug, Clone)]
 struct FnDef {
     name: String,
-    params: Vec<(String, ValType)>,
+    params: Vec<ParamDef>,
     locals: Vec<(String, ValType)>,
     body: Vec<Stmt>,
-    ret: Option<ValType>,
+    /// Wasm results: empty for `()` (or an unsupported type), several for a tuple.
+    ret: Vec<ValType>,
+    /// Declared `-> u64`, so callers treat the result as unsigned.
+    ret_unsigned: bool,
+    /// Declared `-> never`.
+    diverges: bool,
+    ret_span: Option<Span>,
+    span: Span,
+    has_body: bool,
+    /// Export name from `@export("name")`.
+    export: Option<String>,
+    /// `(module, field)` from `@extern("module", "field")`.
+    import: Option<(String, String)>,
+}
+
+#[derive(Debug, Clone)]
+struct ParamDef {
+    name: String,
+    /// `None` when the declared type has no wasm mapping.
+    ty: Option<ValType>,
+    /// Declared `u64`; wasm `i64` has no sign, the instructions do.
+    unsigned: bool,
+    span: Span,
+    /// Computed by the wrapper that calls this function without this argument.
+    default: Option<Expr>,
 }
 
 #[derive(Debug, Clone)]
 enum Stmt {
-    Let { name: String, expr: Expr },
+    /// `unsigned` is whether the annotation is `u64`, or `None` to follow the initializer.
+    Let { name: String, expr: Expr, unsigned: Option<bool> },
+    /// `let (a, _, c) = expr;` over a tuple value; `None` for a `_` element. `annotated` is
+    /// whether the element types come from a type annotation rather than the initializer.
+    LetTuple { names: Vec<Option<String>>, expr: Expr, annotated: bool },
+    Assign { name: String, expr: Expr },
     Return(Expr),
     Expr(Expr),
     If { cond: Expr, then_body: Vec<Stmt>, else_body: Vec<Stmt> },
+    /// `let p = P { .. };`: struct values only arrive as parameters, one local per field.
+    StructLocal { name: String, span: Span },
 }
 
 #[derive(Debug, Clone)]
 enum Expr {
     Int(i64),
+    Float(f64),
     Bool(bool),
-    Ident(String),
-    Bin { op: SyntaxKind, lhs: Box<Expr>, rhs: Box<Expr> },
-    Call { name: String, args: Vec<Expr> },
+    /// Decoded `b"..."` contents, placed in the data section.
+    Bytes(Vec<u8>),
+    Ident { name: String, span: Span },
+    /// `a::b`, joined with `::`; only enum variants have a value.
+    Path { path: String, span: Span },
+    Bin { op: SyntaxKind, lhs: Box<Expr>, rhs: Box<Expr>, span: Span },
+    /// `instance` is set for `f::<T, U>(..)`, whose `name` is the instantiation's, `f::<T, U>`.
+    Call { name: String, instance: Option<Instance>, args: Vec<Expr>, span: Span },
+    /// Pushes each element in order, e.g. the multi-value result of `return (q, r);`.
+    Tuple(Vec<Expr>),
+    /// An expression the backend cannot lower, such as a closure or a struct literal.
+    Unsupported { span: Span },
 }
 
-fn collect_functions(root: &SyntaxNode) -> Vec<FnDef> {
+/// A call's generic function and its type arguments, each resolved through aliases.
+#[derive(Debug, Clone)]
+struct Instance {
+    generic: String,
+    type_args: Vec<String>,
+}
+
+/// The non-generic functions and methods in `root`; generic ones are lowered per instantiation by `monomorphize`.
+fn collect_functions(root: &Root, aliases: &TypeAliases, structs: &Structs) -> Vec<FnDef> {
     let mut out = Vec::new();
-    for node in root.children().filter(|n| n.kind() == SyntaxKind::FnDecl) {
-        if let Some(f) = lower_fn(&node) {
-            out.push(f);
+    for item in root.items() {
+        let fns = match item {
+            Item::Fn(node) => vec![node],
+            Item::Impl(block) => block.methods().collect(),
+            _ => continue,
+        };
+        for node in fns {
+            if generic_params(node.syntax()).is_some() {
+                continue;
+            }
+            if let Some(f) = lower_fn(node.syntax(), aliases, structs) {
+                out.push(f);
+            }
         }
     }
     out
 }
 
-fn lower_fn(node: &SyntaxNode) -> Option<FnDef> {
+/// For a function whose trailing parameters have defaults, one wrapper per shorter argument list:
+/// `f#k` takes the first `k` arguments, computes the rest in order so each default sees the
+/// parameters before it, and calls `f` with all of them.
+fn default_wrappers(functions: &[FnDef]) -> Vec<FnDef> {
+    let mut out = Vec::new();
+    for f in functions.iter().filter(|f| f.has_body && f.import.is_none()) {
+        let required = f.params.iter().rposition(|p| p.default.is_none()).map_or(0, |idx| idx + 1);
+        for given in required..f.params.len() {
+            let omitted = &f.params[given..];
+            let mut body: Vec<Stmt> = omitted
+                .iter()
+                .filter_map(|p| Some(Stmt::Let { name: p.name.clone(), expr: p.default.clone()?, unsigned: Some(p.unsigned) }))
+                .collect();
+            let args = f.params.iter().map(|p| Expr::Ident { name: p.name.clone(), span: p.span }).collect();
+            body.push(Stmt::Return(Expr::Call { name: f.name.clone(), instance: None, args, span: f.span }));
+            out.push(FnDef {
+                name: default_wrapper_name(&f.name, given),
+                params: f.params[..given].to_vec(),
+                locals: omitted.iter().map(|p| (p.name.clone(), p.ty.unwrap_or(ValType::I64))).collect(),
+                body,
+                ret: f.ret.clone(),
+                ret_unsigned: f.ret_unsigned,
+                diverges: f.diverges,
+                ret_span: f.ret_span,
+                span: f.span,
+                has_body: true,
+                export: None,
+                import: None,
+            });
+        }
+    }
+    out
+}
+
+/// The defined functions that no exported function calls, directly or through other functions.
+fn unreachable_functions(functions: &[FnDef]) -> BTreeSet<String> {
+    let mut reachable: BTreeSet<String> = functions.iter().filter(|f| export_name(f).is_some()).map(|f| f.name.clone()).collect();
+    let mut pending: Vec<String> = reachable.iter().cloned().collect();
+    while let Some(name) = pending.pop() {
+        let Some(f) = functions.iter().find(|f| f.name == name) else {
+            continue;
+        };
+        let mut callees: Vec<String> = fn_calls(f).into_iter().map(|(callee, _)| callee).collect();
+        // A call that omits defaulted arguments is emitted as a call to one of the wrappers.
+        callees.extend(
+            functions
+                .iter()
+                .filter(|g| g.name.strip_prefix(name.as_str()).is_some_and(|rest| rest.starts_with('#')))
+                .map(|g| g.name.clone()),
+        );
+        for callee in callees {
+            if reachable.insert(callee.clone()) {
+                pending.push(callee);
+            }
+        }
+    }
+    functions.iter().filter(|f| f.import.is_none() && !reachable.contains(&f.name)).map(|f| f.name.clone()).collect()
+}
+
+fn default_wrapper_name(name: &str, given: usize) -> String {
+    format!("{name}#{given}")
+}
+
+/// Appends a copy of each generic function for every distinct list of type arguments it is called
+/// with, named as at the call site (`id::<i64>`) and lowered with each type parameter standing for
+/// its argument. A copy may itself call other instantiations, so the new functions are scanned too.
+fn monomorphize(root: &Root, aliases: &TypeAliases, structs: &Structs, functions: &mut Vec<FnDef>) {
+    let generics: BTreeMap<String, (SyntaxNode, Vec<String>)> = root
+        .items()
+        .filter_map(|item| {
+            let Item::Fn(node) = item else {
+                return None;
+            };
+            let params = generic_params(node.syntax())?;
+            let name = node.syntax().children().find(|n| n.kind() == SyntaxKind::IdentNode).and_then(find_ident_text)?;
+            Some((name, (node.syntax().clone(), params)))
+        })
+        .collect();
+    if generics.is_empty() {
+        return;
+    }
+    let mut instantiated = BTreeSet::new();
+    let mut next = 0;
+    while next < functions.len() {
+        let calls = fn_calls(&functions[next]);
+        next += 1;
+        for (call, instance) in calls {
+            let Some(instance) = instance else {
+                continue;
+            };
+            let Some((node, params)) = generics.get(&instance.generic) else {
+                continue;
+            };
+            if instance.type_args.len() != params.len() || !instantiated.insert(call.clone()) {
+                continue;
+            }
+            let mut bound = aliases.clone();
+            bound.extend(params.iter().cloned().zip(instance.type_args));
+            if let Some(mut f) = lower_fn(node, &bound, structs) {
+                f.name = call;
+                functions.push(f);
+            }
+        }
+    }
+}
+
+/// Names of the type parameters in a `fn name<T, U>` declaration, or `None` for a plain function.
+fn generic_params(node: &SyntaxNode) -> Option<Vec<String>> {
+    let list = node.children().find(|n| n.kind() == SyntaxKind::GenericParamList)?;
+    Some(list.children().filter_map(find_ident_text).collect())
+}
+
+/// The functions `f` calls, in its body and in its parameter defaults, each with the
+/// instantiation it names if the callee is generic.
+fn fn_calls(f: &FnDef) -> Vec<(String, Option<Instance>)> {
+    let mut calls = Vec::new();
+    for stmt in &f.body {
+        stmt_calls(stmt, &mut calls);
+    }
+    for default in f.params.iter().filter_map(|p| p.default.as_ref()) {
+        expr_calls(default, &mut calls);
+    }
+    calls
+}
+
+fn stmt_calls(stmt: &Stmt, out: &mut Vec<(String, Option<Instance>)>) {
+    match stmt {
+        Stmt::Let { expr, .. } | Stmt::LetTuple { expr, .. } | Stmt::Assign { expr, .. } | Stmt::Return(expr) | Stmt::Expr(expr) => {
+            expr_calls(expr, out)
+        }
+        Stmt::If { cond, then_body, else_body } => {
+            expr_calls(cond, out);
+            for stmt in then_body.iter().chain(else_body) {
+                stmt_calls(stmt, out);
+            }
+        }
+        Stmt::StructLocal { .. } => {}
+    }
+}
+
+fn expr_calls(expr: &Expr, out: &mut Vec<(String, Option<Instance>)>) {
+    match expr {
+        Expr::Call { name, instance, args, .. } => {
+            out.push((name.clone(), instance.clone()));
+            for arg in args {
+                expr_calls(arg, out);
+            }
+        }
+        Expr::Bin { lhs, rhs, .. } => {
+            expr_calls(lhs, out);
+            expr_calls(rhs, out);
+        }
+        Expr::Tuple(elems) => {
+            for elem in elems {
+                expr_calls(elem, out);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn lower_fn(node: &SyntaxNode, aliases: &TypeAliases, structs: &Structs) -> Option<FnDef> {
     let name = node
         .children()
         .find(|n| n.kind() == SyntaxKind::IdentNode)
-        .and_then(|n| find_ident_text(n))?;
+        .and_then(find_ident_text)?;
+    // A method is a plain function named `Type::method` whose first parameter is `self`.
+    let impl_type = node
+        .parent()
+        .filter(|n| n.kind() == SyntaxKind::ImplBlock)
+        .and_then(|block| block.children().find(|n| n.kind() == SyntaxKind::IdentNode))
+        .and_then(find_ident_text);
+    let name = match &impl_type {
+        Some(ty) => format!("{ty}::{name}"),
+        None => name,
+    };
 
-    let params = node
-        .children()
-        .find(|n| n.kind() == SyntaxKind::ParamList)
-        .map(lower_params)
+    let param_list = node.children().find(|n| n.kind() == SyntaxKind::ParamList);
+    let receivers = param_list
+        .as_ref()
+        .map(|list| struct_params(list, aliases, structs, impl_type.as_deref()))
         .unwrap_or_default();
+    let params = param_list.map(|list| lower_params(list, aliases, structs, &receivers)).unwrap_or_default();
 
-    let ret = find_return_type(node).and_then(map_type);
+    // `never` has no wasm results, like `()`; calls to such a function end in `unreachable`.
+    let diverges = find_return_type(node).is_some_and(|n| resolve_alias(&n.text().to_string(), aliases) == "never");
+    let ret_node = find_return_type(node).filter(|n| n.text().to_string().trim() != "()" && !diverges);
+    let ret = ret_node.as_ref().and_then(|n| map_types(n, aliases)).unwrap_or_default();
+    let ret_span = ret_node.map(|n| span_of(n.text_range()));
 
     let mut locals = Vec::new();
     let mut body = Vec::new();
-    if let Some(block) = node.children().find(|n| n.kind() == SyntaxKind::Block) {
-        lower_block(block, &mut locals, &mut body);
+    let block = node.children().find(|n| n.kind() == SyntaxKind::Block);
+    let has_body = block.is_some();
+    if let Some(block) = block {
+        // The body's trailing expression is the function's value.
+        let tail = block_tail(&block).and_then(|n| lower_expr(n, aliases));
+        lower_block(block, aliases, &mut locals, &mut body);
+        if let Some(tail) = tail {
+            body.push(if ret.is_empty() { Stmt::Expr(tail) } else { Stmt::Return(tail) });
+        }
+        for stmt in &mut body {
+            stmt_pass_fields(stmt, &receivers, structs);
+        }
+    }
+
+    let mut export = None;
+    let mut import = None;
+    for attr in node.children().filter(|n| n.kind() == SyntaxKind::Attribute) {
+        let attr_name = attr.children().find(|n| n.kind() == SyntaxKind::IdentNode).and_then(find_ident_text);
+        let args: Vec<String> = attr
+            .children()
+            .filter(|n| n.kind() == SyntaxKind::LiteralNode)
+            .filter_map(|n| literal_value(&n))
+            .filter_map(|(_, text)| decode_string(&text).ok())
+            .collect();
+        match (attr_name.as_deref(), args.as_slice()) {
+            (Some("export"), [export_name]) => export = Some(export_name.clone()),
+            (Some("extern"), [module, field]) => import = Some((module.clone(), field.clone())),
+            _ => {}
+        }
     }
 
-    Some(FnDef { name, params, locals, body, ret })
+    Some(FnDef {
+        name,
+        params,
+        locals,
+        body,
+        ret,
+        ret_unsigned: find_return_type(node).is_some_and(|n| resolve_alias(&n.text().to_string(), aliases) == "u64"),
+        diverges,
+        ret_span,
+        span: span_of(node.text_range()),
+        has_body,
+        export,
+        import,
+    })
+}
+
+/// Struct-typed parameters, including a method's `self`, mapped to their struct's name.
+fn struct_params(node: &SyntaxNode, aliases: &TypeAliases, structs: &Structs, impl_type: Option<&str>) -> BTreeMap<String, String> {
+    node.children()
+        .filter(|n| n.kind() == SyntaxKind::Param)
+        .filter_map(|param| {
+            let name = param.children().find(|n| n.kind() == SyntaxKind::IdentNode).and_then(find_ident_text)?;
+            let ty = match param.children().find(|n| n.kind() == SyntaxKind::Type) {
+                Some(ty) => resolve_alias(&ty.text().to_string(), aliases).to_string(),
+                None if name == "self" => impl_type?.to_string(),
+                None => return None,
+            };
+            structs.contains_key(&ty).then_some((name, ty))
+        })
+        .collect()
 }
 
-fn lower_params(node: SyntaxNode) -> Vec<(String, ValType)> {
+/// A struct parameter `p` becomes one parameter per field, `p.x`, `p.y`, ..., in declaration order.
+fn lower_params(node: SyntaxNode, aliases: &TypeAliases, structs: &Structs, receivers: &BTreeMap<String, String>) -> Vec<ParamDef> {
     let mut out = Vec::new();
     for param in node.children().filter(|n| n.kind() == SyntaxKind::Param) {
-        if let (Some(name), Some(ty)) = (
-            param.children().find(|n| n.kind() == SyntaxKind::IdentNode).and_then(|n| find_ident_text(n)),
-            param.children().find(|n| n.kind() == SyntaxKind::Type).and_then(|n| map_type(n.text().to_string())),
+        let name = param.children().find(|n| n.kind() == SyntaxKind::IdentNode).and_then(find_ident_text);
+        if let Some((name, fields)) = name.as_ref().and_then(|name| Some((name, &structs[receivers.get(name)?]))) {
+            for (field, ty) in fields {
+                out.push(ParamDef {
+                    name: format!("{name}.{field}"),
+                    ty: map_type(ty, aliases),
+                    unsigned: resolve_alias(ty, aliases) == "u64",
+                    span: span_of(param.text_range()),
+                    default: None,
+                });
+            }
+            continue;
+        }
+        if let (Some(name), Some(ty_node)) = (
+            param.children().find(|n| n.kind() == SyntaxKind::IdentNode).and_then(find_ident_text),
+            param.children().find(|n| n.kind() == SyntaxKind::Type),
         ) {
-            out.push((name, ty));
+            let text = ty_node.text().to_string();
+            let ty = map_type(&text, aliases);
+            let unsigned = resolve_alias(&text, aliases) == "u64";
+            let default = find_expr_after_token(&param, SyntaxKind::Eq).and_then(|n| lower_expr(n, aliases));
+            out.push(ParamDef { name, ty, unsigned, span: span_of(param.text_range()), default });
         }
     }
     out
 }
 
-fn lower_block(node: SyntaxNode, locals: &mut Vec<(String, ValType)>, out: &mut Vec<Stmt>) {
+fn lower_block(node: SyntaxNode, aliases: &TypeAliases, locals: &mut Vec<(String, ValType)>, out: &mut Vec<Stmt>) {
     if let Some(stmts) = node.children().find(|n| n.kind() == SyntaxKind::StmtList) {
         for stmt in stmts.children() {
             match stmt.kind() {
+                SyntaxKind::LetStmt if is_tuple_let(&stmt) => {
+                    if let Some(stmt_tuple) = lower_tuple_let(&stmt, aliases, locals) {
+                        out.push(stmt_tuple);
+                    }
+                }
+                SyntaxKind::LetStmt if stmt.children().any(|n| n.kind() == SyntaxKind::StructLit) => {
+                    if let Some(name) = stmt.children().find(|n| n.kind() == SyntaxKind::Pattern).and_then(find_ident_text) {
+                        out.push(Stmt::StructLocal { name, span: span_of(stmt.text_range()) });
+                    }
+                }
                 SyntaxKind::LetStmt => {
                     if let (Some(name), Some(expr)) = (
-                        stmt.children().find(|n| n.kind() == SyntaxKind::Pattern).and_then(|n| find_ident_text(n)),
-                        stmt.children().find(|n| is_expr_kind(n.kind())).and_then(lower_expr),
+                        stmt.children().find(|n| n.kind() == SyntaxKind::Pattern).and_then(find_ident_text),
+                        stmt.children().find(|n| is_expr_kind(n.kind())).and_then(|n| lower_expr(n, aliases)),
                     ) {
-                        let ty = stmt
-                            .children()
-                            .find(|n| n.kind() == SyntaxKind::Type)
-                            .and_then(|n| map_type(n.text().to_string()))
-                            .unwrap_or(ValType::I64);
+                        let annotation = stmt.children().find(|n| n.kind() == SyntaxKind::Type).map(|n| n.text().to_string());
+                        // An unannotated local is typed by `resolve_let_types` once every signature is known.
+                        let ty = annotation.as_ref().and_then(|text| map_type(text, aliases)).unwrap_or(ValType::I64);
+                        let unsigned = annotation.map(|text| resolve_alias(&text, aliases) == "u64");
                         locals.push((name.clone(), ty));
-                        out.push(Stmt::Let { name, expr });
+                        out.push(Stmt::Let { name, expr, unsigned });
                     }
                 }
                 SyntaxKind::ReturnStmt => {
-                    if let Some(expr) = stmt.children().find(|n| is_expr_kind(n.kind())).and_then(lower_expr) {
+                    if let Some(expr) = stmt.children().find(|n| is_expr_kind(n.kind())).and_then(|n| lower_expr(n, aliases)) {
                         out.push(Stmt::Return(expr));
                     }
                 }
                 SyntaxKind::IfExpr => {
-                    if let Some(stmt_if) = lower_if(stmt) {
+                    if let Some(stmt_if) = lower_if(stmt, aliases) {
                         out.push(stmt_if);
                     }
                 }
-                SyntaxKind::ExprStmt => {
-                    if let Some(expr) = stmt.children().find(|n| is_expr_kind(n.kind())).and_then(lower_expr) {
-                        out.push(Stmt::Expr(expr));
+                SyntaxKind::ExprStmt => match stmt.children().find(|n| is_expr_kind(n.kind())) {
+                    Some(assign) if assign.kind() == SyntaxKind::AssignExpr => {
+                        if let Some(stmt_assign) = lower_assign(assign, aliases) {
+                            out.push(stmt_assign);
+                        }
                     }
-                }
+                    Some(expr) => {
+                        if let Some(expr) = lower_expr(expr, aliases) {
+                            out.push(Stmt::Expr(expr));
+                        }
+                    }
+                    None => {}
+                },
                 _ => {}
             }
         }
     }
 }
 
-fn lower_if(node: SyntaxNode) -> Option<Stmt> {
+fn is_tuple_let(stmt: &SyntaxNode) -> bool {
+    stmt.children()
+        .find(|n| n.kind() == SyntaxKind::Pattern)
+        .is_some_and(|p| p.children().any(|n| n.kind() == SyntaxKind::Pattern))
+}
+
+/// Lowers `let (a, b) = expr;`. Element types come from the annotation; without one,
+/// `resolve_let_types` takes them from the elements of a tuple literal or the callee's results.
+fn lower_tuple_let(stmt: &SyntaxNode, aliases: &TypeAliases, locals: &mut Vec<(String, ValType)>) -> Option<Stmt> {
+    let pattern = stmt.children().find(|n| n.kind() == SyntaxKind::Pattern)?;
+    let expr = stmt.children().find(|n| is_expr_kind(n.kind())).and_then(|n| lower_expr(n, aliases))?;
+    let annotated = stmt.children().find(|n| n.kind() == SyntaxKind::Type).and_then(|n| map_types(&n, aliases));
+    let names: Vec<Option<String>> = pattern.children().filter(|n| n.kind() == SyntaxKind::Pattern).map(find_ident_text).collect();
+    for (idx, name) in names.iter().enumerate() {
+        let ty = annotated.as_ref().and_then(|types| types.get(idx).copied());
+        if let Some(name) = name {
+            locals.push((name.clone(), ty.unwrap_or(ValType::I64)));
+        }
+    }
+    Some(Stmt::LetTuple { names, expr, annotated: annotated.is_some() })
+}
+
+/// Trailing value expression of a block; `if`/`match` tails are lowered as statements.
+fn block_tail(block: &SyntaxNode) -> Option<SyntaxNode> {
+    let last = block.children().find(|n| n.kind() == SyntaxKind::StmtList)?.children().last()?;
+    let is_value = is_expr_kind(last.kind())
+        && !matches!(last.kind(), SyntaxKind::IfExpr | SyntaxKind::MatchExpr | SyntaxKind::Block);
+    is_value.then_some(last)
+}
+
+fn lower_if(node: SyntaxNode, aliases: &TypeAliases) -> Option<Stmt> {
     let mut kids = node.children();
-    let cond = kids.next().and_then(lower_expr)?;
+    let cond = kids.next().and_then(|n| lower_expr(n, aliases))?;
     let then_block = kids.next()?;
     let else_block = kids.next();
     let mut then_body = Vec::new();
     let mut else_body = Vec::new();
-    lower_block(then_block, &mut Vec::new(), &mut then_body);
+    lower_block(then_block, aliases, &mut Vec::new(), &mut then_body);
     if let Some(else_node) = else_block {
         if else_node.kind() == SyntaxKind::IfExpr {
-            if let Some(nested) = lower_if(else_node) {
+            if let Some(nested) = lower_if(else_node, aliases) {
                 else_body.push(nested);
             }
         } else {
-            lower_block(else_node, &mut Vec::new(), &mut else_body);
+            lower_block(else_node, aliases, &mut Vec::new(), &mut else_body);
         }
     }
     Some(Stmt::If { cond, then_body, else_body })
 }
 
-fn lower_expr(node: SyntaxNode) -> Option<Expr> {
+/// Lowers `x = e` and `x op= e` (as `x = x op e`) for local targets.
+fn lower_assign(node: SyntaxNode, aliases: &TypeAliases) -> Option<Stmt> {
+    let mut kids = node.children();
+    let target = kids.next().filter(|n| n.kind() == SyntaxKind::IdentNode)?;
+    let name = find_ident_text(target.clone())?;
+    let value = kids.next().and_then(|n| lower_expr(n, aliases))?;
+    let op = node.children_with_tokens().find_map(|e| match e {
+        SyntaxElement::Token(t) if t.kind().is_assign_op() => Some(t.kind()),
+        _ => None,
+    })?;
+    let expr = match op.compound_op() {
+        Some(bin) => {
+            let current = Expr::Ident { name: name.clone(), span: span_of(target.text_range()) };
+            Expr::Bin { op: bin, lhs: Box::new(current), rhs: Box::new(value), span: span_of(node.text_range()) }
+        }
+        None => value,
+    };
+    Some(Stmt::Assign { name, expr })
+}
+
+fn lower_expr(node: SyntaxNode, aliases: &TypeAliases) -> Option<Expr> {
     match node.kind() {
         SyntaxKind::LiteralNode => {
-            for el in node.children_with_tokens() {
-                if let SyntaxElement::Token(t) = el {
-                    return match t.kind() {
-                        SyntaxKind::Int => t.text().parse::<i64>().ok().map(Expr::Int),
-                        SyntaxKind::KwTrue => Some(Expr::Bool(true)),
-                        SyntaxKind::KwFalse => Some(Expr::Bool(false)),
-                        _ => None,
-                    };
-                }
+            let (kind, text) = literal_value(&node)?;
+            match kind {
+                SyntaxKind::Int => int_literal_value(&text).and_then(|v| i64::try_from(v).ok()).map(Expr::Int),
+                SyntaxKind::Float => text.replace('_', "").parse().ok().map(Expr::Float),
+                SyntaxKind::KwTrue => Some(Expr::Bool(true)),
+                SyntaxKind::KwFalse => Some(Expr::Bool(false)),
+                SyntaxKind::Bytes => decode_byte_string(&text).ok().map(Expr::Bytes),
+                _ => None,
             }
-            None
         }
-        SyntaxKind::IdentNode => find_ident_text(node).map(Expr::Ident),
+        SyntaxKind::IdentNode => {
+            let span = span_of(node.text_range());
+            find_ident_text(node).map(|name| Expr::Ident { name, span })
+        }
         SyntaxKind::BinExpr => {
             let mut children = node.children();
-            let lhs = children.next().and_then(lower_expr)?;
-            let rhs = children.next().and_then(lower_expr)?;
+            let lhs = children.next().and_then(|n| lower_expr(n, aliases))?;
+            let rhs = children.next().and_then(|n| lower_expr(n, aliases))?;
             let op = node.children_with_tokens().find_map(|e| match e {
                 SyntaxElement::Token(t) if is_bin_op(t.kind()) => Some(t.kind()),
                 _ => None,
             })?;
-            Some(Expr::Bin { op, lhs: Box::new(lhs), rhs: Box::new(rhs) })
+            Some(Expr::Bin { op, lhs: Box::new(lhs), rhs: Box::new(rhs), span: span_of(node.text_range()) })
         }
         SyntaxKind::CallExpr => {
+            let span = span_of(node.text_range());
             let mut kids = node.children();
-            let name = kids.next().and_then(|n| find_ident_text(n))?;
+            let callee = kids.next()?;
+            // `recv.method(..)` is named after the receiver until `stmt_pass_fields` knows its type.
+            let mut name = if callee.kind() == SyntaxKind::MemberExpr { member_name(&callee)? } else { find_ident_text(callee)? };
+            let mut instance = None;
+            if let Some(type_args) = node.children().find(|n| n.kind() == SyntaxKind::TypeArgList) {
+                // A call to one instantiation of a generic function; see `monomorphize`.
+                let type_args: Vec<String> = type_args
+                    .children()
+                    .filter(|n| n.kind() == SyntaxKind::Type)
+                    .map(|n| resolve_alias(&n.text().to_string(), aliases).to_string())
+                    .collect();
+                let generic = name.clone();
+                name = format!("{generic}::<{}>", type_args.join(", "));
+                instance = Some(Instance { generic, type_args });
+            }
             let mut args = Vec::new();
-            for arg in kids {
-                if let Some(expr) = lower_expr(arg) {
+            for arg in kids.filter(|n| is_expr_kind(n.kind())) {
+                if let Some(expr) = lower_expr(arg, aliases) {
                     args.push(expr);
                 }
             }
-            Some(Expr::Call { name, args })
+            Some(Expr::Call { name, instance, args, span })
         }
-        SyntaxKind::ParenExpr => node.children().find(|n| is_expr_kind(n.kind())).and_then(lower_expr),
-        _ => None,
+        // Struct values live in one local per field, so `p.x` reads the local `p.x`.
+        SyntaxKind::MemberExpr => {
+            let span = span_of(node.text_range());
+            member_name(&node).map(|name| Expr::Ident { name, span })
+        }
+        SyntaxKind::PathExpr => {
+            let span = span_of(node.text_range());
+            let segments: Vec<String> = node.children().filter_map(find_ident_text).collect();
+            Some(Expr::Path { path: segments.join("::"), span })
+        }
+        SyntaxKind::ParenExpr => node.children().find(|n| is_expr_kind(n.kind())).and_then(|n| lower_expr(n, aliases)),
+        SyntaxKind::TupleExpr => node.children().filter(|n| is_expr_kind(n.kind())).map(|n| lower_expr(n, aliases)).collect::<Option<Vec<_>>>().map(Expr::Tuple),
+        _ => Some(Expr::Unsupported { span: span_of(node.text_range()) }),
     }
 }
 
-fn signature_from_fn(f: &FnDef, diags: &mut Vec<Diagnostic>) -> (Vec<ValType>, Vec<ValType>) {
-    for (_, ty) in &f.params {
-        if *ty != ValType::I64 {
-            diags.push(Diagnostic { code: "E2002".to_string(), message: "only i64 params supported".to_string() });
+/// `base.member` for a member of a plain name; `None` for anything else, such as `f().x`.
+fn member_name(node: &SyntaxNode) -> Option<String> {
+    let mut kids = node.children();
+    let base = kids.next().filter(|n| n.kind() == SyntaxKind::IdentNode).and_then(find_ident_text)?;
+    let member = kids.next().and_then(find_ident_text)?;
+    Some(format!("{base}.{member}"))
+}
+
+/// Rewrites calls in `stmt` for struct values passed as their fields: `p.sum()` on a `Point` `p`
+/// becomes `Point::sum(p.x, p.y)`, and a struct argument `f(p)` becomes `f(p.x, p.y)`.
+fn stmt_pass_fields(stmt: &mut Stmt, receivers: &BTreeMap<String, String>, structs: &Structs) {
+    match stmt {
+        Stmt::Let { expr, .. } | Stmt::LetTuple { expr, .. } | Stmt::Assign { expr, .. } | Stmt::Return(expr) | Stmt::Expr(expr) => {
+            expr_pass_fields(expr, receivers, structs)
+        }
+        Stmt::If { cond, then_body, else_body } => {
+            expr_pass_fields(cond, receivers, structs);
+            for stmt in then_body.iter_mut().chain(else_body) {
+                stmt_pass_fields(stmt, receivers, structs);
+            }
+        }
+        Stmt::StructLocal { .. } => {}
+    }
+}
+
+fn expr_pass_fields(expr: &mut Expr, receivers: &BTreeMap<String, String>, structs: &Structs) {
+    match expr {
+        Expr::Call { name, args, span, .. } => {
+            for arg in args.iter_mut() {
+                expr_pass_fields(arg, receivers, structs);
+            }
+            if let Some((receiver, method)) = name.split_once('.') {
+                if let Some(ty) = receivers.get(receiver) {
+                    args.insert(0, Expr::Ident { name: receiver.to_string(), span: *span });
+                    *name = format!("{ty}::{method}");
+                }
+            }
+            *args = std::mem::take(args)
+                .into_iter()
+                .flat_map(|arg| match &arg {
+                    Expr::Ident { name, span } if receivers.contains_key(name) => structs[&receivers[name]]
+                        .iter()
+                        .map(|(field, _)| Expr::Ident { name: format!("{name}.{field}"), span: *span })
+                        .collect(),
+                    _ => vec![arg],
+                })
+                .collect();
+        }
+        Expr::Bin { lhs, rhs, .. } => {
+            expr_pass_fields(lhs, receivers, structs);
+            expr_pass_fields(rhs, receivers, structs);
         }
+        Expr::Tuple(elems) => {
+            for elem in elems {
+                expr_pass_fields(elem, receivers, structs);
+            }
+        }
+        _ => {}
     }
-    if let Some(ret) = f.ret {
-        if ret != ValType::I64 {
-            diags.push(Diagnostic { code: "E2003".to_string(), message: "only i64 return supported".to_string() });
+}
+
+/// Types each unannotated `let` local from its initializer, in order, so an initializer that
+/// reads a parameter, an earlier local, or a call result gets that value's type.
+fn resolve_let_types(f: &mut FnDef, signatures: &Signatures) {
+    fn walk(stmts: &[Stmt], params: &[ParamDef], locals: &mut [(String, ValType)], signatures: &Signatures) {
+        for stmt in stmts {
+            match stmt {
+                Stmt::Let { name, expr, unsigned: None } => {
+                    let ty = val_type(expr, params, locals, signatures);
+                    if let Some(local) = locals.iter_mut().find(|(n, _)| n == name) {
+                        local.1 = ty;
+                    }
+                }
+                Stmt::LetTuple { names, expr: Expr::Tuple(elems), annotated: false } => {
+                    for (name, elem) in names.iter().zip(elems) {
+                        let ty = val_type(elem, params, locals, signatures);
+                        if let Some(local) = name.as_ref().and_then(|name| locals.iter_mut().find(|(n, _)| n == name)) {
+                            local.1 = ty;
+                        }
+                    }
+                }
+                Stmt::LetTuple { names, expr: Expr::Call { name: callee, .. }, annotated: false } => {
+                    let results = signatures.get(callee).map(|(_, results)| results.as_slice()).unwrap_or_default();
+                    for (name, ty) in names.iter().zip(results) {
+                        if let Some(local) = name.as_ref().and_then(|name| locals.iter_mut().find(|(n, _)| n == name)) {
+                            local.1 = *ty;
+                        }
+                    }
+                }
+                Stmt::If { then_body, else_body, .. } => {
+                    walk(then_body, params, locals, signatures);
+                    walk(else_body, params, locals, signatures);
+                }
+                _ => {}
+            }
         }
     }
+    walk(&f.body, &f.params, &mut f.locals, signatures);
+}
+
+/// Wasm type of the value `expr` produces; unit calls and unknown names default to `i64`.
+fn val_type(expr: &Expr, params: &[ParamDef], locals: &[(String, ValType)], signatures: &Signatures) -> ValType {
+    match expr {
+        Expr::Float(_) => ValType::F64,
+        Expr::Bool(_) | Expr::Path { .. } => ValType::I32,
+        Expr::Bin { op, .. } if is_comparison_op(*op) => ValType::I32,
+        Expr::Bin { lhs, rhs, .. } => match val_type(lhs, params, locals, signatures) {
+            ValType::I64 => val_type(rhs, params, locals, signatures),
+            ty => ty,
+        },
+        Expr::Ident { name, .. } => local_type(name, params, locals),
+        Expr::Call { name, .. } => match signatures.get(name).map(|(_, results)| results.as_slice()) {
+            Some([ty]) => *ty,
+            _ => ValType::I64,
+        },
+        _ => ValType::I64,
+    }
+}
+
+/// Source span of `expr`, when it records one.
+fn expr_span(expr: &Expr) -> Option<Span> {
+    match expr {
+        Expr::Ident { span, .. }
+        | Expr::Path { span, .. }
+        | Expr::Bin { span, .. }
+        | Expr::Call { span, .. }
+        | Expr::Unsupported { span } => Some(*span),
+        _ => None,
+    }
+}
+
+/// Wasm type of a parameter or local; unknown names default to `i64`.
+fn local_type(name: &str, params: &[ParamDef], locals: &[(String, ValType)]) -> ValType {
+    let param = params.iter().find(|p| p.name == name).map(|p| p.ty);
+    let local = || locals.iter().find(|(n, _)| n == name).map(|(_, ty)| Some(*ty));
+    param.or_else(local).flatten().unwrap_or(ValType::I64)
+}
+
+fn signature_from_fn(f: &FnDef, diags: &mut Vec<Diagnostic>) -> (Vec<ValType>, Vec<ValType>) {
+    for param in f.params.iter().filter(|p| p.ty.is_none()) {
+        diags.push(Diagnostic {
+            code: "E2002".to_string(),
+            message: format!("unsupported type for parameter {}", param.name),
+            span: Some(param.span),
+        });
+    }
+    if f.ret.is_empty() && f.ret_span.is_some() {
+        diags.push(Diagnostic {
+            code: "E2003".to_string(),
+            message: "unsupported return type".to_string(),
+            span: f.ret_span,
+        });
+    }
     (
-        f.params.iter().map(|(_, t)| *t).collect(),
-        vec![ValType::I64],
+        f.params.iter().map(|p| p.ty.unwrap_or(ValType::I64)).collect(),
+        f.ret.clone(),
     )
 }
 
 fn emit_stmt(body: &mut Function, ctx: &mut EmitCtx, stmt: &Stmt) {
     match stmt {
-        Stmt::Let { name, expr } => {
-            emit_expr(body, ctx, expr);
+        Stmt::Let { expr, .. } if ctx.result_count(expr) > 1 => {
+            ctx.diagnostics.push(Diagnostic {
+                code: "E2012".to_string(),
+                message: "tuple value bound to a single name; destructure it with `let (a, b) = ...`".to_string(),
+                span: expr_span(expr),
+            });
+        }
+        Stmt::Let { name, expr, unsigned } => {
+            let unsigned = unsigned.unwrap_or_else(|| ctx.is_unsigned(expr));
+            emit_widened(body, ctx, expr, ctx.local_type(name));
             if let Some(idx) = ctx.local_index(name) {
                 body.instruction(&Instruction::LocalSet(idx));
             }
+            ctx.unsigned.retain(|n| n != name);
+            if unsigned {
+                ctx.unsigned.push(name.clone());
+            }
         }
-        Stmt::Return(expr) => {
+        Stmt::Assign { name, expr } => {
+            emit_widened(body, ctx, expr, ctx.local_type(name));
+            if let Some(idx) = ctx.local_index(name) {
+                body.instruction(&Instruction::LocalSet(idx));
+            }
+        }
+        Stmt::LetTuple { names, expr, .. } => {
+            // A tuple leaves its elements on the stack, last on top.
             emit_expr(body, ctx, expr);
+            ctx.unsigned.retain(|n| !names.iter().flatten().any(|m| m == n));
+            for name in names.iter().rev() {
+                match name.as_deref().and_then(|n| ctx.local_index(n)) {
+                    Some(idx) => body.instruction(&Instruction::LocalSet(idx)),
+                    None => body.instruction(&Instruction::Drop),
+                };
+            }
+        }
+        Stmt::Return(expr) => {
+            match ctx.ret {
+                [ty] => emit_widened(body, ctx, expr, *ty),
+                _ => emit_expr(body, ctx, expr),
+            }
             body.instruction(&Instruction::Return);
         }
         Stmt::Expr(expr) => {
             emit_expr(body, ctx, expr);
-            body.instruction(&Instruction::Drop);
+            for _ in 0..ctx.result_count(expr) {
+                body.instruction(&Instruction::Drop);
+            }
         }
         Stmt::If { cond, then_body, else_body } => {
             emit_expr(body, ctx, cond);
             body.instruction(&Instruction::If(wasm_encoder::BlockType::Empty));
+            // Bindings made in a branch end with it.
+            let outer = ctx.unsigned.clone();
             for stmt in then_body {
                 emit_stmt(body, ctx, stmt);
             }
+            ctx.unsigned.clone_from(&outer);
             if !else_body.is_empty() {
                 body.instruction(&Instruction::Else);
                 for stmt in else_body {
                     emit_stmt(body, ctx, stmt);
                 }
+                ctx.unsigned = outer;
             }
             body.instruction(&Instruction::End);
         }
+        Stmt::StructLocal { name, span } => {
+            ctx.diagnostics.push(Diagnostic {
+                code: "E2014".to_string(),
+                message: format!("struct locals are not supported: {name}"),
+                span: Some(*span),
+            });
+            ctx.struct_locals.push(name.clone());
+        }
     }
 }
 
@@ -303,59 +1356,399 @@ fn emit_expr(body: &mut Function, ctx: &mut EmitCtx, expr: &Expr) {
         Expr::Int(v) => {
             body.instruction(&Instruction::I64Const(*v));
         }
+        Expr::Float(v) => {
+            body.instruction(&Instruction::F64Const(*v));
+        }
+        Expr::Tuple(elems) => {
+            for elem in elems {
+                emit_expr(body, ctx, elem);
+            }
+        }
+        Expr::Unsupported { span } => {
+            ctx.diagnostics.push(Diagnostic {
+                code: "E2013".to_string(),
+                message: "unsupported expression".to_string(),
+                span: Some(*span),
+            });
+            body.instruction(&Instruction::I64Const(0));
+        }
         Expr::Bool(v) => {
             body.instruction(&Instruction::I32Const(if *v { 1 } else { 0 }));
         }
-        Expr::Ident(name) => {
+        Expr::Bytes(bytes) => {
+            // (ptr, len) packed into one i64: pointer in the high half, length in the low half.
+            let ptr = DATA_BASE + ctx.data.len() as i64;
+            ctx.data.extend_from_slice(bytes);
+            body.instruction(&Instruction::I64Const((ptr << 32) | bytes.len() as i64));
+        }
+        Expr::Ident { name, span } => {
+            ctx.mark(body, *span);
             if let Some(idx) = ctx.local_index(name) {
                 body.instruction(&Instruction::LocalGet(idx));
+            } else if ctx.is_struct_local(name) {
+                body.instruction(&Instruction::I64Const(0));
             } else {
-                ctx.diagnostics.push(Diagnostic { code: "E2004".to_string(), message: format!("unknown local {name}") });
+                ctx.diagnostics.push(Diagnostic {
+                    code: "E2004".to_string(),
+                    message: format!("unknown local {name}"),
+                    span: Some(*span),
+                });
                 body.instruction(&Instruction::I64Const(0));
             }
         }
-        Expr::Bin { op, lhs, rhs } => {
-            emit_expr(body, ctx, lhs);
-            emit_expr(body, ctx, rhs);
-            match op {
-                SyntaxKind::Plus => body.instruction(&Instruction::I64Add),
-                SyntaxKind::Minus => body.instruction(&Instruction::I64Sub),
-                SyntaxKind::Star => body.instruction(&Instruction::I64Mul),
-                SyntaxKind::Slash => body.instruction(&Instruction::I64DivS),
-                SyntaxKind::EqEq => body.instruction(&Instruction::I64Eq),
-                SyntaxKind::Neq => body.instruction(&Instruction::I64Ne),
-                SyntaxKind::Lt => body.instruction(&Instruction::I64LtS),
-                SyntaxKind::Lte => body.instruction(&Instruction::I64LeS),
-                SyntaxKind::Gt => body.instruction(&Instruction::I64GtS),
-                SyntaxKind::Gte => body.instruction(&Instruction::I64GeS),
-                _ => return,
+        Expr::Path { path, span } => {
+            if let Some(value) = ctx.discriminants.get(path) {
+                body.instruction(&Instruction::I32Const(*value));
+            } else {
+                ctx.diagnostics.push(Diagnostic {
+                    code: "E2004".to_string(),
+                    message: format!("unknown local {path}"),
+                    span: Some(*span),
+                });
+                body.instruction(&Instruction::I32Const(0));
+            }
+        }
+        Expr::Bin { op, lhs, rhs, span } => {
+            // Both operands share a type; an `f64` on either side picks the float instructions.
+            let is_float = ctx.val_type(lhs) == ValType::F64 || ctx.val_type(rhs) == ValType::F64;
+            // `i32` operands, such as enum discriminants or an `i32` and a literal, use the `i32` instructions.
+            let is_i32 = !is_float && operand_type(ctx, lhs, rhs) == ValType::I32;
+            if is_i32 {
+                emit_widened(body, ctx, lhs, ValType::I32);
+                emit_widened(body, ctx, rhs, ValType::I32);
+            } else {
+                emit_expr(body, ctx, lhs);
+                emit_expr(body, ctx, rhs);
+            }
+            if let Some((panic, scratch)) = ctx.div_check.filter(|_| !is_float && matches!(op, SyntaxKind::Slash | SyntaxKind::Percent)) {
+                // if divisor == 0 { jalm_panic(PANIC_DIVIDE_BY_ZERO) }; the scratch local is an `i64`.
+                if is_i32 {
+                    body.instruction(&Instruction::I64ExtendI32S);
+                }
+                body.instruction(&Instruction::LocalTee(scratch));
+                body.instruction(&Instruction::I64Eqz);
+                body.instruction(&Instruction::If(wasm_encoder::BlockType::Empty));
+                body.instruction(&Instruction::I32Const(PANIC_DIVIDE_BY_ZERO));
+                body.instruction(&Instruction::Call(panic));
+                body.instruction(&Instruction::Unreachable);
+                body.instruction(&Instruction::End);
+                body.instruction(&Instruction::LocalGet(scratch));
+                if is_i32 {
+                    body.instruction(&Instruction::I32WrapI64);
+                }
+            }
+            ctx.mark(body, *span);
+            if is_float {
+                match op {
+                    SyntaxKind::Plus => body.instruction(&Instruction::F64Add),
+                    SyntaxKind::Minus => body.instruction(&Instruction::F64Sub),
+                    SyntaxKind::Star => body.instruction(&Instruction::F64Mul),
+                    SyntaxKind::Slash => body.instruction(&Instruction::F64Div),
+                    SyntaxKind::EqEq => body.instruction(&Instruction::F64Eq),
+                    SyntaxKind::Neq => body.instruction(&Instruction::F64Ne),
+                    SyntaxKind::Lt => body.instruction(&Instruction::F64Lt),
+                    SyntaxKind::Lte => body.instruction(&Instruction::F64Le),
+                    SyntaxKind::Gt => body.instruction(&Instruction::F64Gt),
+                    SyntaxKind::Gte => body.instruction(&Instruction::F64Ge),
+                    SyntaxKind::Percent => {
+                        ctx.diagnostics.push(Diagnostic {
+                            code: "E2011".to_string(),
+                            message: "unsupported f64 remainder".to_string(),
+                            span: Some(*span),
+                        });
+                        return;
+                    }
+                    _ => return,
+                };
+                return;
+            }
+            if is_i32 {
+                emit_i32_op(body, *op);
+                return;
+            }
+            // A `u64` on either side picks the unsigned division, remainder, and comparisons.
+            let unsigned = match op {
+                _ if !ctx.is_unsigned(lhs) && !ctx.is_unsigned(rhs) => None,
+                SyntaxKind::Slash => Some(Instruction::I64DivU),
+                SyntaxKind::Percent => Some(Instruction::I64RemU),
+                SyntaxKind::Lt => Some(Instruction::I64LtU),
+                SyntaxKind::Lte => Some(Instruction::I64LeU),
+                SyntaxKind::Gt => Some(Instruction::I64GtU),
+                SyntaxKind::Gte => Some(Instruction::I64GeU),
+                _ => None,
             };
+            match unsigned {
+                Some(instruction) => {
+                    body.instruction(&instruction);
+                }
+                None => emit_i64_op(body, *op),
+            }
+        }
+        Expr::Call { name, args, span, .. } if ctx.is_memory_builtin(name) => {
+            // memcpy(dst, src, len) / memset(dst, value, len): every operand is an i32 in wasm.
+            for arg in args {
+                emit_expr(body, ctx, arg);
+                body.instruction(&Instruction::I32WrapI64);
+            }
+            if args.len() != 3 {
+                ctx.diagnostics.push(Diagnostic {
+                    code: "E2008".to_string(),
+                    message: format!("{name} expects 3 arguments, found {}", args.len()),
+                    span: Some(*span),
+                });
+                return;
+            }
+            *ctx.uses_memory = true;
+            ctx.mark(body, *span);
+            if name == "memcpy" {
+                body.instruction(&Instruction::MemoryCopy { src_mem: 0, dst_mem: 0 });
+            } else {
+                body.instruction(&Instruction::MemoryFill(0));
+            }
         }
-        Expr::Call { name, args } => {
+        Expr::Call { name, args, span, .. } if ctx.is_panic_builtin(name) => {
+            // The code is evaluated for its side effects only; the trap carries no payload.
             for arg in args {
                 emit_expr(body, ctx, arg);
+                body.instruction(&Instruction::Drop);
+            }
+            ctx.mark(body, *span);
+            body.instruction(&Instruction::Unreachable);
+        }
+        Expr::Call { name, args, span, .. } if ctx.is_assert_builtin(name) => {
+            if args.len() != 1 {
+                ctx.diagnostics.push(Diagnostic {
+                    code: "E2008".to_string(),
+                    message: format!("{name} expects 1 argument, found {}", args.len()),
+                    span: Some(*span),
+                });
+                return;
+            }
+            // if !cond { unreachable }
+            emit_expr(body, ctx, &args[0]);
+            body.instruction(&Instruction::I32Eqz);
+            body.instruction(&Instruction::If(wasm_encoder::BlockType::Empty));
+            ctx.mark(body, *span);
+            body.instruction(&Instruction::Unreachable);
+            body.instruction(&Instruction::End);
+        }
+        Expr::Call { name, args, span, .. } => {
+            let params = ctx.signatures.get(name).map(|(params, _)| params.clone()).unwrap_or_default();
+            for (idx, arg) in args.iter().enumerate() {
+                match params.get(idx) {
+                    Some(ty) => emit_widened(body, ctx, arg, *ty),
+                    None => emit_expr(body, ctx, arg),
+                }
             }
-            if let Some(idx) = ctx.func_indices.get(name) {
+            // Omitted trailing arguments go through the wrapper that computes their defaults.
+            let target = if args.len() < params.len() { default_wrapper_name(name, args.len()) } else { name.clone() };
+            if ctx.signatures.contains_key(name) && (args.len() > params.len() || !ctx.func_indices.contains_key(&target)) {
+                ctx.diagnostics.push(Diagnostic {
+                    code: "E2008".to_string(),
+                    message: format!("{name} expects {} arguments, found {}", params.len(), args.len()),
+                    span: Some(*span),
+                });
+                body.instruction(&Instruction::I64Const(0));
+            } else if let Some(idx) = ctx.func_indices.get(&target) {
+                ctx.mark(body, *span);
                 body.instruction(&Instruction::Call(*idx));
+                // The call does not return, so whatever value the context expects is unreachable.
+                if ctx.never_fns.contains(name) {
+                    body.instruction(&Instruction::Unreachable);
+                }
+            } else if ctx.is_struct_local(name) {
+                body.instruction(&Instruction::I64Const(0));
             } else {
-                ctx.diagnostics.push(Diagnostic { code: "E2005".to_string(), message: format!("unknown function {name}") });
+                ctx.diagnostics.push(Diagnostic {
+                    code: "E2005".to_string(),
+                    message: format!("unknown function {name}"),
+                    span: Some(*span),
+                });
                 body.instruction(&Instruction::I64Const(0));
             }
         }
     }
 }
 
+/// Whether `stmt` contains a `/` or `%`, so checked arithmetic needs `jalm_panic`.
+fn stmt_divides(stmt: &Stmt) -> bool {
+    match stmt {
+        Stmt::Let { expr, .. } | Stmt::LetTuple { expr, .. } | Stmt::Assign { expr, .. } | Stmt::Return(expr) | Stmt::Expr(expr) => {
+            expr_divides(expr)
+        }
+        Stmt::If { cond, then_body, else_body } => {
+            expr_divides(cond) || then_body.iter().chain(else_body).any(stmt_divides)
+        }
+        Stmt::StructLocal { .. } => false,
+    }
+}
+
+fn expr_divides(expr: &Expr) -> bool {
+    match expr {
+        Expr::Bin { op: SyntaxKind::Slash | SyntaxKind::Percent, .. } => true,
+        Expr::Bin { lhs, rhs, .. } => expr_divides(lhs) || expr_divides(rhs),
+        Expr::Call { args, .. } | Expr::Tuple(args) => args.iter().any(expr_divides),
+        _ => false,
+    }
+}
+
+/// Type both operands of `lhs op rhs` are emitted as: an integer literal takes the other side's type.
+fn operand_type(ctx: &EmitCtx, lhs: &Expr, rhs: &Expr) -> ValType {
+    match ctx.val_type(lhs) {
+        ValType::I64 => ctx.val_type(rhs),
+        ty => ty,
+    }
+}
+
+/// Emits `expr` for a slot of type `target`, widening an `i32` to `i64` or an `f32` to `f64`
+/// as the type checker allows. In an `i32` slot an integer literal is an `i32.const`, and
+/// arithmetic on literals alone is computed as `i64` and wrapped.
+fn emit_widened(body: &mut Function, ctx: &mut EmitCtx, expr: &Expr, target: ValType) {
+    if let (Expr::Int(v), ValType::I32) = (expr, target) {
+        body.instruction(&Instruction::I32Const(*v as i32));
+        return;
+    }
+    emit_expr(body, ctx, expr);
+    match (ctx.val_type(expr), target) {
+        (ValType::I32, ValType::I64) => {
+            body.instruction(&Instruction::I64ExtendI32S);
+        }
+        (ValType::I64, ValType::I32) => {
+            body.instruction(&Instruction::I32WrapI64);
+        }
+        (ValType::F32, ValType::F64) => {
+            body.instruction(&Instruction::F64PromoteF32);
+        }
+        _ => {}
+    }
+}
+
+/// Emits the signed (or sign-agnostic) `i64` instruction for `op`. `i64.div_s` traps on
+/// `i64::MIN / -1`, like division by zero; `i64.rem_s` gives 0 there.
+fn emit_i64_op(body: &mut Function, op: SyntaxKind) {
+    match op {
+        SyntaxKind::Plus => body.instruction(&Instruction::I64Add),
+        SyntaxKind::Minus => body.instruction(&Instruction::I64Sub),
+        SyntaxKind::Star => body.instruction(&Instruction::I64Mul),
+        SyntaxKind::Slash => body.instruction(&Instruction::I64DivS),
+        SyntaxKind::Percent => body.instruction(&Instruction::I64RemS),
+        SyntaxKind::EqEq => body.instruction(&Instruction::I64Eq),
+        SyntaxKind::Neq => body.instruction(&Instruction::I64Ne),
+        SyntaxKind::Lt => body.instruction(&Instruction::I64LtS),
+        SyntaxKind::Lte => body.instruction(&Instruction::I64LeS),
+        SyntaxKind::Gt => body.instruction(&Instruction::I64GtS),
+        SyntaxKind::Gte => body.instruction(&Instruction::I64GeS),
+        _ => return,
+    };
+}
+
+fn emit_i32_op(body: &mut Function, op: SyntaxKind) {
+    match op {
+        SyntaxKind::Plus => body.instruction(&Instruction::I32Add),
+        SyntaxKind::Minus => body.instruction(&Instruction::I32Sub),
+        SyntaxKind::Star => body.instruction(&Instruction::I32Mul),
+        SyntaxKind::Slash => body.instruction(&Instruction::I32DivS),
+        SyntaxKind::Percent => body.instruction(&Instruction::I32RemS),
+        SyntaxKind::EqEq => body.instruction(&Instruction::I32Eq),
+        SyntaxKind::Neq => body.instruction(&Instruction::I32Ne),
+        SyntaxKind::Lt => body.instruction(&Instruction::I32LtS),
+        SyntaxKind::Lte => body.instruction(&Instruction::I32LeS),
+        SyntaxKind::Gt => body.instruction(&Instruction::I32GtS),
+        SyntaxKind::Gte => body.instruction(&Instruction::I32GeS),
+        _ => return,
+    };
+}
+
+/// Wasm parameter and result types of each function.
+type Signatures = BTreeMap<String, (Vec<ValType>, Vec<ValType>)>;
+
 struct EmitCtx<'a> {
-    func_indices: &'a std::collections::HashMap<String, u32>,
+    func_indices: &'a BTreeMap<String, u32>,
+    /// Wasm parameter and result types of each function; unit functions have no results.
+    signatures: &'a Signatures,
+    /// `i32` value of each `Enum::Variant` of a fieldless enum.
+    discriminants: &'a BTreeMap<String, i32>,
+    data: &'a mut Vec<u8>,
+    /// Set when a memory builtin is emitted, so the module declares a memory.
+    uses_memory: &'a mut bool,
     locals: &'a [(String, ValType)],
-    params: &'a [(String, ValType)],
+    params: &'a [ParamDef],
+    /// Results of the function being emitted.
+    ret: &'a [ValType],
+    /// Names bound to a `u64` at the point being emitted; a later `let` of the same name rebinds it.
+    unsigned: Vec<String>,
+    /// Functions declared `-> u64`.
+    unsigned_fns: &'a BTreeSet<String>,
+    /// Functions declared `-> never`.
+    never_fns: &'a BTreeSet<String>,
+    /// `(jalm_panic function index, scratch local)` when integer divisors are checked.
+    div_check: Option<(u32, u32)>,
+    /// `(offset in the function body, source span)` of calls, operators, and locals.
+    spans: Vec<(u32, Span)>,
+    /// Struct locals already reported as E2014; uses of them are not reported again.
+    struct_locals: Vec<String>,
     diagnostics: &'a mut Vec<Diagnostic>,
 }
 
 impl<'a> EmitCtx<'a> {
+    /// Whether `name`, or the receiver before its first `.`, is a reported struct local.
+    fn is_struct_local(&self, name: &str) -> bool {
+        let base = name.split('.').next().unwrap_or(name);
+        self.struct_locals.iter().any(|n| n == base)
+    }
+
+    /// Records that the next instruction emitted into `body` comes from `span`.
+    fn mark(&mut self, body: &Function, span: Span) {
+        self.spans.push((body.byte_len() as u32, span));
+    }
+
+    fn is_memory_builtin(&self, name: &str) -> bool {
+        MEMORY_BUILTINS.contains(&name) && !self.func_indices.contains_key(name)
+    }
+
+    fn is_panic_builtin(&self, name: &str) -> bool {
+        name == PANIC_BUILTIN && !self.func_indices.contains_key(name)
+    }
+
+    fn is_assert_builtin(&self, name: &str) -> bool {
+        name == ASSERT_BUILTIN && !self.func_indices.contains_key(name)
+    }
+
+    /// Number of values `expr` leaves on the stack.
+    fn result_count(&self, expr: &Expr) -> usize {
+        match expr {
+            Expr::Call { name, .. }
+                if self.is_memory_builtin(name) || self.is_panic_builtin(name) || self.is_assert_builtin(name) =>
+            {
+                0
+            }
+            Expr::Call { name, .. } => self.signatures.get(name).map_or(1, |(_, results)| results.len()),
+            Expr::Tuple(elems) => elems.iter().map(|e| self.result_count(e)).sum(),
+            _ => 1,
+        }
+    }
+
+    fn val_type(&self, expr: &Expr) -> ValType {
+        val_type(expr, self.params, self.locals, self.signatures)
+    }
+
+    fn local_type(&self, name: &str) -> ValType {
+        local_type(name, self.params, self.locals)
+    }
+
+    /// Whether `expr` is a `u64` value: a `u64` name, a call returning `u64`, or arithmetic on one.
+    fn is_unsigned(&self, expr: &Expr) -> bool {
+        match expr {
+            Expr::Ident { name, .. } => self.unsigned.contains(name),
+            Expr::Call { name, .. } => self.unsigned_fns.contains(name),
+            Expr::Bin { op, lhs, rhs, .. } if !is_comparison_op(*op) => self.is_unsigned(lhs) || self.is_unsigned(rhs),
+            _ => false,
+        }
+    }
+
     fn local_index(&self, name: &str) -> Option<u32> {
-        for (i, (n, _)) in self.params.iter().enumerate() {
-            if n == name {
+        for (i, p) in self.params.iter().enumerate() {
+            if p.name == name {
                 return Some(i as u32);
             }
         }
@@ -370,29 +1763,76 @@ impl<'a> EmitCtx<'a> {
     }
 }
 
-fn find_return_type(node: &SyntaxNode) -> Option<String> {
+fn find_return_type(node: &SyntaxNode) -> Option<SyntaxNode> {
     let mut seen_arrow = false;
     for el in node.children_with_tokens() {
         match el {
             SyntaxElement::Token(t) if t.kind() == SyntaxKind::Arrow => seen_arrow = true,
-            SyntaxElement::Node(n) if seen_arrow && n.kind() == SyntaxKind::Type => {
-                return Some(n.text().to_string());
-            }
+            SyntaxElement::Node(n) if seen_arrow && n.kind() == SyntaxKind::Type => return Some(n),
             _ => {}
         }
     }
     None
 }
 
-fn map_type(text: String) -> Option<ValType> {
-    match text.trim() {
-        "i64" => Some(ValType::I64),
+/// Defined functions are exported under their `@export` name; `main` is always exported.
+fn export_name(f: &FnDef) -> Option<&str> {
+    match &f.export {
+        Some(name) => Some(name),
+        None if f.name == "main" => Some("main"),
+        None => None,
+    }
+}
+
+fn val_type_name(ty: ValType) -> &'static str {
+    match ty {
+        ValType::I32 => "i32",
+        ValType::I64 => "i64",
+        ValType::F32 => "f32",
+        ValType::F64 => "f64",
+        _ => "<unsupported>",
+    }
+}
+
+/// Wasm results for a return type: one value, or one per element of a flat tuple.
+fn map_types(node: &SyntaxNode, aliases: &TypeAliases) -> Option<Vec<ValType>> {
+    let elems: Vec<SyntaxNode> = node.children().filter(|n| n.kind() == SyntaxKind::Type).collect();
+    if elems.is_empty() {
+        return map_type(&node.text().to_string(), aliases).map(|ty| vec![ty]);
+    }
+    elems.iter().map(|e| map_type(&e.text().to_string(), aliases)).collect()
+}
+
+fn map_type(text: &str, aliases: &TypeAliases) -> Option<ValType> {
+    match resolve_alias(text, aliases) {
+        "i64" | "u64" => Some(ValType::I64),
         "i32" => Some(ValType::I32),
         "bool" => Some(ValType::I32),
+        "f64" => Some(ValType::F64),
+        "f32" => Some(ValType::F32),
         _ => None,
     }
 }
 
+fn find_expr_after_token(node: &SyntaxNode, token_kind: SyntaxKind) -> Option<SyntaxNode> {
+    let mut seen = false;
+    for el in node.children_with_tokens() {
+        match el {
+            SyntaxElement::Token(t) if t.kind() == token_kind => seen = true,
+            SyntaxElement::Node(n) if seen && is_expr_kind(n.kind()) => return Some(n),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn has_module_attribute(root: &Root, name: &str) -> bool {
+    root.items().any(|item| {
+        matches!(item, Item::ModuleAttribute(_))
+            && item.syntax().children().next().and_then(find_ident_text).as_deref() == Some(name)
+    })
+}
+
 fn find_ident_text(node: SyntaxNode) -> Option<String> {
     node.descendants_with_tokens().find_map(|e| match e {
         SyntaxElement::Token(t) if t.kind() == SyntaxKind::Ident => Some(t.text().to_string()),
@@ -400,18 +1840,41 @@ fn find_ident_text(node: SyntaxNode) -> Option<String> {
     })
 }
 
+fn span_of(range: TextRange) -> Span {
+    Span {
+        start: range.start().into(),
+        end: range.end().into(),
+    }
+}
+
 fn is_expr_kind(kind: SyntaxKind) -> bool {
     matches!(
         kind,
         SyntaxKind::BinExpr
+            | SyntaxKind::AssignExpr
             | SyntaxKind::CallExpr
             | SyntaxKind::MemberExpr
+            | SyntaxKind::PathExpr
+            | SyntaxKind::ClosureExpr
+            | SyntaxKind::StructLit
             | SyntaxKind::IfExpr
+            | SyntaxKind::IfLetExpr
             | SyntaxKind::MatchExpr
             | SyntaxKind::IdentNode
             | SyntaxKind::LiteralNode
             | SyntaxKind::ParenExpr
+            | SyntaxKind::TupleExpr
+            | SyntaxKind::ArrayExpr
+            | SyntaxKind::AwaitExpr
             | SyntaxKind::Block
+            | SyntaxKind::LabeledBlock
+    )
+}
+
+fn is_comparison_op(kind: SyntaxKind) -> bool {
+    matches!(
+        kind,
+        SyntaxKind::EqEq | SyntaxKind::Neq | SyntaxKind::Lt | SyntaxKind::Lte | SyntaxKind::Gt | SyntaxKind::Gte
     )
 }
 
@@ -422,6 +1885,7 @@ fn is_bin_op(kind: SyntaxKind) -> bool {
             | SyntaxKind::Minus
             | SyntaxKind::Star
             | SyntaxKind::Slash
+            | SyntaxKind::Percent
             | SyntaxKind::EqEq
             | SyntaxKind::Neq
             | SyntaxKind::Lt
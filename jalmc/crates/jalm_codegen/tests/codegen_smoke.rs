@@ -1,5 +1,8 @@
-use jalm_codegen::compile_to_wasm;
-use wasmtime::{Engine, Instance, Module, Store};
+use jalm_codegen::{
+    compile_to_wasm, compile_to_wasm_with_options, module_interface, read_source_map, CompileOptions, ExportedFn,
+    ImportedFn,
+};
+use wasmtime::{Engine, Func, Instance, Module, Store};
 
 fn run_main(source: &str) -> i64 {
     let wasm = compile_to_wasm(source).expect("compile ok");
@@ -48,3 +51,1046 @@ fn main() -> i64 {
     let errs = compile_to_wasm(source).unwrap_err();
     assert!(errs.iter().any(|d| d.code == "E2005"));
 }
+
+#[test]
+fn unknown_function_reports_call_span() {
+    let source = r#"
+fn main() -> i64 {
+  return nope();
+}
+"#;
+    let errs = compile_to_wasm(source).unwrap_err();
+    let diag = errs.iter().find(|d| d.code == "E2005").expect("E2005");
+    let span = diag.span.expect("span");
+    assert_eq!(&source[span.start..span.end], "nope()");
+    assert_eq!((span.start, span.end), (29, 35));
+}
+
+#[test]
+fn boolean_literals_lower_to_i32_conditions() {
+    let source = r#"
+fn main() -> i64 {
+  let flag = true;
+  let annotated: bool = false;
+  if annotated {
+    return 1;
+  }
+  if flag {
+    return 7;
+  }
+  return 0;
+}
+"#;
+    assert_eq!(run_main(source), 7);
+}
+
+#[test]
+fn negative_literal_lowers_to_single_constant() {
+    let source = r#"
+fn main() -> i64 {
+  let base: i64 = - 40;
+  return base - -2;
+}
+"#;
+    assert_eq!(run_main(source), -38);
+}
+
+#[test]
+fn assignments_update_locals() {
+    let source = r#"
+fn main() -> i64 {
+  let mut total = 1;
+  total = total + 4;
+  total *= 3;
+  total -= 1;
+  return total;
+}
+"#;
+    assert_eq!(run_main(source), 14);
+}
+
+#[test]
+fn export_attribute_renames_export() {
+    let source = r#"
+@export("foo")
+fn add_one(x: i64) -> i64 {
+  return x + 1;
+}
+"#;
+    let wasm = compile_to_wasm(source).expect("compile ok");
+    let engine = Engine::default();
+    let module = Module::new(&engine, wasm).expect("wasm module");
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[]).expect("instance");
+    assert!(instance.get_func(&mut store, "add_one").is_none());
+    let foo = instance
+        .get_typed_func::<i64, i64>(&mut store, "foo")
+        .expect("foo func");
+    assert_eq!(foo.call(&mut store, 41).expect("call foo"), 42);
+}
+
+#[test]
+fn extern_attribute_emits_import() {
+    let source = r#"
+@extern("env", "host_double")
+fn double(x: i64) -> i64;
+
+fn main() -> i64 {
+  return double(21);
+}
+"#;
+    let wasm = compile_to_wasm(source).expect("compile ok");
+    let engine = Engine::default();
+    let module = Module::new(&engine, wasm).expect("wasm module");
+    let mut store = Store::new(&engine, ());
+    let host = Func::wrap(&mut store, |x: i64| x * 2);
+    let instance = Instance::new(&mut store, &module, &[host.into()]).expect("instance");
+    let main = instance
+        .get_typed_func::<(), i64>(&mut store, "main")
+        .expect("main func");
+    assert_eq!(main.call(&mut store, ()).expect("call main"), 42);
+}
+
+#[test]
+fn bodiless_fn_without_extern_is_rejected() {
+    let source = "fn nope() -> i64;\nfn main() -> i64 { return 0; }\n";
+    let errs = compile_to_wasm(source).unwrap_err();
+    assert!(errs.iter().any(|d| d.code == "E2006"));
+}
+
+#[test]
+fn module_interface_lists_exports_and_imports() {
+    let source = r#"
+@extern("env", "log")
+fn log(x: i64) -> i64;
+
+@export("add")
+fn add(a: i64, b: i64) -> i64 {
+  return a + b;
+}
+
+fn main() -> i64 {
+  return add(1, 2);
+}
+"#;
+    let interface = module_interface(source).expect("interface");
+    let sig = |name: &str, params: &[&str]| ExportedFn {
+        name: name.to_string(),
+        params: params.iter().map(|p| p.to_string()).collect(),
+        result: "i64".to_string(),
+    };
+    assert_eq!(interface.exports, vec![sig("add", &["i64", "i64"]), sig("main", &[])]);
+    assert_eq!(
+        interface.imports,
+        vec![ImportedFn {
+            module: "env".to_string(),
+            field: "log".to_string(),
+            params: vec!["i64".to_string()],
+            result: "i64".to_string(),
+        }]
+    );
+}
+
+#[test]
+fn attribute_strings_decode_escapes() {
+    let source = "@extern(\"env\", \"log\\x21\")\nfn log(x: i64) -> i64;\n\n@export(r#\"add\\n\"#)\nfn add(a: i64) -> i64 { log(a) }\n\n@export(\"say \\\"hi\\\"\")\nfn hi() -> i64 { 0 }";
+    let interface = module_interface(source).expect("interface");
+    assert_eq!(interface.imports[0].field, "log!");
+    let names: Vec<_> = interface.exports.iter().map(|e| e.name.as_str()).collect();
+    assert_eq!(names, ["add\\n", "say \"hi\""]);
+}
+
+#[test]
+fn wide_integers_report_e2007() {
+    let source = r#"
+fn main() -> i64 {
+  let big: i128 = 0;
+  return 0;
+}
+"#;
+    let errs = compile_to_wasm(source).unwrap_err();
+    let diag = errs.iter().find(|d| d.code == "E2007").expect("E2007");
+    let span = diag.span.expect("span");
+    assert_eq!(&source[span.start..span.end], "i128");
+}
+
+#[test]
+fn omitted_arguments_use_parameter_defaults() {
+    let source = r#"
+fn scale(x: i64 = 5, factor: i64 = 10) -> i64 {
+  return x * factor;
+}
+
+fn main() -> i64 {
+  return scale() + scale(1) + scale(2, 3);
+}
+"#;
+    assert_eq!(run_main(source), 50 + 10 + 6);
+}
+
+#[test]
+fn defaults_are_computed_in_the_callee() {
+    // `b`'s default reads the callee's `a`, not the caller's local of the same name.
+    let source = r#"
+fn f(a: i64, b: i64 = a * 2) -> i64 {
+  return a + b;
+}
+
+fn main() -> i64 {
+  let a: i64 = 100;
+  return f(3) + f(1, a);
+}
+"#;
+    assert_eq!(run_main(source), 3 + 6 + 1 + 100);
+}
+
+#[test]
+fn omitting_a_parameter_without_default_is_e2008() {
+    let source = "fn f(a: i64, b: i64 = 1) -> i64 {\n  return a + b;\n}\n\nfn main() -> i64 {\n  return f() + f(1, 2, 3);\n}";
+    let errs = compile_to_wasm(source).unwrap_err();
+    let messages: Vec<_> = errs.iter().map(|d| (d.code.as_str(), d.message.as_str())).collect();
+    assert_eq!(messages, [("E2008", "f expects 2 arguments, found 0"), ("E2008", "f expects 2 arguments, found 3")]);
+}
+
+fn read_leb(bytes: &[u8], pos: &mut usize) -> u64 {
+    let mut result = 0;
+    let mut shift = 0;
+    loop {
+        let byte = bytes[*pos];
+        *pos += 1;
+        result |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return result;
+        }
+        shift += 7;
+    }
+}
+
+/// Returns the payload of every active data segment in a module.
+fn data_segments(wasm: &[u8]) -> Vec<Vec<u8>> {
+    let mut pos = 8;
+    let mut segments = Vec::new();
+    while pos < wasm.len() {
+        let id = wasm[pos];
+        pos += 1;
+        let size = read_leb(wasm, &mut pos) as usize;
+        let end = pos + size;
+        if id == 11 {
+            for _ in 0..read_leb(wasm, &mut pos) {
+                assert_eq!(read_leb(wasm, &mut pos), 0, "active segment for memory 0");
+                while wasm[pos] != 0x0b {
+                    pos += 1;
+                }
+                pos += 1;
+                let len = read_leb(wasm, &mut pos) as usize;
+                segments.push(wasm[pos..pos + len].to_vec());
+                pos += len;
+            }
+        }
+        pos = end;
+    }
+    segments
+}
+
+#[test]
+fn byte_string_literal_lowers_to_data_segment() {
+    let source = r#"
+fn main() -> i64 {
+  let raw = b"\x00\x01";
+  return raw;
+}
+"#;
+    let wasm = compile_to_wasm(source).expect("compile ok");
+    assert_eq!(data_segments(&wasm), vec![vec![0x00, 0x01]]);
+
+    let packed = run_main(source);
+    let (ptr, len) = ((packed >> 32) as usize, (packed & 0xffff_ffff) as usize);
+    let engine = Engine::default();
+    let module = Module::new(&engine, &wasm).expect("wasm module");
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[]).expect("instance");
+    let memory = instance.get_memory(&mut store, "memory").expect("memory export");
+    assert_eq!(&memory.data(&store)[ptr..ptr + len], &[0x00, 0x01]);
+}
+
+#[test]
+fn bool_return_compiles_to_i32_result() {
+    let source = r#"
+@export("lte")
+fn lte(a: i64, b: i64) -> bool {
+  a <= b
+}
+"#;
+    let wasm = compile_to_wasm(source).expect("compile ok");
+    let engine = Engine::default();
+    let module = Module::new(&engine, wasm).expect("wasm module");
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[]).expect("instance");
+    let lte = instance
+        .get_typed_func::<(i64, i64), i32>(&mut store, "lte")
+        .expect("lte func");
+    assert_eq!(lte.call(&mut store, (1, 2)).expect("call lte"), 1);
+    assert_eq!(lte.call(&mut store, (3, 2)).expect("call lte"), 0);
+}
+
+#[test]
+fn float_comparisons_use_f64_instructions() {
+    let source = r#"
+@export("lt")
+fn lt() -> bool {
+  1.5 < 2.5
+}
+
+@export("ge")
+fn ge(a: f64, b: f64) -> bool {
+  let scaled = a * 2.0;
+  scaled >= b
+}
+"#;
+    let wasm = compile_to_wasm(source).expect("compile ok");
+    let engine = Engine::default();
+    Module::validate(&engine, &wasm).expect("valid module");
+    let module = Module::new(&engine, wasm).expect("wasm module");
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[]).expect("instance");
+    let lt = instance.get_typed_func::<(), i32>(&mut store, "lt").expect("lt func");
+    assert_eq!(lt.call(&mut store, ()).expect("call lt"), 1);
+    let ge = instance.get_typed_func::<(f64, f64), i32>(&mut store, "ge").expect("ge func");
+    assert_eq!(ge.call(&mut store, (1.5, 3.0)).expect("call ge"), 1);
+    assert_eq!(ge.call(&mut store, (1.0, 2.5)).expect("call ge"), 0);
+}
+
+#[test]
+fn unannotated_lets_take_the_type_of_params_and_calls() {
+    let source = r#"
+fn half(x: f64) -> f64 {
+  x / 2.0
+}
+
+@export("mean")
+fn mean(a: f64, b: f64) -> f64 {
+  let copy = a;
+  let sum = copy + b;
+  let h = half(sum);
+  h
+}
+"#;
+    let wasm = compile_to_wasm(source).expect("compile ok");
+    let engine = Engine::default();
+    let module = Module::new(&engine, wasm).expect("wasm module");
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[]).expect("instance");
+    let mean = instance.get_typed_func::<(f64, f64), f64>(&mut store, "mean").expect("mean func");
+    assert_eq!(mean.call(&mut store, (1.5, 2.5)).expect("call mean"), 2.0);
+}
+
+#[test]
+fn f64_remainder_is_e2011() {
+    let errs = compile_to_wasm("fn f(a: f64, b: f64) -> f64 {\n  a % b\n}").unwrap_err();
+    let codes: Vec<_> = errs.iter().map(|d| (d.code.as_str(), d.message.as_str())).collect();
+    assert_eq!(codes, [("E2011", "unsupported f64 remainder")]);
+}
+
+#[test]
+fn signatures_follow_declared_types() {
+    let source = r#"
+@export("narrow")
+fn narrow(x: i32) -> i32 {
+  x
+}
+
+@export("scale")
+fn scale(x: f64) -> f64 {
+  x
+}
+
+@export("noop")
+fn noop() {
+}
+"#;
+    let interface = module_interface(source).expect("interface ok");
+    let signatures: Vec<(&str, Vec<&str>, &str)> = interface
+        .exports
+        .iter()
+        .map(|f| (f.name.as_str(), f.params.iter().map(String::as_str).collect(), f.result.as_str()))
+        .collect();
+    assert_eq!(
+        signatures,
+        [("narrow", vec!["i32"], "i32"), ("scale", vec!["f64"], "f64"), ("noop", vec![], "()")]
+    );
+
+    let wasm = compile_to_wasm(source).expect("compile ok");
+    let engine = Engine::default();
+    let module = Module::new(&engine, wasm).expect("wasm module");
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[]).expect("instance");
+    let narrow = instance.get_typed_func::<i32, i32>(&mut store, "narrow").expect("narrow func");
+    assert_eq!(narrow.call(&mut store, 7).expect("call narrow"), 7);
+    let scale = instance.get_typed_func::<f64, f64>(&mut store, "scale").expect("scale func");
+    assert_eq!(scale.call(&mut store, 1.5).expect("call scale"), 1.5);
+    let noop = instance.get_typed_func::<(), ()>(&mut store, "noop").expect("noop func");
+    noop.call(&mut store, ()).expect("call noop");
+}
+
+#[test]
+fn unsupported_signature_types_are_rejected() {
+    let errs = compile_to_wasm("fn f(s: string) -> string { s }").expect_err("should fail");
+    let codes: Vec<&str> = errs.iter().map(|d| d.code.as_str()).collect();
+    assert_eq!(codes, ["E2002", "E2003"]);
+}
+
+#[test]
+fn unit_function_calls_are_not_dropped() {
+    let source = r#"
+@export("greet")
+fn greet(times: i64) {
+  times + 1;
+}
+
+fn main() -> i64 {
+  greet(2);
+  return 7;
+}
+"#;
+    assert_eq!(run_main(source), 7);
+
+    let wasm = compile_to_wasm(source).expect("compile ok");
+    let engine = Engine::default();
+    let module = Module::new(&engine, wasm).expect("wasm module");
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[]).expect("instance");
+    let greet = instance.get_typed_func::<i64, ()>(&mut store, "greet").expect("greet func");
+    greet.call(&mut store, 1).expect("call greet");
+}
+
+#[test]
+fn memcpy_and_memset_builtins_use_bulk_memory() {
+    let source = r#"
+fn main() -> i64 {
+  let raw = b"jalm";
+  memcpy(64, 8, 4);
+  memset(68, 33, 2);
+  return raw;
+}
+"#;
+    let wasm = compile_to_wasm(source).expect("compile ok");
+    let mut config = wasmtime::Config::new();
+    config.wasm_bulk_memory(true);
+    let engine = Engine::new(&config).expect("engine");
+    let module = Module::new(&engine, &wasm).expect("wasm module");
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[]).expect("instance");
+    let main = instance.get_typed_func::<(), i64>(&mut store, "main").expect("main func");
+    let packed = main.call(&mut store, ()).expect("call main");
+    assert_eq!(packed >> 32, 8, "byte string placed at the copy source");
+    let memory = instance.get_memory(&mut store, "memory").expect("memory export");
+    assert_eq!(&memory.data(&store)[64..70], b"jalm!!");
+}
+
+#[test]
+fn memory_builtin_arity_is_checked() {
+    let errs = compile_to_wasm("fn main() {\n  memcpy(0, 8);\n}").unwrap_err();
+    assert!(errs.iter().any(|d| d.code == "E2008"));
+    // Without a byte string the builtin alone still declares a memory.
+    let wasm = compile_to_wasm("fn main() {\n  memset(0, 0, 16);\n}").expect("compile ok");
+    Module::new(&Engine::default(), wasm).expect("valid module");
+}
+
+#[test]
+fn prefixed_integer_literals_lower_to_their_values() {
+    let source = r#"
+fn main() -> i64 {
+  return 0xFF + 0o17 + 0b1010 + 1_000;
+}
+"#;
+    assert_eq!(run_main(source), 255 + 15 + 10 + 1000);
+}
+
+#[test]
+fn no_host_module_rejects_host_calls() {
+    let source = "@no_host;\n\nfn main() -> i64 {\n  log::info(1);\n  0\n}";
+    let errs = compile_to_wasm(source).unwrap_err();
+    assert_eq!(errs.len(), 1);
+    assert_eq!(errs[0].code, "E2009");
+    let span = errs[0].span.expect("span");
+    assert_eq!(&source[span.start..span.end], "log::info");
+    assert_eq!(run_main("@no_host;\n\nfn main() -> i64 {\n  return 7;\n}"), 7);
+}
+
+#[test]
+fn panic_builtin_traps() {
+    let source = r#"
+fn main() -> i64 {
+  let x = 1;
+  if x == 1 {
+    panic(7);
+  }
+  return 0;
+}
+"#;
+    let wasm = compile_to_wasm(source).expect("compile ok");
+    let engine = Engine::default();
+    let module = Module::new(&engine, wasm).expect("wasm module");
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[]).expect("instance");
+    let main = instance
+        .get_typed_func::<(), i64>(&mut store, "main")
+        .expect("main func");
+    assert!(main.call(&mut store, ()).is_err());
+
+    let in_value_position = r#"
+fn main() -> i64 {
+  return panic();
+}
+"#;
+    assert!(compile_to_wasm(in_value_position).is_ok());
+}
+
+#[test]
+fn assert_builtin_traps_only_when_false() {
+    let passing = r#"
+fn main() -> i64 {
+  assert(1 == 1);
+  return 5;
+}
+"#;
+    assert_eq!(run_main(passing), 5);
+
+    let failing = r#"
+fn main() -> i64 {
+  assert(1 == 2);
+  return 5;
+}
+"#;
+    let wasm = compile_to_wasm(failing).expect("compile ok");
+    let engine = Engine::default();
+    let module = Module::new(&engine, wasm).expect("wasm module");
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[]).expect("instance");
+    let main = instance
+        .get_typed_func::<(), i64>(&mut store, "main")
+        .expect("main func");
+    assert!(main.call(&mut store, ()).is_err());
+}
+
+#[test]
+fn compilation_is_reproducible() {
+    let source = r#"
+@extern("env", "log")
+fn log(v: i64);
+fn helper(a: i64, b: i64 = 2) -> i64 {
+  return a * b;
+}
+fn main() -> i64 {
+  log(1);
+  let x = helper(3);
+  let msg = b"hi";
+  return x;
+}
+@extern("env", "now")
+fn now() -> i64;
+"#;
+    let first = compile_to_wasm(source).expect("compile ok");
+    let second = compile_to_wasm(source).expect("compile ok");
+    assert_eq!(first, second);
+    let names: Vec<String> = module_interface(source)
+        .expect("interface")
+        .imports
+        .into_iter()
+        .map(|i| i.field)
+        .collect();
+    assert_eq!(names, ["log", "now"]);
+}
+
+#[test]
+fn unreachable_functions_are_left_out_of_the_module() {
+    let live = "fn helper(a: i64) -> i64 {\n  a + 1\n}\nfn main() -> i64 {\n  helper(41)\n}\n";
+    let with_dead = format!("fn unused(a: i64) -> i64 {{\n  a / helper(a)\n}}\n{live}");
+    let options = CompileOptions { checked_arithmetic: true, ..CompileOptions::default() };
+    let expected = compile_to_wasm_with_options(live, &options).expect("compile ok");
+    assert_eq!(compile_to_wasm_with_options(&with_dead, &options).expect("compile ok"), expected);
+    assert_eq!(run_main(&with_dead), 42);
+
+    let broken = format!("fn unused() -> i64 {{\n  missing()\n}}\n{live}");
+    let errs = compile_to_wasm(&broken).unwrap_err();
+    assert_eq!(errs.iter().map(|d| d.code.as_str()).collect::<Vec<_>>(), ["E2005"]);
+}
+
+#[test]
+fn debug_build_maps_instructions_to_spans() {
+    let source = r#"
+fn double(a: i64) -> i64 {
+  return a * 2;
+}
+fn main() -> i64 {
+  let x = double(21);
+  assert(x == 42);
+  return x;
+}
+"#;
+    assert_eq!(read_source_map(&compile_to_wasm(source).expect("compile ok")), None);
+    let wasm = compile_to_wasm_with_options(source, &CompileOptions { debug: true, ..CompileOptions::default() }).expect("compile ok");
+    Module::new(&Engine::default(), &wasm).expect("debug module is valid");
+    let entries = read_source_map(&wasm).expect("source map section");
+    let texts: Vec<(u32, &str)> = entries.iter().map(|e| (e.func_index, &source[e.span.start..e.span.end])).collect();
+    assert_eq!(
+        texts,
+        [(0, "a"), (0, "a * 2"), (1, "double(21)"), (1, "x"), (1, "x == 42"), (1, "assert(x == 42)"), (1, "x")]
+    );
+    assert!(entries.windows(2).all(|w| w[0].func_index < w[1].func_index || w[0].offset <= w[1].offset));
+}
+
+#[test]
+fn tuple_return_lowers_to_multi_value() {
+    let source = r#"
+fn divmod(a: i64, b: i64) -> (i64, i64) {
+  return (a / b, a - a / b * b);
+}
+fn main() -> i64 {
+  let (q, r) = divmod(17, 5);
+  let (_, again) = divmod(9, 4);
+  divmod(1, 1);
+  return q * 100 + r * 10 + again;
+}
+"#;
+    assert_eq!(run_main(source), 321);
+    let interface = module_interface("@export(\"divmod\")\nfn divmod(a: i64, b: i64) -> (i64, i64) { (a / b, a) }").unwrap();
+    assert_eq!(interface.exports[0].result, "(i64, i64)");
+}
+
+#[test]
+fn tuple_let_takes_element_types_from_the_callee() {
+    let source = r#"
+fn split(x: f64) -> (f64, i64) {
+  (x * 2.0, 7)
+}
+
+@export("run")
+fn run(x: f64) -> f64 {
+  let (doubled, n) = split(x);
+  doubled + 1.0
+}
+"#;
+    let wasm = compile_to_wasm(source).expect("compile ok");
+    let engine = Engine::default();
+    let module = Module::new(&engine, wasm).expect("wasm module");
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[]).expect("instance");
+    let run = instance.get_typed_func::<f64, f64>(&mut store, "run").expect("run func");
+    assert_eq!(run.call(&mut store, 1.25).expect("call run"), 3.5);
+}
+
+#[test]
+fn tuple_bound_to_one_name_is_e2012() {
+    let source = "fn pair() -> (i64, i64) {\n  (1, 2)\n}\n\nfn main() -> i64 {\n  let t = pair();\n  0\n}";
+    let errs = compile_to_wasm(source).unwrap_err();
+    assert_eq!(errs.len(), 1);
+    assert_eq!(errs[0].code, "E2012");
+    let span = errs[0].span.expect("span");
+    assert_eq!(&source[span.start..span.end], "pair()");
+}
+
+#[test]
+fn u64_division_uses_unsigned_instructions() {
+    let source = r#"
+@export("div")
+fn div(a: u64, b: u64) -> u64 {
+  a / b
+}
+
+@export("rem")
+fn rem(a: u64, b: u64) -> u64 {
+  let r: u64 = a % b;
+  r
+}
+
+@export("sdiv")
+fn sdiv(a: i64, b: i64) -> i64 {
+  a / b
+}
+"#;
+    let wasm = compile_to_wasm(source).expect("compile ok");
+    let engine = Engine::default();
+    let module = Module::new(&engine, wasm).expect("wasm module");
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[]).expect("instance");
+    // u64::MAX - 1 has the high bit set; as an i64 it is -2.
+    let big = (u64::MAX - 1) as i64;
+    let div = instance.get_typed_func::<(i64, i64), i64>(&mut store, "div").expect("div func");
+    assert_eq!(div.call(&mut store, (big, 2)).expect("call div") as u64, (u64::MAX - 1) / 2);
+    let rem = instance.get_typed_func::<(i64, i64), i64>(&mut store, "rem").expect("rem func");
+    assert_eq!(rem.call(&mut store, (big, 10)).expect("call rem") as u64, (u64::MAX - 1) % 10);
+    let sdiv = instance.get_typed_func::<(i64, i64), i64>(&mut store, "sdiv").expect("sdiv func");
+    assert_eq!(sdiv.call(&mut store, (big, 2)).expect("call sdiv"), -1);
+    // Signed overflow traps rather than wrapping.
+    assert!(sdiv.call(&mut store, (i64::MIN, -1)).is_err());
+}
+
+#[test]
+fn u64_signedness_follows_values_not_annotations() {
+    let source = r#"
+fn half(a: u64) -> u64 {
+  a / 2
+}
+
+@export("copy")
+fn copy(a: u64) -> u64 {
+  let y = a;
+  y / 2
+}
+
+@export("call")
+fn call(a: u64) -> u64 {
+  half(a) / 2
+}
+
+@export("nested")
+fn nested(a: u64) -> u64 {
+  let y = half(a) + 1;
+  (y + half(y)) / 3
+}
+
+@export("shadow")
+fn shadow(a: u64, b: i64) -> i64 {
+  if b == 0 {
+    let q: u64 = a / 2;
+    return 0;
+  }
+  let q: i64 = b;
+  q / 2
+}
+"#;
+    let wasm = compile_to_wasm(source).expect("compile ok");
+    let engine = Engine::default();
+    let module = Module::new(&engine, wasm).expect("wasm module");
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[]).expect("instance");
+    let big = u64::MAX - 1;
+    let copy = instance.get_typed_func::<i64, i64>(&mut store, "copy").expect("copy func");
+    assert_eq!(copy.call(&mut store, big as i64).expect("call copy") as u64, big / 2);
+    let call = instance.get_typed_func::<i64, i64>(&mut store, "call").expect("call func");
+    assert_eq!(call.call(&mut store, big as i64).expect("call call") as u64, big / 4);
+    let nested = instance.get_typed_func::<i64, i64>(&mut store, "nested").expect("nested func");
+    let y = big / 2 + 1;
+    assert_eq!(nested.call(&mut store, big as i64).expect("call nested") as u64, (y + y / 2) / 3);
+    // The `u64` `q` in the branch does not make the later `i64` `q` unsigned.
+    let shadow = instance.get_typed_func::<(i64, i64), i64>(&mut store, "shadow").expect("shadow func");
+    assert_eq!(shadow.call(&mut store, (big as i64, -4)).expect("call shadow"), -2);
+}
+
+#[test]
+fn i32_to_i64_widening_sign_extends() {
+    let source = r#"
+fn narrow(a: i32) -> i32 {
+  a
+}
+
+@export("widen")
+fn widen(a: i32) -> i64 {
+  let x: i64 = narrow(a);
+  x + add(a, 1)
+}
+
+fn add(a: i64, b: i64) -> i64 {
+  a + b
+}
+"#;
+    assert!(jalm_typecheck::check(source).diagnostics.is_empty());
+    let wasm = compile_to_wasm(source).expect("compile ok");
+    // Without `i64.extend_i32_s` at the `let` and the argument, the module would not validate.
+    let engine = Engine::default();
+    let module = Module::new(&engine, wasm).expect("wasm module");
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[]).expect("instance");
+    let widen = instance.get_typed_func::<i32, i64>(&mut store, "widen").expect("widen func");
+    assert_eq!(widen.call(&mut store, -5).expect("call widen"), -9);
+}
+
+#[test]
+fn integer_literals_in_i32_context_are_i32_constants() {
+    let source = r#"
+@export("inc")
+fn inc(y: i32) -> i32 {
+  let step: i32 = 2 * 3;
+  y + 1 + step
+}
+
+@export("small")
+fn small(y: i32) -> bool {
+  10 > y
+}
+
+@export("seven")
+fn seven() -> i32 {
+  inc(0)
+}
+"#;
+    assert!(jalm_typecheck::check(source).diagnostics.is_empty());
+    let wasm = compile_to_wasm(source).expect("compile ok");
+    let engine = Engine::default();
+    let module = Module::new(&engine, wasm).expect("wasm module");
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[]).expect("instance");
+    let inc = instance.get_typed_func::<i32, i32>(&mut store, "inc").expect("inc func");
+    assert_eq!(inc.call(&mut store, 5).expect("call inc"), 12);
+    let small = instance.get_typed_func::<i32, i32>(&mut store, "small").expect("small func");
+    assert_eq!(small.call(&mut store, 3).expect("call small"), 1);
+    assert_eq!(small.call(&mut store, 30).expect("call small"), 0);
+    let seven = instance.get_typed_func::<(), i32>(&mut store, "seven").expect("seven func");
+    assert_eq!(seven.call(&mut store, ()).expect("call seven"), 7);
+}
+
+#[test]
+fn c_style_enum_variants_lower_to_i32_constants() {
+    let source = r#"
+enum Color {
+  Red = 1;
+  Green;
+  Blue = 10;
+}
+
+@export("green")
+fn green() -> Color {
+  Color::Green
+}
+
+@export("blue")
+fn blue() -> Color {
+  let c = Color::Blue;
+  c
+}
+
+@export("same")
+fn same(c: Color) -> bool {
+  c == Color::Green
+}
+"#;
+    assert!(jalm_typecheck::check(source).diagnostics.is_empty());
+    let wasm = compile_to_wasm(source).expect("compile ok");
+    let engine = Engine::default();
+    let module = Module::new(&engine, wasm).expect("wasm module");
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[]).expect("instance");
+    let green = instance.get_typed_func::<(), i32>(&mut store, "green").expect("green func");
+    assert_eq!(green.call(&mut store, ()).expect("call green"), 2);
+    let blue = instance.get_typed_func::<(), i32>(&mut store, "blue").expect("blue func");
+    assert_eq!(blue.call(&mut store, ()).expect("call blue"), 10);
+    let same = instance.get_typed_func::<i32, i32>(&mut store, "same").expect("same func");
+    assert_eq!(same.call(&mut store, 2).expect("call same"), 1);
+    assert_eq!(same.call(&mut store, 1).expect("call same"), 0);
+}
+
+#[test]
+fn type_aliases_lower_to_their_primitive() {
+    let source = r#"
+type Id = i64;
+type Key = Id;
+
+@export("next")
+fn next(k: Key) -> Id {
+  let step: Key = 1;
+  k + step
+}
+"#;
+    assert!(jalm_typecheck::check(source).diagnostics.is_empty());
+    let wasm = compile_to_wasm(source).expect("compile ok");
+    let engine = Engine::default();
+    let module = Module::new(&engine, wasm).expect("wasm module");
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[]).expect("instance");
+    let next = instance.get_typed_func::<i64, i64>(&mut store, "next").expect("next func");
+    assert_eq!(next.call(&mut store, 41).expect("call next"), 42);
+}
+
+#[test]
+fn generic_functions_get_one_copy_per_type_argument() {
+    let source = r#"
+fn id<T>(x: T) -> T {
+  x
+}
+
+fn wrap<T>(x: T) -> T {
+  id::<T>(x)
+}
+
+@export("int")
+fn int(x: i64) -> i64 {
+  wrap::<i64>(x) + id::<i64>(x)
+}
+
+@export("float")
+fn float(x: f64) -> f64 {
+  id::<f64>(x) + 0.5
+}
+"#;
+    assert!(jalm_typecheck::check(source).diagnostics.is_empty());
+    let wasm = compile_to_wasm(source).expect("compile ok");
+    let engine = Engine::default();
+    let module = Module::new(&engine, wasm).expect("wasm module");
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[]).expect("instance");
+    let int = instance.get_typed_func::<i64, i64>(&mut store, "int").expect("int func");
+    assert_eq!(int.call(&mut store, 21).expect("call int"), 42);
+    let float = instance.get_typed_func::<f64, f64>(&mut store, "float").expect("float func");
+    assert_eq!(float.call(&mut store, 1.25).expect("call float"), 1.75);
+}
+
+#[test]
+fn generic_type_arguments_may_be_tuples() {
+    let source = "fn second<T, U>(x: U) -> U {\n  x\n}\n\nfn main() -> i64 {\n  second::<(i64, i64), i64>(42)\n}";
+    assert_eq!(run_main(source), 42);
+}
+
+#[test]
+fn exported_generic_functions_are_e2015() {
+    let source = "@export(\"id\")\nfn id<T>(x: T) -> T {\n  x\n}\n\nfn main() -> i64 {\n  id::<i64>(1)\n}";
+    let errs = compile_to_wasm(source).unwrap_err();
+    let found: Vec<_> = errs.iter().map(|d| (d.code.as_str(), &source[d.span.unwrap().start..d.span.unwrap().end])).collect();
+    assert_eq!(found, [("E2015", "@export(\"id\")")]);
+}
+
+#[test]
+fn methods_take_the_receiver_fields_as_leading_params() {
+    let source = r#"
+struct Point {
+  x: i64;
+  y: i64;
+}
+
+impl Point {
+  fn sum(self) -> i64 {
+    self.x + self.y
+  }
+
+  fn scaled_sum(self, k: i64) -> i64 {
+    self.sum() * k
+  }
+}
+
+fn dot(a: Point, b: Point) -> i64 {
+  a.x * b.x + a.y * b.y
+}
+
+@export("sum")
+fn sum(p: Point) -> i64 {
+  p.sum()
+}
+
+@export("mix")
+fn mix(p: Point, k: i64) -> i64 {
+  p.scaled_sum(k) + dot(p, p)
+}
+"#;
+    assert!(jalm_typecheck::check(source).diagnostics.is_empty());
+    let wasm = compile_to_wasm(source).expect("compile ok");
+    let engine = Engine::default();
+    let module = Module::new(&engine, wasm).expect("wasm module");
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[]).expect("instance");
+    let sum = instance.get_typed_func::<(i64, i64), i64>(&mut store, "sum").expect("sum func");
+    assert_eq!(sum.call(&mut store, (3, 4)).expect("call sum"), 7);
+    let mix = instance.get_typed_func::<(i64, i64, i64), i64>(&mut store, "mix").expect("mix func");
+    assert_eq!(mix.call(&mut store, (3, 4, 10)).expect("call mix"), 70 + 25);
+}
+
+#[test]
+fn checked_division_by_zero_calls_jalm_panic() {
+    let source = r#"
+@export("div")
+fn div(a: i64, b: i64) -> i64 {
+  a / b
+}
+"#;
+    // Unchecked, the module has no imports and wasm's own trap fires.
+    let plain = compile_to_wasm(source).expect("compile ok");
+    let engine = Engine::default();
+    assert_eq!(Module::new(&engine, plain).expect("wasm module").imports().len(), 0);
+
+    let options = CompileOptions { checked_arithmetic: true, ..CompileOptions::default() };
+    let wasm = compile_to_wasm_with_options(source, &options).expect("compile ok");
+    let module = Module::new(&engine, wasm).expect("wasm module");
+    let mut store = Store::new(&engine, ());
+    let panic = Func::wrap(&mut store, |code: i32| -> wasmtime::Result<()> { Err(wasmtime::Error::msg(format!("jalm_panic({code})"))) });
+    let instance = Instance::new(&mut store, &module, &[panic.into()]).expect("instance");
+    let div = instance.get_typed_func::<(i64, i64), i64>(&mut store, "div").expect("div func");
+    assert_eq!(div.call(&mut store, (7, 2)).expect("call div"), 3);
+    let err = div.call(&mut store, (7, 0)).expect_err("division by zero traps");
+    assert!(format!("{err:?}").contains(&format!("jalm_panic({})", jalm_codegen::PANIC_DIVIDE_BY_ZERO)), "{err:?}");
+}
+
+#[test]
+fn features_select_gated_items() {
+    let source = r#"
+@cfg(feature = "extra")
+struct Pair { a: i64; b: i64; }
+
+@cfg(feature = "extra")
+impl Pair {
+  fn sum(self) -> i64 { self.a + self.b }
+}
+
+@cfg(feature = "extra")
+@export("extra")
+fn extra(p: Pair) -> i64 { p.sum() }
+
+@export("base")
+fn base() -> i64 { 1 }
+"#;
+    let exports = |options: &CompileOptions| {
+        let wasm = compile_to_wasm_with_options(source, options).expect("compile ok");
+        let module = Module::new(&Engine::default(), wasm).expect("wasm module");
+        module.exports().map(|e| e.name().to_string()).collect::<Vec<_>>()
+    };
+    assert_eq!(exports(&CompileOptions::default()), ["base"]);
+    let options = CompileOptions { features: vec!["extra".to_string()], ..CompileOptions::default() };
+    assert_eq!(exports(&options), ["extra", "base"]);
+}
+
+#[test]
+fn never_functions_have_no_results() {
+    let source = r#"
+@extern("env", "abort")
+fn abort(code: i32) -> never;
+
+fn fail() -> never {
+  abort(7)
+}
+
+@export("pick")
+fn pick(c: bool) -> i64 {
+  if c {
+    return 1;
+  }
+  fail()
+}
+"#;
+    let wasm = compile_to_wasm(source).expect("compile ok");
+    let engine = Engine::default();
+    let module = Module::new(&engine, wasm).expect("wasm module");
+    let mut store = Store::new(&engine, ());
+    let abort = Func::wrap(&mut store, |code: i32| -> wasmtime::Result<()> { Err(wasmtime::Error::msg(format!("abort({code})"))) });
+    let instance = Instance::new(&mut store, &module, &[abort.into()]).expect("instance");
+    let pick = instance.get_typed_func::<i32, i64>(&mut store, "pick").expect("pick func");
+    assert_eq!(pick.call(&mut store, 1).expect("call pick"), 1);
+    let err = pick.call(&mut store, 0).expect_err("fail traps");
+    assert!(format!("{err:?}").contains("abort(7)"), "{err:?}");
+}
+
+#[test]
+fn unsupported_expressions_are_e2013() {
+    let source = "fn main() -> i64 {\n  let f = |x: i64| x;\n  let v = if let 1 = 2 { 3 } else { 4 };\n  let b = 'l: { break 'l 5; };\n  0\n}";
+    let errs = compile_to_wasm(source).unwrap_err();
+    let found: Vec<_> = errs.iter().map(|d| (d.code.as_str(), &source[d.span.unwrap().start..d.span.unwrap().end])).collect();
+    assert_eq!(
+        found,
+        [("E2013", "|x: i64| x"), ("E2013", "if let 1 = 2 { 3 } else { 4 }"), ("E2013", "'l: { break 'l 5; }")]
+    );
+}
+
+#[test]
+fn struct_locals_are_e2014_at_the_let() {
+    let source = "struct P { x: i64, y: i64 }\nimpl P {\n  fn sum(self) -> i64 { self.x + self.y }\n}\nfn main() -> i64 {\n  let p = P { x: 1, y: 2 };\n  p.sum() + p.x\n}";
+    let errs = compile_to_wasm(source).unwrap_err();
+    let found: Vec<_> = errs.iter().map(|d| (d.code.as_str(), &source[d.span.unwrap().start..d.span.unwrap().end])).collect();
+    assert_eq!(found, [("E2014", "let p = P { x: 1, y: 2 };")]);
+}
@@ -1,7 +1,11 @@
-use jalm_parser::parse;
+use jalm_ast::{AstNode, Import};
+use jalm_parser::{parse, Parse};
 use jalm_syntax::{SyntaxElement, SyntaxKind, SyntaxNode};
 use serde::Serialize;
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+/// Version of this crate, reported by `jalmt version --verbose`.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct Diagnostic {
@@ -22,66 +26,205 @@ pub struct CheckResult {
     pub diagnostics: Vec<Diagnostic>,
 }
 
+/// Settings for [`check_parsed_with_options`].
+#[derive(Debug, Clone, Default)]
+pub struct CheckOptions {
+    /// Report `ns::` paths whose `ns` is not an effect namespace, `std`, an item, or an import.
+    pub strict_effects: bool,
+}
+
+/// Namespaces whose members perform an effect, and the effect each one requires.
+const EFFECT_NAMESPACES: &[(&str, &str)] = &[
+    ("fs", "fs"),
+    ("net", "net"),
+    ("http", "net"),
+    ("time", "time"),
+    ("rand", "rand"),
+    ("log", "io"),
+    ("ffi", "ffi"),
+];
+
+/// `effect set` aliases by name: the alias's name node and its member names.
+type Aliases = HashMap<String, (SyntaxNode, Vec<String>)>;
+
+/// Declared (alias-expanded) effects of each top-level function by name, and of each method
+/// by `Type::method`.
+type FnEffects = HashMap<String, HashSet<String>>;
+
 pub fn check(source: &str) -> CheckResult {
-    let parsed = parse(source);
+    check_parsed(&parse(source))
+}
+
+/// Like [`check`], for a caller that has already parsed the source.
+pub fn check_parsed(parsed: &Parse) -> CheckResult {
+    check_parsed_with_options(parsed, &CheckOptions::default())
+}
+
+pub fn check_parsed_with_options(parsed: &Parse, options: &CheckOptions) -> CheckResult {
     let root = parsed.syntax();
     let mut diagnostics = Vec::new();
-    for item in root.children() {
-        if item.kind() == SyntaxKind::FnDecl {
-            check_fn(&item, &mut diagnostics);
-        }
+    let aliases = collect_aliases(&root, &mut diagnostics);
+    // Methods are checked like functions, each with the type of its `impl` block.
+    let fns: Vec<(SyntaxNode, Option<String>)> = root
+        .children()
+        .flat_map(|n| match n.kind() {
+            SyntaxKind::ImplBlock => {
+                let ty = n.children().find(|c| c.kind() == SyntaxKind::IdentNode).and_then(|c| find_ident_text(&c));
+                n.children().map(|method| (method, ty.clone())).collect()
+            }
+            _ => vec![(n, None)],
+        })
+        .filter(|(n, _)| n.kind() == SyntaxKind::FnDecl)
+        .collect();
+    let fn_effects: FnEffects = fns
+        .iter()
+        .filter_map(|(f, ty)| {
+            let name = f.children().find(|n| n.kind() == SyntaxKind::IdentNode).and_then(|n| find_ident_text(&n))?;
+            let key = match ty {
+                Some(ty) => format!("{ty}::{name}"),
+                None => name,
+            };
+            Some((key, declared_effects(f, &aliases)))
+        })
+        .collect();
+    for (item, ty) in &fns {
+        check_fn(item, ty.as_deref(), &aliases, &fn_effects, &mut diagnostics);
+    }
+    if options.strict_effects {
+        check_namespaces(&root, &mut diagnostics);
     }
+    // Report in source order regardless of the order effects are scanned.
+    diagnostics.sort_by(|a, b| (a.span.start, &a.code).cmp(&(b.span.start, &b.code)));
     CheckResult { diagnostics }
 }
 
-fn check_fn(node: &SyntaxNode, diagnostics: &mut Vec<Diagnostic>) {
-    let declared = declared_effects(node);
+fn collect_aliases(root: &SyntaxNode, diagnostics: &mut Vec<Diagnostic>) -> Aliases {
+    let mut aliases = Aliases::new();
+    for decl in root.children().filter(|n| n.kind() == SyntaxKind::EffectAliasDecl) {
+        let mut idents = decl.children().filter(|n| n.kind() == SyntaxKind::IdentNode);
+        if let Some((name_node, name)) = idents.next().and_then(|n| find_ident_text(&n).map(|name| (n, name))) {
+            let members = idents.filter_map(|n| find_ident_text(&n)).collect();
+            aliases.insert(name, (name_node, members));
+        }
+    }
+    for (name, (name_node, _)) in &aliases {
+        if alias_reaches(&aliases, name, name, &mut HashSet::new()) {
+            diagnostics.push(Diagnostic {
+                code: "E1007".to_string(),
+                message: "cyclic effect alias".to_string(),
+                span: span_of(name_node),
+                required: name.clone(),
+            });
+        }
+    }
+    aliases
+}
+
+/// True if expanding alias `from` eventually refers back to `target`.
+fn alias_reaches(aliases: &Aliases, from: &str, target: &str, visited: &mut HashSet<String>) -> bool {
+    let Some((_, members)) = aliases.get(from) else {
+        return false;
+    };
+    for member in members {
+        if member == target {
+            return true;
+        }
+        if visited.insert(member.clone()) && alias_reaches(aliases, member, target, visited) {
+            return true;
+        }
+    }
+    false
+}
+
+fn check_fn(node: &SyntaxNode, self_type: Option<&str>, aliases: &Aliases, fn_effects: &FnEffects, diagnostics: &mut Vec<Diagnostic>) {
+    let declared = declared_effects(node, aliases);
     if let Some(block) = node.children().find(|n| n.kind() == SyntaxKind::Block) {
-        for (effect, span) in effects_used_in(&block) {
-            if !declared.contains(effect) {
+        // `async fn` carries the `async` capability; `await` needs it like an effect.
+        if !is_async(node) {
+            let awaits = block.descendants_with_tokens().filter_map(|e| e.into_token()).filter(|t| t.kind() == SyntaxKind::KwAwait);
+            for keyword in awaits {
+                let range = keyword.text_range();
                 diagnostics.push(Diagnostic {
-                    code: "E1001".to_string(),
-                    message: "undeclared effect".to_string(),
+                    code: "E1009".to_string(),
+                    message: "await requires async context".to_string(),
+                    span: Span { start: range.start().into(), end: range.end().into() },
+                    required: "async".to_string(),
+                });
+            }
+        }
+        // Closures run with the enclosing function's effects.
+        let closures: Vec<_> = block
+            .descendants()
+            .filter(|n| n.kind() == SyntaxKind::ClosureExpr)
+            .map(|n| n.text_range())
+            .collect();
+        let used = effects_used_in(&block)
+            .into_iter()
+            .map(|(effect, span)| (effect.to_string(), span))
+            .chain(call_effects_in(&block, &receiver_types(node, self_type), fn_effects));
+        for (effect, span) in used {
+            if !declared.contains(&effect) {
+                let in_closure = closures
+                    .iter()
+                    .any(|range| usize::from(range.start()) <= span.start && span.end <= usize::from(range.end()));
+                let (code, message) = if in_closure {
+                    ("E1006", "effect used in closure not declared by enclosing function")
+                } else {
+                    ("E1001", "undeclared effect")
+                };
+                diagnostics.push(Diagnostic {
+                    code: code.to_string(),
+                    message: message.to_string(),
                     span,
-                    required: effect.to_string(),
+                    required: effect,
                 });
             }
         }
     }
 }
 
-fn declared_effects(node: &SyntaxNode) -> HashSet<String> {
+fn is_async(node: &SyntaxNode) -> bool {
+    node.children_with_tokens().any(|e| e.kind() == SyntaxKind::KwAsync)
+}
+
+fn declared_effects(node: &SyntaxNode, aliases: &Aliases) -> HashSet<String> {
     let mut effects = HashSet::new();
     if let Some(effect_set) = node.children().find(|n| n.kind() == SyntaxKind::EffectSet) {
         for ident in effect_set.children().filter(|n| n.kind() == SyntaxKind::IdentNode) {
             if let Some(name) = find_ident_text(&ident) {
-                match name.as_str() {
-                    "io" | "net" | "fs" | "time" | "rand" | "ffi" => {
-                        effects.insert(name);
+                expand_effect(&name, aliases, &mut effects, &mut HashSet::new());
+            }
+        }
+    }
+    effects
+}
+
+/// Adds `name` to `effects`, expanding aliases transitively; `seen` stops cycles.
+fn expand_effect(name: &str, aliases: &Aliases, effects: &mut HashSet<String>, seen: &mut HashSet<String>) {
+    match name {
+        "io" | "net" | "fs" | "time" | "rand" | "ffi" => {
+            effects.insert(name.to_string());
+        }
+        _ => {
+            if let Some((_, members)) = aliases.get(name) {
+                if seen.insert(name.to_string()) {
+                    for member in members {
+                        expand_effect(member, aliases, effects, seen);
                     }
-                    _ => {}
                 }
             }
         }
     }
-    effects
 }
 
 fn effects_used_in(node: &SyntaxNode) -> Vec<(&'static str, Span)> {
     let mut effects = Vec::new();
     let text = node.text().to_string();
     let base: usize = node.text_range().start().into();
-    for (prefix, effect) in [
-        ("fs::", "fs"),
-        ("net::", "net"),
-        ("http::", "net"),
-        ("time::", "time"),
-        ("rand::", "rand"),
-        ("log::", "io"),
-        ("ffi::", "ffi"),
-    ] {
+    for &(namespace, effect) in EFFECT_NAMESPACES {
+        let prefix = format!("{namespace}::");
         let mut offset = 0;
-        while let Some(pos) = text[offset..].find(prefix) {
+        while let Some(pos) = text[offset..].find(&prefix) {
             let start = base + offset + pos;
             let end = start + prefix.len();
             effects.push((effect, Span { start, end }));
@@ -91,9 +234,112 @@ fn effects_used_in(node: &SyntaxNode) -> Vec<(&'static str, Span)> {
     effects
 }
 
+/// Type names of the locals in function `node` whose type is known without inference: `self`,
+/// typed parameters, and `let`s with a type annotation or a struct literal initializer.
+fn receiver_types(node: &SyntaxNode, self_type: Option<&str>) -> HashMap<String, String> {
+    let mut types = HashMap::new();
+    if let Some(ty) = self_type {
+        types.insert("self".to_string(), ty.to_string());
+    }
+    let params = node.children().filter(|n| n.kind() == SyntaxKind::ParamList).flat_map(|list| list.children());
+    let lets = node.descendants().filter(|n| n.kind() == SyntaxKind::LetStmt);
+    for binding in params.chain(lets) {
+        // A parameter's name, or a `let` pattern that is a single name.
+        let pattern = binding.children().find(|n| n.kind() == SyntaxKind::Pattern).filter(|p| p.children().count() == 1);
+        let name_node = pattern.as_ref().unwrap_or(&binding).children().find(|n| n.kind() == SyntaxKind::IdentNode);
+        let Some(name) = name_node.and_then(|n| find_ident_text(&n)) else {
+            continue;
+        };
+        let ty = binding.children().find(|n| n.kind() == SyntaxKind::Type).map(|n| n.text().to_string().trim().to_string());
+        let literal = || {
+            let lit = binding.children().find(|n| n.kind() == SyntaxKind::StructLit)?;
+            lit.children().find(|n| n.kind() == SyntaxKind::IdentNode).and_then(|n| find_ident_text(&n))
+        };
+        if let Some(ty) = ty.or_else(literal) {
+            types.insert(name, ty);
+        }
+    }
+    types
+}
+
+/// Effects required by calls `name(...)`, `Type::name(...)`, and member calls `recv.name(...)`,
+/// anywhere in `node` including `match` guards: those declared by the function or method
+/// called, reported at its name. A member call resolves through the receiver's type in
+/// `receivers`; when that is unknown it requires the effects of every function and method so named.
+fn call_effects_in(node: &SyntaxNode, receivers: &HashMap<String, String>, fn_effects: &FnEffects) -> Vec<(String, Span)> {
+    let mut effects = Vec::new();
+    for call in node.descendants().filter(|n| n.kind() == SyntaxKind::CallExpr) {
+        let Some(callee) = call.first_child() else {
+            continue;
+        };
+        let idents: Vec<_> = callee.children().filter(|n| n.kind() == SyntaxKind::IdentNode).collect();
+        let (name_node, keys) = match (callee.kind(), idents.as_slice()) {
+            (SyntaxKind::IdentNode, _) => match find_ident_text(&callee) {
+                Some(name) => (callee, vec![name]),
+                None => continue,
+            },
+            (SyntaxKind::PathExpr, [ty, name]) => match (find_ident_text(ty), find_ident_text(name)) {
+                (Some(ty), Some(method)) => (name.clone(), vec![format!("{ty}::{method}")]),
+                _ => continue,
+            },
+            (SyntaxKind::MemberExpr, [.., name]) => {
+                let Some(method) = find_ident_text(name) else {
+                    continue;
+                };
+                let receiver = callee.first_child().filter(|n| n.kind() == SyntaxKind::IdentNode).and_then(|n| find_ident_text(&n));
+                let keys = match receiver.and_then(|r| receivers.get(&r)) {
+                    Some(ty) => vec![format!("{ty}::{method}")],
+                    None => fn_effects.keys().filter(|key| key.rsplit("::").next() == Some(method.as_str())).cloned().collect(),
+                };
+                (name.clone(), keys)
+            }
+            _ => continue,
+        };
+        let declared: BTreeSet<_> = keys.iter().filter_map(|key| fn_effects.get(key)).flatten().cloned().collect();
+        effects.extend(declared.into_iter().map(|effect| (effect, span_of(&name_node))));
+    }
+    effects
+}
+
+/// Reports `E1008` for a path `ns::member` whose `ns` names nothing known, e.g. a typo like `ner::`.
+fn check_namespaces(root: &SyntaxNode, diagnostics: &mut Vec<Diagnostic>) {
+    let mut known: HashSet<String> = EFFECT_NAMESPACES.iter().map(|(ns, _)| ns.to_string()).collect();
+    known.insert("std".to_string());
+    // Items such as enums (`Color::Red`) and anything brought in by `use`, under its alias if any.
+    for item in root.children() {
+        if let Some(name) = item.children().find(|n| n.kind() == SyntaxKind::IdentNode).and_then(|n| find_ident_text(&n)) {
+            known.insert(name);
+        }
+    }
+    for import in root.descendants().filter_map(Import::cast) {
+        known.extend(import.bound_names().iter().filter_map(find_ident_text));
+    }
+    for path in root.descendants().filter(|n| n.kind() == SyntaxKind::PathExpr) {
+        let Some(ns_node) = path.children().find(|n| n.kind() == SyntaxKind::IdentNode) else {
+            continue;
+        };
+        if let Some(ns) = find_ident_text(&ns_node).filter(|ns| !known.contains(ns)) {
+            diagnostics.push(Diagnostic {
+                code: "E1008".to_string(),
+                message: "unknown effect namespace".to_string(),
+                span: span_of(&ns_node),
+                required: ns,
+            });
+        }
+    }
+}
+
 fn find_ident_text(node: &SyntaxNode) -> Option<String> {
     node.children_with_tokens().find_map(|e| match e {
         SyntaxElement::Token(t) if t.kind() == SyntaxKind::Ident => Some(t.text().to_string()),
         _ => None,
     })
 }
+
+fn span_of(node: &SyntaxNode) -> Span {
+    let range = node.text_range();
+    Span {
+        start: range.start().into(),
+        end: range.end().into(),
+    }
+}
@@ -1,30 +1,105 @@
+use jalm_ast::{AstNode, Root};
 use jalm_parser::{parse, ParseError};
-use jalm_syntax::{SyntaxElement, SyntaxKind, SyntaxNode};
+use jalm_syntax::{literal_value, normalize_literal_newlines, SyntaxElement, SyntaxKind, SyntaxNode};
+use std::fmt;
+use std::ops::Range;
+
+/// Version of this crate, reported by `jalmt version --verbose`.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
 #[derive(Debug)]
 pub enum FormatError {
     ParseErrors(Vec<ParseError>),
 }
 
+impl FormatError {
+    /// The parse errors that prevented formatting, with their spans.
+    pub fn errors(&self) -> &[ParseError] {
+        match self {
+            FormatError::ParseErrors(errors) => errors,
+        }
+    }
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let errors = self.errors();
+        write!(f, "cannot format source with {} parse error(s)", errors.len())?;
+        for err in errors {
+            write!(f, "\n  {}..{}: {}", err.span.start, err.span.end, err.message)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for FormatError {}
+
+/// Settings for [`format_source_with_options`].
+#[derive(Debug, Clone)]
+pub struct FormatOptions {
+    /// Keep a block holding a single statement or expression on one line, as in
+    /// `fn f() -> i64 { 1 }`, when the whole line fits within `max_width`.
+    pub compact_blocks: bool,
+    /// Line width, in characters, that a compact block must fit within.
+    pub max_width: usize,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self { compact_blocks: false, max_width: 100 }
+    }
+}
+
 pub fn format_source(source: &str) -> Result<String, FormatError> {
+    format_source_with_options(source, &FormatOptions::default())
+}
+
+pub fn format_source_with_options(source: &str, options: &FormatOptions) -> Result<String, FormatError> {
     let parsed = parse(source);
-    if !parsed.errors.is_empty() {
+    if !parsed.is_ok() {
         return Err(FormatError::ParseErrors(parsed.errors));
     }
-    let root = parsed.syntax();
-    let mut fmt = Formatter::new();
+    let root = parsed.ast();
+    let mut fmt = Formatter::new(options.clone());
     fmt.root(&root);
     Ok(fmt.finish())
 }
 
+/// Reformats the top-level items overlapping `range`, leaving the rest of the
+/// source byte-for-byte unchanged.
+pub fn format_range(source: &str, range: Range<usize>) -> Result<String, FormatError> {
+    let parsed = parse(source);
+    if !parsed.is_ok() {
+        return Err(FormatError::ParseErrors(parsed.errors));
+    }
+    let mut out = source.to_string();
+    let items: Vec<_> = parsed.ast().items().collect();
+    for item in items.iter().rev() {
+        let text_range = item.syntax().text_range();
+        let (start, end) = (usize::from(text_range.start()), usize::from(text_range.end()));
+        let overlaps = if range.is_empty() {
+            start <= range.start && range.start <= end
+        } else {
+            start < range.end && range.start < end
+        };
+        if overlaps {
+            let mut fmt = Formatter::new(FormatOptions::default());
+            fmt.item(item.syntax());
+            out.replace_range(start..end, &fmt.finish());
+        }
+    }
+    Ok(out)
+}
+
 struct Formatter {
     out: String,
     indent: usize,
+    options: FormatOptions,
 }
 
 impl Formatter {
-    fn new() -> Self {
-        Self { out: String::new(), indent: 0 }
+    fn new(options: FormatOptions) -> Self {
+        Self { out: String::new(), indent: 0, options }
     }
 
     fn finish(self) -> String {
@@ -42,24 +117,19 @@ impl Formatter {
         }
     }
 
-    fn root(&mut self, node: &SyntaxNode) {
+    fn root(&mut self, root: &Root) {
+        if let Some(shebang) = root.syntax().first_token().filter(|t| t.kind() == SyntaxKind::Shebang) {
+            self.push(shebang.text());
+            self.push("\n");
+        }
         let mut first = true;
-        for child in node.children() {
-            match child.kind() {
-                SyntaxKind::ModuleDecl
-                | SyntaxKind::UseDecl
-                | SyntaxKind::FnDecl
-                | SyntaxKind::StructDecl
-                | SyntaxKind::EnumDecl => {
-                    if !first {
-                        self.newline();
-                        self.newline();
-                    }
-                    self.item(&child);
-                    first = false;
-                }
-                _ => {}
+        for item in root.items() {
+            if !first {
+                self.newline();
+                self.newline();
             }
+            self.item(item.syntax());
+            first = false;
         }
     }
 
@@ -70,6 +140,16 @@ impl Formatter {
             SyntaxKind::FnDecl => self.fn_decl(node),
             SyntaxKind::StructDecl => self.struct_decl(node),
             SyntaxKind::EnumDecl => self.enum_decl(node),
+            SyntaxKind::EffectAliasDecl => self.effect_alias(node),
+            SyntaxKind::ConstDecl => self.const_decl(node),
+            SyntaxKind::TypeAlias => self.type_alias(node),
+            SyntaxKind::ImplBlock => self.impl_block(node),
+            SyntaxKind::ModuleAttribute => {
+                if let Some(attr) = node.children().find(|n| n.kind() == SyntaxKind::Attribute) {
+                    self.attribute(&attr);
+                }
+                self.push(";");
+            }
             _ => {}
         }
     }
@@ -87,6 +167,7 @@ impl Formatter {
     }
 
     fn use_decl(&mut self, node: &SyntaxNode) {
+        self.attributes(node);
         self.push("use ");
         if let Some(path) = format_use_path(node) {
             self.push(&path);
@@ -98,13 +179,28 @@ impl Formatter {
         self.push(";");
     }
 
-    fn fn_decl(&mut self, node: &SyntaxNode) {
-        let mut tokens = node.children_with_tokens();
-        let has_pub = tokens.clone().any(|e| matches!(e, SyntaxElement::Token(t) if t.kind() == SyntaxKind::KwPub));
-        let has_async = tokens.any(|e| matches!(e, SyntaxElement::Token(t) if t.kind() == SyntaxKind::KwAsync));
-        if has_pub {
-            self.push("pub ");
+    fn visibility(&mut self, node: &SyntaxNode) {
+        if let Some(vis) = node.children().find(|n| n.kind() == SyntaxKind::Visibility) {
+            self.push("pub");
+            if let Some(restriction) = vis
+                .children()
+                .find(|n| n.kind() == SyntaxKind::IdentNode)
+                .and_then(|n| first_ident_child_text(&n))
+            {
+                self.push("(");
+                self.push(&restriction);
+                self.push(")");
+            }
+            self.push(" ");
         }
+    }
+
+    fn fn_decl(&mut self, node: &SyntaxNode) {
+        let has_async = node
+            .children_with_tokens()
+            .any(|e| matches!(e, SyntaxElement::Token(t) if t.kind() == SyntaxKind::KwAsync));
+        self.attributes(node);
+        self.visibility(node);
         if has_async {
             self.push("async ");
         }
@@ -116,6 +212,12 @@ impl Formatter {
         {
             self.push(&name);
         }
+        if let Some(generics) = node.children().find(|n| n.kind() == SyntaxKind::GenericParamList) {
+            let names: Vec<String> = generics.children().filter_map(|n| first_ident_child_text(&n)).collect();
+            self.push("<");
+            self.push(&names.join(", "));
+            self.push(">");
+        }
         if let Some(params) = node.children().find(|n| n.kind() == SyntaxKind::ParamList) {
             self.push("(");
             self.param_list(&params);
@@ -134,6 +236,44 @@ impl Formatter {
         if let Some(block) = node.children().find(|n| n.kind() == SyntaxKind::Block) {
             self.push(" ");
             self.block(&block);
+        } else {
+            self.push(";");
+        }
+    }
+
+    /// The item's attributes, one per line.
+    fn attributes(&mut self, node: &SyntaxNode) {
+        for attr in node.children().filter(|n| n.kind() == SyntaxKind::Attribute) {
+            self.attribute(&attr);
+            self.newline();
+        }
+    }
+
+    fn attribute(&mut self, node: &SyntaxNode) {
+        self.push("@");
+        if let Some(name) = node
+            .children()
+            .find(|n| n.kind() == SyntaxKind::IdentNode)
+            .and_then(|n| first_ident_child_text(&n))
+        {
+            self.push(&name);
+        }
+        let args: Vec<String> = node
+            .children()
+            .filter_map(|n| match n.kind() {
+                SyntaxKind::LiteralNode => literal_text(&n),
+                SyntaxKind::AttrKeyValue => {
+                    let key = n.children().find(|k| k.kind() == SyntaxKind::IdentNode).and_then(|k| first_ident_child_text(&k))?;
+                    let value = n.children().find(|v| v.kind() == SyntaxKind::LiteralNode).and_then(|v| literal_text(&v))?;
+                    Some(format!("{key} = {value}"))
+                }
+                _ => None,
+            })
+            .collect();
+        if !args.is_empty() {
+            self.push("(");
+            self.push(&args.join(", "));
+            self.push(")");
         }
     }
 
@@ -163,9 +303,30 @@ impl Formatter {
             self.push(": ");
             self.type_node(&ty);
         }
+        if let Some(default) = find_expr_after_token(node, SyntaxKind::Eq) {
+            self.push(" = ");
+            self.expr(&default, 0);
+        }
     }
 
     fn type_node(&mut self, node: &SyntaxNode) {
+        if is_paren_list(node) {
+            let elems: Vec<_> = node.children().filter(|n| n.kind() == SyntaxKind::Type).collect();
+            self.paren_list(node, &elems, Self::type_node);
+            return;
+        }
+        if let Some(array) = node.children().find(|n| n.kind() == SyntaxKind::ArrayType) {
+            self.push("[");
+            if let Some(elem) = array.children().find(|n| n.kind() == SyntaxKind::Type) {
+                self.type_node(&elem);
+            }
+            if let Some(len) = array.children().find(|n| is_expr_kind(n.kind())) {
+                self.push("; ");
+                self.expr(&len, 0);
+            }
+            self.push("]");
+            return;
+        }
         let text = node.text().to_string();
         self.push(text.trim());
     }
@@ -185,11 +346,89 @@ impl Formatter {
         self.push("}");
     }
 
-    fn struct_decl(&mut self, node: &SyntaxNode) {
-        let has_pub = node.children_with_tokens().any(|e| matches!(e, SyntaxElement::Token(t) if t.kind() == SyntaxKind::KwPub));
-        if has_pub {
-            self.push("pub ");
+    fn effect_alias(&mut self, node: &SyntaxNode) {
+        let names: Vec<String> = node
+            .children()
+            .filter(|n| n.kind() == SyntaxKind::IdentNode)
+            .filter_map(|n| first_ident_child_text(&n))
+            .collect();
+        if let Some((name, members)) = names.split_first() {
+            self.push("effect set ");
+            self.push(name);
+            self.push(" = {");
+            self.push(&members.join(", "));
+            self.push("};");
+        }
+    }
+
+    /// Methods are separated by a blank line, as top-level items are.
+    fn impl_block(&mut self, node: &SyntaxNode) {
+        self.attributes(node);
+        self.push("impl ");
+        if let Some(name) = node
+            .children()
+            .find(|n| n.kind() == SyntaxKind::IdentNode)
+            .and_then(|n| first_ident_child_text(&n))
+        {
+            self.push(&name);
+        }
+        self.push(" {");
+        self.indent += 1;
+        for (idx, method) in node.children().filter(|n| n.kind() == SyntaxKind::FnDecl).enumerate() {
+            if idx > 0 {
+                self.push("\n");
+            }
+            self.newline();
+            self.fn_decl(&method);
+        }
+        self.indent -= 1;
+        self.newline();
+        self.push("}");
+    }
+
+    fn type_alias(&mut self, node: &SyntaxNode) {
+        self.attributes(node);
+        self.visibility(node);
+        self.push("type ");
+        if let Some(name) = node
+            .children()
+            .find(|n| n.kind() == SyntaxKind::IdentNode)
+            .and_then(|n| first_ident_child_text(&n))
+        {
+            self.push(&name);
+        }
+        if let Some(ty) = node.children().find(|n| n.kind() == SyntaxKind::Type) {
+            self.push(" = ");
+            self.type_node(&ty);
         }
+        self.push(";");
+    }
+
+    fn const_decl(&mut self, node: &SyntaxNode) {
+        self.attributes(node);
+        self.visibility(node);
+        self.push("const ");
+        if let Some(name) = node
+            .children()
+            .find(|n| n.kind() == SyntaxKind::IdentNode)
+            .and_then(|n| first_ident_child_text(&n))
+        {
+            self.push(&name);
+        }
+        if let Some(ty) = node.children().find(|n| n.kind() == SyntaxKind::Type) {
+            self.push(": ");
+            self.type_node(&ty);
+        }
+        if let Some(value) = find_expr_after_token(node, SyntaxKind::Eq) {
+            self.push(" = ");
+            self.expr(&value, 0);
+        }
+        self.push(";");
+    }
+
+    fn struct_decl(&mut self, node: &SyntaxNode) {
+        self.attributes(node);
+        self.visibility(node);
         self.push("struct ");
         if let Some(name) = node
             .children()
@@ -202,6 +441,7 @@ impl Formatter {
         self.indent += 1;
         for field in node.children().filter(|n| n.kind() == SyntaxKind::StructField) {
             self.newline();
+            self.doc_comments(&field);
             if let Some(fname) = field
                 .children()
                 .find(|n| n.kind() == SyntaxKind::IdentNode)
@@ -220,11 +460,17 @@ impl Formatter {
         self.push("}");
     }
 
-    fn enum_decl(&mut self, node: &SyntaxNode) {
-        let has_pub = node.children_with_tokens().any(|e| matches!(e, SyntaxElement::Token(t) if t.kind() == SyntaxKind::KwPub));
-        if has_pub {
-            self.push("pub ");
+    /// Prints the `///` lines that open a field or variant, each on its own line.
+    fn doc_comments(&mut self, node: &SyntaxNode) {
+        for doc in node.children_with_tokens().filter_map(|el| el.into_token()).filter(|t| t.kind() == SyntaxKind::DocComment) {
+            self.push(doc.text().trim_end());
+            self.newline();
         }
+    }
+
+    fn enum_decl(&mut self, node: &SyntaxNode) {
+        self.attributes(node);
+        self.visibility(node);
         self.push("enum ");
         if let Some(name) = node
             .children()
@@ -237,6 +483,7 @@ impl Formatter {
         self.indent += 1;
         for variant in node.children().filter(|n| n.kind() == SyntaxKind::EnumVariant) {
             self.newline();
+            self.doc_comments(&variant);
             if let Some(vname) = variant
                 .children()
                 .find(|n| n.kind() == SyntaxKind::IdentNode)
@@ -257,6 +504,10 @@ impl Formatter {
                 }
                 self.push(")");
             }
+            if let Some(lit) = variant.children().find(|n| n.kind() == SyntaxKind::LiteralNode).as_ref().and_then(literal_text) {
+                self.push(" = ");
+                self.push(&lit);
+            }
             self.push(";");
         }
         self.indent -= 1;
@@ -265,6 +516,12 @@ impl Formatter {
     }
 
     fn block(&mut self, node: &SyntaxNode) {
+        if self.options.compact_blocks {
+            if let Some(line) = self.compact_block(node) {
+                self.push(&line);
+                return;
+            }
+        }
         self.push("{");
         self.indent += 1;
         let mut any_stmt = false;
@@ -272,22 +529,7 @@ impl Formatter {
             let items: Vec<_> = stmts.children().collect();
             let len = items.len();
             for (idx, stmt) in items.into_iter().enumerate() {
-                if matches!(
-                    stmt.kind(),
-                    SyntaxKind::LetStmt
-                        | SyntaxKind::ReturnStmt
-                        | SyntaxKind::ExprStmt
-                        | SyntaxKind::IfExpr
-                        | SyntaxKind::MatchExpr
-                        | SyntaxKind::Block
-                        | SyntaxKind::BinExpr
-                        | SyntaxKind::CallExpr
-                        | SyntaxKind::MemberExpr
-                        | SyntaxKind::IdentNode
-                        | SyntaxKind::LiteralNode
-                        | SyntaxKind::ParenExpr
-                        | SyntaxKind::Error
-                ) {
+                if is_block_item(stmt.kind()) {
                     self.newline();
                     if idx == len - 1 && is_expr_kind(stmt.kind()) && stmt.kind() != SyntaxKind::ExprStmt {
                         self.expr(&stmt, 0);
@@ -305,12 +547,33 @@ impl Formatter {
         self.push("}");
     }
 
+    /// `{ item }` for a block whose only item prints on one line that still fits within
+    /// `max_width` from the current column; `None` otherwise.
+    fn compact_block(&self, node: &SyntaxNode) -> Option<String> {
+        let mut items = node.children().find(|n| n.kind() == SyntaxKind::StmtList)?.children();
+        let item = items.next().filter(|n| is_block_item(n.kind()))?;
+        if items.next().is_some() {
+            return None;
+        }
+        let mut inner = Formatter::new(self.options.clone());
+        if is_expr_kind(item.kind()) {
+            inner.expr(&item, 0);
+        } else {
+            inner.stmt(&item);
+        }
+        let line = format!("{{ {} }}", inner.finish());
+        let column = self.out.rsplit('\n').next().unwrap_or("").chars().count();
+        (!line.contains('\n') && column + line.chars().count() <= self.options.max_width).then_some(line)
+    }
+
     fn stmt(&mut self, node: &SyntaxNode) {
         match node.kind() {
             SyntaxKind::LetStmt => self.let_stmt(node),
             SyntaxKind::ReturnStmt => self.return_stmt(node),
+            SyntaxKind::BreakStmt => self.break_stmt(node),
+            SyntaxKind::UseDecl => self.use_decl(node),
             SyntaxKind::ExprStmt => self.expr_stmt(node),
-            SyntaxKind::IfExpr => self.if_expr(node),
+            SyntaxKind::IfExpr | SyntaxKind::IfLetExpr => self.if_expr(node),
             SyntaxKind::MatchExpr => self.match_expr(node),
             SyntaxKind::Block => self.block(node),
             _ => self.expr(node, 0),
@@ -333,6 +596,10 @@ impl Formatter {
         if let Some(expr) = find_expr_after_token(node, SyntaxKind::Eq) {
             self.expr(&expr, 0);
         }
+        if let Some(else_block) = find_expr_after_token(node, SyntaxKind::KwElse) {
+            self.push(" else ");
+            self.block(&else_block);
+        }
         self.push(";");
     }
 
@@ -345,6 +612,29 @@ impl Formatter {
         self.push(";");
     }
 
+    fn break_stmt(&mut self, node: &SyntaxNode) {
+        self.push("break");
+        if let Some(label) = label_text(node) {
+            self.push(" ");
+            self.push(&label);
+        }
+        if let Some(expr) = node.children().find(|n| is_expr_kind(n.kind())) {
+            self.push(" ");
+            self.expr(&expr, 0);
+        }
+        self.push(";");
+    }
+
+    fn labeled_block(&mut self, node: &SyntaxNode) {
+        if let Some(label) = label_text(node) {
+            self.push(&label);
+            self.push(": ");
+        }
+        if let Some(block) = node.children().find(|n| n.kind() == SyntaxKind::Block) {
+            self.block(&block);
+        }
+    }
+
     fn expr_stmt(&mut self, node: &SyntaxNode) {
         if let Some(expr) = node.children().find(|n| is_expr_kind(n.kind())) {
             self.expr(&expr, 0);
@@ -355,6 +645,13 @@ impl Formatter {
     fn if_expr(&mut self, node: &SyntaxNode) {
         self.push("if ");
         let mut kids = node.children();
+        if node.kind() == SyntaxKind::IfLetExpr {
+            if let Some(pattern) = kids.next() {
+                self.push("let ");
+                self.pattern(&pattern);
+                self.push(" = ");
+            }
+        }
         if let Some(cond) = kids.next() {
             self.expr(&cond, 0);
         }
@@ -364,7 +661,7 @@ impl Formatter {
         }
         if let Some(else_node) = kids.next() {
             self.push(" else ");
-            if else_node.kind() == SyntaxKind::IfExpr {
+            if matches!(else_node.kind(), SyntaxKind::IfExpr | SyntaxKind::IfLetExpr) {
                 self.if_expr(&else_node);
             } else {
                 self.block(&else_node);
@@ -393,14 +690,29 @@ impl Formatter {
         if let Some(pat) = node.children().find(|n| n.kind() == SyntaxKind::Pattern) {
             self.pattern(&pat);
         }
+        if let Some(guard) = node.children().find(|n| n.kind() == SyntaxKind::MatchGuard) {
+            self.push(" if ");
+            if let Some(cond) = guard.children().find(|n| is_expr_kind(n.kind())) {
+                self.expr(&cond, 0);
+            }
+        }
         self.push(" => ");
-        if let Some(expr) = node.children().find(|n| n.kind() != SyntaxKind::Pattern) {
+        if let Some(expr) = node.children().find(|n| is_expr_kind(n.kind())) {
             self.expr(&expr, 0);
         }
         self.push(",");
     }
 
     fn pattern(&mut self, node: &SyntaxNode) {
+        if is_paren_list(node) {
+            // `Some(x)` keeps its variant name; `(a, b)` is a tuple
+            if let Some(name) = node.children().find(|n| n.kind() == SyntaxKind::IdentNode).and_then(|n| first_ident_child_text(&n)) {
+                self.push(&name);
+            }
+            let elems: Vec<_> = node.children().filter(|n| n.kind() == SyntaxKind::Pattern).collect();
+            self.paren_list(node, &elems, Self::pattern);
+            return;
+        }
         if let Some(token) = node.children_with_tokens().find_map(|e| match e {
             SyntaxElement::Token(t) if t.kind() == SyntaxKind::Underscore => Some(t.text().to_string()),
             _ => None,
@@ -417,7 +729,6 @@ impl Formatter {
         if let Some(ident) = node.children().find(|n| n.kind() == SyntaxKind::IdentNode) {
             if let Some(name) = first_ident_child_text(&ident) {
                 self.push(&name);
-                return;
             }
         }
     }
@@ -425,12 +736,40 @@ impl Formatter {
     fn expr(&mut self, node: &SyntaxNode, min_bp: u8) {
         match node.kind() {
             SyntaxKind::BinExpr => self.bin_expr(node, min_bp),
+            SyntaxKind::AssignExpr => self.assign_expr(node),
             SyntaxKind::CallExpr => self.call_expr(node),
             SyntaxKind::MemberExpr => self.member_expr(node),
-            SyntaxKind::IfExpr => self.if_expr(node),
+            SyntaxKind::PathExpr => {
+                let segments: Vec<_> = node.children().filter_map(|n| first_ident_child_text(&n)).collect();
+                self.push(&segments.join("::"));
+            }
+            SyntaxKind::ClosureExpr => self.closure_expr(node),
+            SyntaxKind::StructLit => self.struct_lit(node),
+            SyntaxKind::IfExpr | SyntaxKind::IfLetExpr => self.if_expr(node),
             SyntaxKind::MatchExpr => self.match_expr(node),
             SyntaxKind::Block => self.block(node),
-            SyntaxKind::ParenExpr => self.paren_expr(node),
+            SyntaxKind::LabeledBlock => self.labeled_block(node),
+            SyntaxKind::ParenExpr => self.paren_expr(node, min_bp),
+            SyntaxKind::TupleExpr => {
+                let elems: Vec<_> = node.children().filter(|n| is_expr_kind(n.kind())).collect();
+                self.paren_list(node, &elems, |f, e| f.expr(e, 0));
+            }
+            SyntaxKind::ArrayExpr => {
+                self.push("[");
+                for (idx, elem) in node.children().filter(|n| is_expr_kind(n.kind())).enumerate() {
+                    if idx > 0 {
+                        self.push(", ");
+                    }
+                    self.expr(&elem, 0);
+                }
+                self.push("]");
+            }
+            SyntaxKind::AwaitExpr => {
+                self.push("await ");
+                if let Some(inner) = node.children().find(|n| is_expr_kind(n.kind())) {
+                    self.expr(&inner, POSTFIX_BP);
+                }
+            }
             SyntaxKind::IdentNode => {
                 if let Some(name) = first_ident_child_text(node) {
                     self.push(&name);
@@ -465,14 +804,42 @@ impl Formatter {
         }
     }
 
+    fn assign_expr(&mut self, node: &SyntaxNode) {
+        let mut kids = node.children();
+        let (target, value) = match (kids.next(), kids.next()) {
+            (Some(target), Some(value)) => (target, value),
+            _ => return,
+        };
+        let op = node.children_with_tokens().find_map(|e| match e {
+            SyntaxElement::Token(t) if t.kind().is_assign_op() => Some(t.text().to_string()),
+            _ => None,
+        });
+        self.expr(&target, 0);
+        self.push(" ");
+        self.push(op.as_deref().unwrap_or("="));
+        self.push(" ");
+        self.expr(&value, 0);
+    }
+
     fn call_expr(&mut self, node: &SyntaxNode) {
         let mut kids = node.children();
         if let Some(callee) = kids.next() {
-            self.expr(&callee, 0);
+            self.expr(&callee, POSTFIX_BP);
+        }
+        let mut args = kids.peekable();
+        if let Some(type_args) = args.next_if(|n| n.kind() == SyntaxKind::TypeArgList) {
+            self.push("::<");
+            for (idx, ty) in type_args.children().filter(|n| n.kind() == SyntaxKind::Type).enumerate() {
+                if idx > 0 {
+                    self.push(", ");
+                }
+                self.type_node(&ty);
+            }
+            self.push(">");
         }
         self.push("(");
         let mut first = true;
-        for arg in kids {
+        for arg in args {
             if !first {
                 self.push(", ");
             }
@@ -485,7 +852,7 @@ impl Formatter {
     fn member_expr(&mut self, node: &SyntaxNode) {
         let mut kids = node.children();
         if let Some(base) = kids.next() {
-            self.expr(&base, 0);
+            self.expr(&base, POSTFIX_BP);
         }
         if let Some(field) = kids.next() {
             self.push(".");
@@ -495,13 +862,91 @@ impl Formatter {
         }
     }
 
-    fn paren_expr(&mut self, node: &SyntaxNode) {
+    fn closure_expr(&mut self, node: &SyntaxNode) {
+        self.push("|");
+        if let Some(params) = node.children().find(|n| n.kind() == SyntaxKind::ParamList) {
+            for (idx, param) in params.children().filter(|n| n.kind() == SyntaxKind::Param).enumerate() {
+                if idx > 0 {
+                    self.push(", ");
+                }
+                self.param(&param);
+            }
+        }
+        self.push("| ");
+        if let Some(body) = node.children().find(|n| is_expr_kind(n.kind())) {
+            self.expr(&body, 0);
+        }
+    }
+
+    /// Prints `Name { a: 1, ..base }` on one line, or `Name {}` when empty.
+    fn struct_lit(&mut self, node: &SyntaxNode) {
+        let mut kids = node.children();
+        if let Some(name) = kids.next().as_ref().and_then(first_ident_child_text) {
+            self.push(&name);
+        }
+        let parts: Vec<_> = kids.collect();
+        if parts.is_empty() {
+            self.push(" {}");
+            return;
+        }
+        self.push(" { ");
+        for (idx, part) in parts.iter().enumerate() {
+            if idx > 0 {
+                self.push(", ");
+            }
+            let mut kids = part.children();
+            if part.kind() == SyntaxKind::StructLitSpread {
+                self.push("..");
+            } else if let Some(field) = kids.next().as_ref().and_then(first_ident_child_text) {
+                self.push(&field);
+                self.push(": ");
+            }
+            if let Some(value) = kids.next() {
+                self.expr(&value, 0);
+            }
+        }
+        self.push(" }");
+    }
+
+    /// Drops parentheses that precedence does not need, so `((a + b))` prints as `a + b`;
+    /// a binary operand that still needs them gets them back from `bin_expr`.
+    fn paren_expr(&mut self, node: &SyntaxNode, min_bp: u8) {
+        let inner = node.children().next();
+        if let Some(inner) = inner.as_ref().filter(|inner| is_redundant_paren(node, inner)) {
+            self.expr(inner, min_bp);
+            return;
+        }
         self.push("(");
-        if let Some(inner) = node.children().next() {
+        if let Some(inner) = inner {
             self.expr(&inner, 0);
         }
         self.push(")");
     }
+
+    /// Prints `(a, b)`; a one-element list keeps its trailing comma so `(a,)` stays a tuple.
+    fn paren_list(&mut self, node: &SyntaxNode, elems: &[SyntaxNode], mut item: impl FnMut(&mut Self, &SyntaxNode)) {
+        self.push("(");
+        for (idx, elem) in elems.iter().enumerate() {
+            if idx > 0 {
+                self.push(", ");
+            }
+            item(self, elem);
+        }
+        if elems.len() == 1 && has_comma(node) {
+            self.push(",");
+        }
+        self.push(")");
+    }
+}
+
+fn is_paren_list(node: &SyntaxNode) -> bool {
+    node.children_with_tokens()
+        .any(|e| matches!(e, SyntaxElement::Token(t) if t.kind() == SyntaxKind::LParen))
+}
+
+fn has_comma(node: &SyntaxNode) -> bool {
+    node.children_with_tokens()
+        .any(|e| matches!(e, SyntaxElement::Token(t) if t.kind() == SyntaxKind::Comma))
 }
 
 fn first_ident_child_text(node: &SyntaxNode) -> Option<String> {
@@ -511,19 +956,24 @@ fn first_ident_child_text(node: &SyntaxNode) -> Option<String> {
     })
 }
 
-fn literal_text(node: &SyntaxNode) -> Option<String> {
+/// The `'label` token directly under a `LabeledBlock` or `BreakStmt`.
+fn label_text(node: &SyntaxNode) -> Option<String> {
     node.children_with_tokens().find_map(|e| match e {
-        SyntaxElement::Token(t) if t.kind().is_literal() => Some(t.text().to_string()),
+        SyntaxElement::Token(t) if t.kind() == SyntaxKind::Label => Some(t.text().to_string()),
         _ => None,
     })
 }
 
+fn literal_text(node: &SyntaxNode) -> Option<String> {
+    literal_value(node).map(|(_, text)| normalize_literal_newlines(&text))
+}
+
 fn find_kw_as_alias(node: &SyntaxNode) -> Option<String> {
     let mut seen_as = false;
     for el in node.children_with_tokens() {
         match el {
             SyntaxElement::Token(t) if t.kind() == SyntaxKind::KwAs => seen_as = true,
-            SyntaxElement::Token(t) if seen_as && t.kind() == SyntaxKind::Ident => return Some(t.text().to_string()),
+            SyntaxElement::Node(n) if seen_as && n.kind() == SyntaxKind::IdentNode => return first_ident_child_text(&n),
             _ => {}
         }
     }
@@ -536,8 +986,9 @@ fn format_use_path(node: &SyntaxNode) -> Option<String> {
         if child.kind() == SyntaxKind::UsePath {
             for el in child.children_with_tokens() {
                 match el {
-                    SyntaxElement::Token(t) if t.kind() == SyntaxKind::Ident => parts.push(t.text().to_string()),
+                    SyntaxElement::Node(n) if n.kind() == SyntaxKind::IdentNode => parts.extend(first_ident_child_text(&n)),
                     SyntaxElement::Token(t) if t.kind() == SyntaxKind::ColonColon => parts.push("::".to_string()),
+                    SyntaxElement::Node(n) if n.kind() == SyntaxKind::UseGroup => parts.push(format_use_group(&n)),
                     _ => {}
                 }
             }
@@ -551,6 +1002,23 @@ fn format_use_path(node: &SyntaxNode) -> Option<String> {
     }
 }
 
+/// `{a as b, c}`, one member per `UseGroupItem`.
+fn format_use_group(node: &SyntaxNode) -> String {
+    let items: Vec<String> = node
+        .children()
+        .filter(|n| n.kind() == SyntaxKind::UseGroupItem)
+        .map(|item| {
+            let mut names = item.children().filter(|n| n.kind() == SyntaxKind::IdentNode).filter_map(|n| first_ident_child_text(&n));
+            let name = names.next().unwrap_or_default();
+            match names.next() {
+                Some(alias) => format!("{name} as {alias}"),
+                None => name,
+            }
+        })
+        .collect();
+    format!("{{{}}}", items.join(", "))
+}
+
 fn find_return_type(node: &SyntaxNode) -> Option<SyntaxNode> {
     let mut seen_arrow = false;
     for el in node.children_with_tokens() {
@@ -583,25 +1051,65 @@ fn find_expr_after_token(node: &SyntaxNode, token_kind: SyntaxKind) -> Option<Sy
     None
 }
 
+/// Node kinds printed as one line of a block: statements, expressions, and error nodes.
+fn is_block_item(kind: SyntaxKind) -> bool {
+    matches!(
+        kind,
+        SyntaxKind::LetStmt
+            | SyntaxKind::ReturnStmt
+            | SyntaxKind::BreakStmt
+            | SyntaxKind::UseDecl
+            | SyntaxKind::ExprStmt
+            | SyntaxKind::IfExpr
+            | SyntaxKind::IfLetExpr
+            | SyntaxKind::MatchExpr
+            | SyntaxKind::Block
+            | SyntaxKind::LabeledBlock
+            | SyntaxKind::BinExpr
+            | SyntaxKind::AssignExpr
+            | SyntaxKind::CallExpr
+            | SyntaxKind::MemberExpr
+            | SyntaxKind::PathExpr
+            | SyntaxKind::ClosureExpr
+            | SyntaxKind::StructLit
+            | SyntaxKind::IdentNode
+            | SyntaxKind::LiteralNode
+            | SyntaxKind::ParenExpr
+            | SyntaxKind::TupleExpr
+            | SyntaxKind::ArrayExpr
+            | SyntaxKind::AwaitExpr
+            | SyntaxKind::Error
+    )
+}
+
 fn is_expr_kind(kind: SyntaxKind) -> bool {
     matches!(
         kind,
         SyntaxKind::BinExpr
+            | SyntaxKind::AssignExpr
             | SyntaxKind::CallExpr
             | SyntaxKind::MemberExpr
+            | SyntaxKind::PathExpr
+            | SyntaxKind::ClosureExpr
+            | SyntaxKind::StructLit
             | SyntaxKind::IfExpr
+            | SyntaxKind::IfLetExpr
             | SyntaxKind::MatchExpr
             | SyntaxKind::IdentNode
             | SyntaxKind::LiteralNode
             | SyntaxKind::ParenExpr
+            | SyntaxKind::TupleExpr
+            | SyntaxKind::ArrayExpr
+            | SyntaxKind::AwaitExpr
             | SyntaxKind::Block
+            | SyntaxKind::LabeledBlock
     )
 }
 
 fn bin_parts(node: &SyntaxNode) -> Option<(SyntaxKind, String, SyntaxNode, SyntaxNode)> {
     let mut children = node.children();
     let left = children.next()?;
-    let right = children.nth(0)?;
+    let right = children.next()?;
     let mut op_kind = None;
     let mut op_text = None;
     for el in node.children_with_tokens() {
@@ -620,6 +1128,40 @@ fn bin_parts(node: &SyntaxNode) -> Option<(SyntaxKind, String, SyntaxNode, Synta
     Some((op_kind?, op_text?, left, right))
 }
 
+/// The binding power a callee or member base needs: tighter than any infix operator.
+const POSTFIX_BP: u8 = 13;
+
+/// Whether `(inner)` can print as `inner` without changing how the result parses.
+fn is_redundant_paren(paren: &SyntaxNode, inner: &SyntaxNode) -> bool {
+    // Parentheses lift the struct-literal restriction in conditions.
+    if inner.descendants().any(|n| n.kind() == SyntaxKind::StructLit) {
+        return false;
+    }
+    match inner.kind() {
+        SyntaxKind::IdentNode
+        | SyntaxKind::LiteralNode
+        | SyntaxKind::CallExpr
+        | SyntaxKind::MemberExpr
+        | SyntaxKind::PathExpr
+        | SyntaxKind::ParenExpr
+        | SyntaxKind::TupleExpr
+        | SyntaxKind::ArrayExpr => true,
+        // `(a < b) < c` must not become the chained comparison `a < b < c`.
+        SyntaxKind::BinExpr => {
+            let operand_of = paren.ancestors().skip(1).find(|n| n.kind() != SyntaxKind::ParenExpr);
+            !(is_comparison(inner) && operand_of.is_some_and(|p| is_comparison(&p)))
+        }
+        _ => false,
+    }
+}
+
+fn is_comparison(node: &SyntaxNode) -> bool {
+    node.kind() == SyntaxKind::BinExpr
+        && bin_parts(node).is_some_and(|(op, ..)| {
+            matches!(op, SyntaxKind::EqEq | SyntaxKind::Neq | SyntaxKind::Lt | SyntaxKind::Lte | SyntaxKind::Gt | SyntaxKind::Gte)
+        })
+}
+
 fn infix_binding_power(kind: SyntaxKind) -> (u8, u8) {
     match kind {
         SyntaxKind::OrOr => (1, 2),
@@ -1,7 +1,11 @@
+use jalm_ast::{cfg_feature, AstNode, Root};
 use jalm_syntax::parser_events::Event;
-use jalm_syntax::{build_green, lex, SyntaxKind, SyntaxNode, Token};
+use jalm_syntax::{build_green, is_unterminated_block_comment, lex, SyntaxKind, SyntaxNode, Token};
 use serde::{Deserialize, Serialize};
 
+/// Version of this crate, reported by `jalmt version --verbose`.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Span {
     pub start: usize,
@@ -10,8 +14,13 @@ pub struct Span {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ParseError {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
     pub message: String,
     pub span: Span,
+    /// A second location the error refers to, e.g. the opening of an unclosed delimiter.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub related: Option<Span>,
 }
 
 #[derive(Debug, Clone)]
@@ -24,27 +33,80 @@ impl Parse {
     pub fn syntax(&self) -> SyntaxNode {
         SyntaxNode::new_root(self.green.clone())
     }
+
+    pub fn ast(&self) -> Root {
+        Root::cast(self.syntax()).expect("parser always produces a Root node")
+    }
+
+    /// True if the source parsed without errors.
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    pub fn error_count(&self) -> usize {
+        self.errors.len()
+    }
+
+    /// The parse errors, in the order they were reported.
+    pub fn errors(&self) -> &[ParseError] {
+        &self.errors
+    }
 }
 
 pub fn parse(source: &str) -> Parse {
-    let mut tokens = lex(source);
-    let end = source.len();
-    tokens.push(Token {
-        kind: SyntaxKind::Eof,
-        text: String::new(),
-        span: end..end,
-    });
-    let mut p = Parser::new(tokens);
+    let mut p = Parser::for_source(source);
     p.parse_root();
     let green = build_green(p.events);
     Parse { green, errors: p.errors }
 }
 
+/// Parses `source` with only the items enabled by `features`. An item marked
+/// `@cfg(feature = "name")` for a feature not listed becomes a single [`SyntaxKind::CfgDisabled`]
+/// trivia token, so later passes skip it and spans still point into `source`.
+pub fn parse_with_features(source: &str, features: &[String]) -> Parse {
+    let parsed = parse(source);
+    Parse { green: without_disabled(&parsed.syntax(), features), errors: parsed.errors }
+}
+
+fn without_disabled(node: &SyntaxNode, features: &[String]) -> rowan::GreenNode {
+    let children: Vec<_> = node.children_with_tokens().map(|child| match child {
+        rowan::NodeOrToken::Node(item) if cfg_feature(&item).is_some_and(|feature| !features.contains(&feature)) => {
+            rowan::NodeOrToken::Token(rowan::GreenToken::new(SyntaxKind::CfgDisabled.into(), &item.text().to_string()))
+        }
+        rowan::NodeOrToken::Node(child) => rowan::NodeOrToken::Node(without_disabled(&child, features)),
+        rowan::NodeOrToken::Token(token) => rowan::NodeOrToken::Token(token.green().to_owned()),
+    }).collect();
+    rowan::GreenNode::new(node.kind().into(), children)
+}
+
+/// Parses `source` as one expression, e.g. a line typed into `jalmt repl`. The `Root` holds
+/// the expression; anything after it is an error.
+pub fn parse_expr(source: &str) -> Parse {
+    let mut p = Parser::for_source(source);
+    let m = p.start();
+    p.parse_expr_bp(0);
+    p.eat_trivia();
+    if !p.at(SyntaxKind::Eof) {
+        let rest = p.start();
+        p.error_here("expected end of expression");
+        while !p.at(SyntaxKind::Eof) {
+            p.bump_any();
+        }
+        p.complete(rest, SyntaxKind::Error);
+    }
+    p.complete(m, SyntaxKind::Root);
+    let green = build_green(p.events);
+    Parse { green, errors: p.errors }
+}
+
 struct Parser {
     tokens: Vec<Token>,
     pos: usize,
     events: Vec<Event>,
     errors: Vec<ParseError>,
+    open_delims: Vec<(SyntaxKind, Span)>,
+    /// Set while parsing an `if`/`match` head, where `{` opens the body, not a struct literal.
+    no_struct_lit: bool,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -64,6 +126,35 @@ impl Parser {
             pos: 0,
             events: Vec::new(),
             errors: Vec::new(),
+            open_delims: Vec::new(),
+            no_struct_lit: false,
+        }
+    }
+
+    /// A parser over the tokens of `source`, with unterminated block comments already reported.
+    fn for_source(source: &str) -> Self {
+        let mut tokens = lex(source);
+        let end = source.len();
+        tokens.push(Token {
+            kind: SyntaxKind::Eof,
+            text: String::new(),
+            span: end..end,
+        });
+        let mut p = Parser::new(tokens);
+        p.check_block_comments();
+        p
+    }
+
+    fn check_block_comments(&mut self) {
+        for token in &self.tokens {
+            if token.kind == SyntaxKind::Comment && is_unterminated_block_comment(&token.text) {
+                self.errors.push(ParseError {
+                    code: Some("E0034".to_string()),
+                    message: "unterminated block comment".to_string(),
+                    span: Span { start: token.span.start, end: token.span.start + 2 },
+                    related: None,
+                });
+            }
         }
     }
 
@@ -75,12 +166,20 @@ impl Parser {
                 self.parse_module_decl();
             } else if self.at(SyntaxKind::KwUse) {
                 self.parse_use_decl();
-            } else if self.at(SyntaxKind::KwPub) {
-                match self.nth(1) {
+            } else if self.at_module_attribute() {
+                self.parse_module_attribute();
+            } else if self.at(SyntaxKind::KwPub) || self.at(SyntaxKind::At) {
+                match self.item_keyword() {
                     SyntaxKind::KwFn | SyntaxKind::KwAsync => self.parse_fn_decl(),
                     SyntaxKind::KwStruct | SyntaxKind::KwEnum => self.parse_struct_or_enum(),
+                    SyntaxKind::KwConst => self.parse_const_decl(),
+                    SyntaxKind::KwType => self.parse_type_alias(),
+                    SyntaxKind::KwImpl if self.at(SyntaxKind::At) => self.parse_impl_block(),
+                    SyntaxKind::KwUse if self.at(SyntaxKind::At) => self.parse_use_decl(),
+                    // Attributes before anything else are reported where a function was expected.
+                    _ if self.at(SyntaxKind::At) => self.parse_fn_decl(),
                     _ => {
-                        self.error_here("expected 'fn', 'struct', or 'enum' after 'pub'");
+                        self.error_here("expected 'fn', 'struct', 'enum', 'const', or 'type' after 'pub'");
                         self.bump_any();
                     }
                 }
@@ -88,6 +187,14 @@ impl Parser {
                 self.parse_fn_decl();
             } else if self.at(SyntaxKind::KwStruct) || self.at(SyntaxKind::KwEnum) {
                 self.parse_struct_or_enum();
+            } else if self.at(SyntaxKind::KwConst) {
+                self.parse_const_decl();
+            } else if self.at(SyntaxKind::KwType) {
+                self.parse_type_alias();
+            } else if self.at(SyntaxKind::KwImpl) {
+                self.parse_impl_block();
+            } else if self.at(SyntaxKind::Ident) && self.current_text() == "effect" {
+                self.parse_effect_alias();
             } else {
                 let m = self.start();
                 self.error_here("expected item");
@@ -111,6 +218,8 @@ impl Parser {
 
     fn parse_use_decl(&mut self) {
         let m = self.start();
+        self.parse_attributes();
+        self.parse_visibility();
         self.expect(SyntaxKind::KwUse);
         self.parse_use_path();
         self.eat_trivia();
@@ -128,39 +237,125 @@ impl Parser {
         self.parse_ident();
         while self.at(SyntaxKind::ColonColon) {
             self.bump_any();
+            if self.nth_non_trivia(0) == SyntaxKind::LBrace {
+                self.parse_use_group();
+                break;
+            }
             self.parse_ident();
         }
         self.complete(m, SyntaxKind::UsePath);
     }
 
-    fn parse_fn_decl(&mut self) {
+    /// `{name [as alias], ...}`; a trailing comma is allowed.
+    fn parse_use_group(&mut self) {
+        self.eat_trivia();
         let m = self.start();
-        if self.at(SyntaxKind::KwPub) {
+        self.expect(SyntaxKind::LBrace);
+        self.parse_comma_separated(SyntaxKind::RBrace, |p| {
+            let item = p.start();
+            p.parse_ident();
+            p.eat_trivia();
+            if p.at(SyntaxKind::KwAs) {
+                p.bump_any();
+                p.parse_ident();
+            }
+            p.complete(item, SyntaxKind::UseGroupItem);
+        });
+        self.expect(SyntaxKind::RBrace);
+        self.complete(m, SyntaxKind::UseGroup);
+    }
+
+    fn parse_visibility(&mut self) {
+        if !self.at(SyntaxKind::KwPub) {
+            return;
+        }
+        let m = self.start();
+        self.bump_any();
+        if self.nth_non_trivia(0) == SyntaxKind::LParen {
+            self.expect(SyntaxKind::LParen);
+            self.eat_trivia();
+            if self.at(SyntaxKind::Ident) && !matches!(self.current_text(), "crate" | "super") {
+                self.error_here("expected 'crate' or 'super' in visibility restriction");
+            }
+            self.parse_ident();
+            self.expect(SyntaxKind::RParen);
+        }
+        self.complete(m, SyntaxKind::Visibility);
+        self.eat_trivia();
+    }
+
+    /// `@name;` applies to the whole module rather than the next function.
+    fn at_module_attribute(&self) -> bool {
+        self.at(SyntaxKind::At) && self.nth_non_trivia(1) == SyntaxKind::Ident && self.nth_non_trivia(2) == SyntaxKind::Semi
+    }
+
+    fn parse_module_attribute(&mut self) {
+        let m = self.start();
+        let attr = self.start();
+        self.bump_any();
+        self.parse_ident();
+        self.complete(attr, SyntaxKind::Attribute);
+        self.expect(SyntaxKind::Semi);
+        self.complete(m, SyntaxKind::ModuleAttribute);
+    }
+
+    /// Parses `@name("arg", ...)` attributes preceding an item.
+    fn parse_attributes(&mut self) {
+        while self.at(SyntaxKind::At) {
+            let m = self.start();
             self.bump_any();
+            self.parse_ident();
+            self.eat_trivia();
+            if self.at(SyntaxKind::LParen) {
+                self.bump_any();
+                self.parse_comma_separated(SyntaxKind::RParen, |p| {
+                    if p.at(SyntaxKind::String) {
+                        p.parse_literal();
+                    } else if p.at(SyntaxKind::Ident) && p.nth_non_trivia(1) == SyntaxKind::Eq {
+                        let arg = p.start();
+                        p.parse_ident();
+                        p.expect(SyntaxKind::Eq);
+                        p.eat_trivia();
+                        if p.at(SyntaxKind::String) {
+                            p.parse_literal();
+                        } else {
+                            p.error_here("expected string literal in attribute");
+                        }
+                        p.complete(arg, SyntaxKind::AttrKeyValue);
+                    } else {
+                        p.error_here("expected string literal in attribute");
+                        p.bump_any();
+                    }
+                });
+                self.expect(SyntaxKind::RParen);
+            }
+            self.complete(m, SyntaxKind::Attribute);
+            self.eat_trivia();
         }
+    }
+
+    fn parse_fn_decl(&mut self) {
+        let m = self.start();
+        self.parse_attributes();
+        self.parse_visibility();
         if self.at(SyntaxKind::KwAsync) {
             self.bump_any();
         }
         self.expect(SyntaxKind::KwFn);
         self.parse_ident();
+        if self.nth_non_trivia(0) == SyntaxKind::Lt {
+            self.eat_trivia();
+            let generics = self.start();
+            self.bump_any();
+            self.parse_comma_separated(SyntaxKind::Gt, |p| {
+                p.parse_ident();
+            });
+            self.expect(SyntaxKind::Gt);
+            self.complete(generics, SyntaxKind::GenericParamList);
+        }
         self.expect(SyntaxKind::LParen);
         let params = self.start();
-        self.eat_trivia();
-        if !self.at(SyntaxKind::RParen) {
-            loop {
-                self.parse_param();
-                self.eat_trivia();
-                if self.at(SyntaxKind::Comma) {
-                    self.bump_any();
-                    self.eat_trivia();
-                    if self.at(SyntaxKind::RParen) {
-                        break;
-                    }
-                } else {
-                    break;
-                }
-            }
-        }
+        self.parse_comma_separated(SyntaxKind::RParen, Self::parse_param);
         self.expect(SyntaxKind::RParen);
         self.complete(params, SyntaxKind::ParamList);
         self.eat_trivia();
@@ -173,7 +368,12 @@ impl Parser {
             self.parse_effect_set();
         }
         self.eat_trivia();
-        self.parse_block();
+        if self.at(SyntaxKind::Semi) {
+            // bodiless declaration, e.g. an `@extern` import
+            self.bump_any();
+        } else {
+            self.parse_block();
+        }
         self.complete(m, SyntaxKind::FnDecl);
     }
 
@@ -182,16 +382,43 @@ impl Parser {
         if self.at(SyntaxKind::KwMut) {
             self.bump_any();
         }
+        self.eat_trivia();
+        // A method's `self` receiver takes the type of its `impl` block.
+        let receiver = self.at(SyntaxKind::Ident) && self.current_text() == "self";
         self.parse_ident();
+        if receiver && self.nth_non_trivia(0) != SyntaxKind::Colon {
+            self.complete(m, SyntaxKind::Param);
+            return;
+        }
         self.expect(SyntaxKind::Colon);
         self.parse_type();
+        if self.nth_non_trivia(0) == SyntaxKind::Eq {
+            self.eat_trivia();
+            self.bump_any();
+            self.parse_expr_bp(0);
+        }
         self.complete(m, SyntaxKind::Param);
     }
 
     fn parse_type(&mut self) {
         self.eat_trivia();
         let m = self.start();
-        if self.at(SyntaxKind::Ident) {
+        if self.at(SyntaxKind::LParen) {
+            // `()` is the unit type; `(T, U)` nests one `Type` per element
+            self.parse_paren_list(Self::parse_type);
+        } else if self.at(SyntaxKind::LBracket) {
+            // `[T; N]` has a fixed length; `[T]` is any length
+            let array = self.start();
+            self.bump_any();
+            self.parse_type();
+            if self.nth_non_trivia(0) == SyntaxKind::Semi {
+                self.eat_trivia();
+                self.bump_any();
+                self.parse_expr_bp(0);
+            }
+            self.expect(SyntaxKind::RBracket);
+            self.complete(array, SyntaxKind::ArrayType);
+        } else if self.at(SyntaxKind::Ident) {
             self.parse_ident();
             while self.at(SyntaxKind::ColonColon) {
                 self.bump_any();
@@ -204,6 +431,98 @@ impl Parser {
         self.complete(m, SyntaxKind::Type);
     }
 
+    /// Parses `( item, item, ... )` with an optional trailing comma into the current node.
+    fn parse_paren_list(&mut self, item: fn(&mut Self)) {
+        self.bump_any();
+        self.parse_comma_separated(SyntaxKind::RParen, item);
+        self.expect(SyntaxKind::RParen);
+    }
+
+    /// Parses `item, item, ...` up to (not including) `close`; a trailing comma before
+    /// `close` is allowed. Every comma-separated list in the grammar goes through here.
+    fn parse_comma_separated(&mut self, close: SyntaxKind, mut item: impl FnMut(&mut Self)) {
+        self.eat_trivia();
+        while !self.at(close) && !self.at(SyntaxKind::Eof) {
+            item(self);
+            self.eat_trivia();
+            if !self.at(SyntaxKind::Comma) {
+                break;
+            }
+            self.bump_any();
+            self.eat_trivia();
+        }
+    }
+
+    /// `const NAME: T = expr;`
+    fn parse_const_decl(&mut self) {
+        let m = self.start();
+        self.parse_attributes();
+        self.parse_visibility();
+        self.expect(SyntaxKind::KwConst);
+        self.parse_ident();
+        self.expect(SyntaxKind::Colon);
+        self.parse_type();
+        self.expect(SyntaxKind::Eq);
+        self.parse_expr_bp(0);
+        self.expect(SyntaxKind::Semi);
+        self.complete(m, SyntaxKind::ConstDecl);
+    }
+
+    fn parse_type_alias(&mut self) {
+        let m = self.start();
+        self.parse_attributes();
+        self.parse_visibility();
+        self.expect(SyntaxKind::KwType);
+        self.parse_ident();
+        self.expect(SyntaxKind::Eq);
+        self.parse_type();
+        self.expect(SyntaxKind::Semi);
+        self.complete(m, SyntaxKind::TypeAlias);
+    }
+
+    fn parse_impl_block(&mut self) {
+        let m = self.start();
+        self.parse_attributes();
+        self.expect(SyntaxKind::KwImpl);
+        self.parse_ident();
+        self.expect(SyntaxKind::LBrace);
+        self.eat_trivia();
+        while !matches!(self.current(), SyntaxKind::RBrace | SyntaxKind::Eof) {
+            if matches!(self.current(), SyntaxKind::KwFn | SyntaxKind::KwAsync | SyntaxKind::At | SyntaxKind::KwPub) {
+                self.parse_fn_decl();
+            } else {
+                let e = self.start();
+                self.error_here("expected 'fn' in impl block");
+                self.bump_any();
+                self.complete(e, SyntaxKind::Error);
+            }
+            self.eat_trivia();
+        }
+        self.expect(SyntaxKind::RBrace);
+        self.complete(m, SyntaxKind::ImplBlock);
+    }
+
+    /// `effect set name = {a, b};` -- `effect` and `set` are contextual, so they stay plain `Ident` tokens.
+    fn parse_effect_alias(&mut self) {
+        let m = self.start();
+        self.bump_any();
+        self.eat_trivia();
+        if self.at(SyntaxKind::Ident) && self.current_text() == "set" {
+            self.bump_any();
+        } else {
+            self.error_here("expected 'set' after 'effect'");
+        }
+        self.parse_ident();
+        self.expect(SyntaxKind::Eq);
+        self.expect(SyntaxKind::LBrace);
+        self.parse_comma_separated(SyntaxKind::RBrace, |p| {
+            p.parse_ident();
+        });
+        self.expect(SyntaxKind::RBrace);
+        self.expect(SyntaxKind::Semi);
+        self.complete(m, SyntaxKind::EffectAliasDecl);
+    }
+
     fn parse_effect_set(&mut self) {
         let m = self.start();
         if self.at(SyntaxKind::Bang) {
@@ -212,67 +531,53 @@ impl Parser {
             self.error_here("expected '!'");
         }
         self.expect(SyntaxKind::LBrace);
-        self.eat_trivia();
-        if !self.at(SyntaxKind::RBrace) {
-            loop {
-                self.parse_ident();
-                self.eat_trivia();
-                if self.at(SyntaxKind::Comma) {
-                    self.bump_any();
-                    self.eat_trivia();
-                    if self.at(SyntaxKind::RBrace) {
-                        break;
-                    }
-                } else {
-                    break;
-                }
-            }
-        }
+        self.parse_comma_separated(SyntaxKind::RBrace, |p| {
+            p.parse_ident();
+        });
         self.expect(SyntaxKind::RBrace);
         self.complete(m, SyntaxKind::EffectSet);
     }
 
     fn parse_struct_or_enum(&mut self) {
-        if self.at(SyntaxKind::KwPub) {
-            if self.nth(1) == SyntaxKind::KwStruct {
-                self.parse_struct_decl();
-            } else if self.nth(1) == SyntaxKind::KwEnum {
-                self.parse_enum_decl();
-            } else {
+        match self.item_keyword() {
+            SyntaxKind::KwStruct => self.parse_struct_decl(),
+            SyntaxKind::KwEnum => self.parse_enum_decl(),
+            _ if self.at(SyntaxKind::KwPub) => {
                 self.error_here("expected 'struct' or 'enum' after 'pub'");
                 self.bump_any();
             }
-            return;
-        }
-        if self.at(SyntaxKind::KwStruct) {
-            self.parse_struct_decl();
-            return;
-        }
-        if self.at(SyntaxKind::KwEnum) {
-            self.parse_enum_decl();
-            return;
+            _ => {
+                self.error_here("expected 'struct' or 'enum'");
+                self.bump_any();
+            }
         }
-        self.error_here("expected 'struct' or 'enum'");
-        self.bump_any();
     }
 
     fn parse_struct_decl(&mut self) {
         let m = self.start();
-        if self.at(SyntaxKind::KwPub) {
-            self.bump_any();
-        }
+        self.parse_attributes();
+        self.parse_visibility();
         self.expect(SyntaxKind::KwStruct);
         self.parse_ident();
         self.expect(SyntaxKind::LBrace);
-        self.eat_trivia();
-        while !self.at(SyntaxKind::RBrace) && !self.at(SyntaxKind::Eof) {
+        self.eat_trivia_before_doc();
+        while !matches!(self.nth_non_trivia(0), SyntaxKind::RBrace | SyntaxKind::Eof) {
             let f = self.start();
+            self.eat_trivia();
             self.parse_ident();
             self.expect(SyntaxKind::Colon);
             self.parse_type();
-            self.expect(SyntaxKind::Semi);
+            // fields end in `;` or Rust-style `,`; the last field's separator is optional
+            match self.nth_non_trivia(0) {
+                SyntaxKind::Comma => {
+                    self.eat_trivia();
+                    self.bump_any();
+                }
+                SyntaxKind::RBrace => {}
+                _ => self.expect(SyntaxKind::Semi),
+            }
             self.complete(f, SyntaxKind::StructField);
-            self.eat_trivia();
+            self.eat_trivia_before_doc();
         }
         self.expect(SyntaxKind::RBrace);
         self.complete(m, SyntaxKind::StructDecl);
@@ -280,40 +585,33 @@ impl Parser {
 
     fn parse_enum_decl(&mut self) {
         let m = self.start();
-        if self.at(SyntaxKind::KwPub) {
-            self.bump_any();
-        }
+        self.parse_attributes();
+        self.parse_visibility();
         self.expect(SyntaxKind::KwEnum);
         self.parse_ident();
         self.expect(SyntaxKind::LBrace);
-        self.eat_trivia();
-        while !self.at(SyntaxKind::RBrace) && !self.at(SyntaxKind::Eof) {
+        self.eat_trivia_before_doc();
+        while !matches!(self.nth_non_trivia(0), SyntaxKind::RBrace | SyntaxKind::Eof) {
             let v = self.start();
+            self.eat_trivia();
             self.parse_ident();
             self.eat_trivia();
             if self.at(SyntaxKind::LParen) {
+                self.parse_paren_list(Self::parse_type);
+            } else if self.at(SyntaxKind::Eq) {
+                // `Red = 1;`: an explicit discriminant on a fieldless variant.
                 self.bump_any();
                 self.eat_trivia();
-                if !self.at(SyntaxKind::RParen) {
-                    loop {
-                        self.parse_type();
-                        self.eat_trivia();
-                        if self.at(SyntaxKind::Comma) {
-                            self.bump_any();
-                            self.eat_trivia();
-                            if self.at(SyntaxKind::RParen) {
-                                break;
-                            }
-                        } else {
-                            break;
-                        }
-                    }
+                if self.at(SyntaxKind::Int) || self.at_negative_number() {
+                    self.parse_literal();
+                } else {
+                    self.error_here("expected integer discriminant");
                 }
-                self.expect(SyntaxKind::RParen);
+                self.eat_trivia();
             }
             self.expect(SyntaxKind::Semi);
             self.complete(v, SyntaxKind::EnumVariant);
-            self.eat_trivia();
+            self.eat_trivia_before_doc();
         }
         self.expect(SyntaxKind::RBrace);
         self.complete(m, SyntaxKind::EnumDecl);
@@ -335,8 +633,22 @@ impl Parser {
                 self.eat_trivia();
                 continue;
             }
+            if self.at(SyntaxKind::KwBreak) {
+                self.parse_break_stmt();
+                self.eat_trivia();
+                continue;
+            }
+            if self.at(SyntaxKind::KwUse) || (self.at(SyntaxKind::KwPub) && self.item_keyword() == SyntaxKind::KwUse) {
+                // Nothing outside the function can name a local item.
+                if self.at(SyntaxKind::KwPub) {
+                    self.error_code_here("E0047", "`pub` not allowed on a local item");
+                }
+                self.parse_use_decl();
+                self.eat_trivia();
+                continue;
+            }
 
-            let expr = self.parse_expr_bp(0);
+            let expr = self.parse_assign_expr();
             self.eat_trivia();
             if self.at(SyntaxKind::Semi) {
                 let s = expr.precede(self);
@@ -346,6 +658,11 @@ impl Parser {
                 continue;
             }
 
+            // block-like expressions may be followed by further statements without a `;`
+            if self.is_block_like(expr) && !self.at(SyntaxKind::RBrace) {
+                continue;
+            }
+
             // tail expression
             break;
         }
@@ -357,6 +674,7 @@ impl Parser {
     fn parse_let_stmt(&mut self) {
         let m = self.start();
         self.expect(SyntaxKind::KwLet);
+        self.eat_trivia();
         if self.at(SyntaxKind::KwMut) {
             self.bump_any();
         }
@@ -367,6 +685,13 @@ impl Parser {
         }
         self.expect(SyntaxKind::Eq);
         self.parse_expr_bp(0);
+        // `let PAT = expr else { ... };` runs the block when the pattern does not match
+        if self.nth_non_trivia(0) == SyntaxKind::KwElse {
+            self.eat_trivia();
+            self.bump_any();
+            self.eat_trivia();
+            self.parse_block();
+        }
         self.expect(SyntaxKind::Semi);
         self.complete(m, SyntaxKind::LetStmt);
     }
@@ -374,19 +699,48 @@ impl Parser {
     fn parse_return_stmt(&mut self) {
         let m = self.start();
         self.expect(SyntaxKind::KwReturn);
-        if !self.at(SyntaxKind::Semi) {
+        self.eat_trivia();
+        if !self.at(SyntaxKind::Semi) && !self.at(SyntaxKind::RBrace) {
             self.parse_expr_bp(0);
         }
-        self.expect(SyntaxKind::Semi);
+        // the `;` is optional when the `return` ends its block
+        if !self.at(SyntaxKind::RBrace) {
+            self.expect(SyntaxKind::Semi);
+        }
         self.complete(m, SyntaxKind::ReturnStmt);
     }
 
+    /// `break ['label] [expr];`, with the same optional `;` before `}` as `return`.
+    fn parse_break_stmt(&mut self) {
+        let m = self.start();
+        self.expect(SyntaxKind::KwBreak);
+        self.eat_trivia();
+        if self.at(SyntaxKind::Label) {
+            self.bump_any();
+            self.eat_trivia();
+        }
+        if !self.at(SyntaxKind::Semi) && !self.at(SyntaxKind::RBrace) {
+            self.parse_expr_bp(0);
+        }
+        if !self.at(SyntaxKind::RBrace) {
+            self.expect(SyntaxKind::Semi);
+        }
+        self.complete(m, SyntaxKind::BreakStmt);
+    }
+
     fn parse_pattern(&mut self) {
         self.eat_trivia();
         let m = self.start();
-        if self.at(SyntaxKind::Ident) {
+        if self.at(SyntaxKind::LParen) {
+            // tuple pattern: one nested `Pattern` per element
+            self.parse_paren_list(Self::parse_pattern);
+        } else if self.at(SyntaxKind::Ident) {
             self.parse_ident();
-        } else if self.current().is_literal() {
+            if self.at(SyntaxKind::LParen) {
+                // enum variant pattern, e.g. `Some(x)`
+                self.parse_paren_list(Self::parse_pattern);
+            }
+        } else if self.current().is_literal() || self.at_negative_number() {
             self.parse_literal();
         } else if self.at(SyntaxKind::Underscore) {
             self.bump_any();
@@ -397,6 +751,18 @@ impl Parser {
         self.complete(m, SyntaxKind::Pattern);
     }
 
+    /// Assignment is right-associative and only valid in statement position.
+    fn parse_assign_expr(&mut self) -> CompletedMarker {
+        let lhs = self.parse_expr_bp(0);
+        if self.current().is_assign_op() {
+            let m = lhs.precede(self);
+            self.bump_any();
+            self.parse_assign_expr();
+            return self.complete(m, SyntaxKind::AssignExpr);
+        }
+        lhs
+    }
+
     fn parse_expr_bp(&mut self, min_bp: u8) -> CompletedMarker {
         self.eat_trivia();
         let mut lhs = self.parse_postfix();
@@ -423,26 +789,28 @@ impl Parser {
         let mut lhs = self.parse_primary();
         loop {
             self.eat_trivia();
-            if self.at(SyntaxKind::LParen) {
+            let turbofish = self.at(SyntaxKind::ColonColon) && self.nth_non_trivia(1) == SyntaxKind::Lt;
+            if turbofish || self.at(SyntaxKind::LParen) {
                 let m = lhs.precede(self);
-                self.bump_any();
-                self.eat_trivia();
-                if !self.at(SyntaxKind::RParen) {
-                    loop {
-                        self.parse_expr_bp(0);
-                        self.eat_trivia();
-                        if self.at(SyntaxKind::Comma) {
-                            self.bump_any();
-                            self.eat_trivia();
-                            if self.at(SyntaxKind::RParen) {
-                                break;
-                            }
-                        } else {
-                            break;
-                        }
+                if turbofish {
+                    // `f::<T>(...)`: explicit type arguments for a generic function.
+                    let args = self.start();
+                    self.bump_any();
+                    self.eat_trivia();
+                    self.bump_any();
+                    self.parse_comma_separated(SyntaxKind::Gt, Self::parse_type);
+                    self.expect(SyntaxKind::Gt);
+                    self.complete(args, SyntaxKind::TypeArgList);
+                    self.eat_trivia();
+                    if !self.at(SyntaxKind::LParen) {
+                        self.error_here("expected `(` after type arguments");
                     }
                 }
-                self.expect(SyntaxKind::RParen);
+                if self.at(SyntaxKind::LParen) {
+                    self.parse_paren_list(|p| {
+                        p.parse_expr_bp(0);
+                    });
+                }
                 lhs = self.complete(m, SyntaxKind::CallExpr);
                 continue;
             }
@@ -463,6 +831,15 @@ impl Parser {
         if self.at(SyntaxKind::LBrace) {
             return self.parse_block();
         }
+        if self.at(SyntaxKind::Label) && self.nth_non_trivia(1) == SyntaxKind::Colon {
+            // `'label: { ... }`
+            let m = self.start();
+            self.bump_any();
+            self.expect(SyntaxKind::Colon);
+            self.eat_trivia();
+            self.parse_block();
+            return self.complete(m, SyntaxKind::LabeledBlock);
+        }
         if self.at(SyntaxKind::KwIf) {
             return self.parse_if_expr();
         }
@@ -470,17 +847,62 @@ impl Parser {
             return self.parse_match_expr();
         }
         if self.at(SyntaxKind::Ident) {
-            return self.parse_ident();
+            let ident = self.parse_ident();
+            // A struct literal where only a block may follow is reported, then parsed anyway
+            // so the block after it stays the body.
+            let ambiguous = self.at_restricted_struct_lit();
+            if ambiguous {
+                self.eat_trivia();
+                self.error_code_here("E0043", "ambiguous struct literal in condition; wrap in parentheses");
+            }
+            if ambiguous || self.at_struct_lit() {
+                let m = ident.precede(self);
+                self.parse_struct_lit_fields();
+                return self.complete(m, SyntaxKind::StructLit);
+            }
+            // `f::<T>` is left to `parse_postfix`, which makes the call.
+            if self.nth_non_trivia(0) != SyntaxKind::ColonColon || self.nth_non_trivia(1) == SyntaxKind::Lt {
+                return ident;
+            }
+            // `a::b::c`; whitespace around `::` is allowed and dropped by the formatter.
+            let m = ident.precede(self);
+            while self.nth_non_trivia(0) == SyntaxKind::ColonColon && self.nth_non_trivia(1) != SyntaxKind::Lt {
+                self.eat_trivia();
+                self.bump_any();
+                self.parse_ident();
+            }
+            return self.complete(m, SyntaxKind::PathExpr);
         }
-        if self.current().is_literal() {
+        if self.at(SyntaxKind::Pipe) || self.at(SyntaxKind::OrOr) {
+            return self.parse_closure();
+        }
+        if self.current().is_literal() || self.at_negative_number() {
             return self.parse_literal();
         }
         if self.at(SyntaxKind::LParen) {
+            // Parentheses lift the struct-literal restriction of an enclosing condition.
+            let saved = std::mem::replace(&mut self.no_struct_lit, false);
+            let paren = self.parse_paren_or_tuple();
+            self.no_struct_lit = saved;
+            return paren;
+        }
+        if self.at(SyntaxKind::KwAwait) {
+            // `await` binds tighter than binary operators but looser than calls and fields.
             let m = self.start();
             self.bump_any();
-            self.parse_expr_bp(0);
-            self.expect(SyntaxKind::RParen);
-            return self.complete(m, SyntaxKind::ParenExpr);
+            self.parse_postfix();
+            return self.complete(m, SyntaxKind::AwaitExpr);
+        }
+        if self.at(SyntaxKind::LBracket) {
+            let m = self.start();
+            self.bump_any();
+            let saved = std::mem::replace(&mut self.no_struct_lit, false);
+            self.parse_comma_separated(SyntaxKind::RBracket, |p| {
+                p.parse_expr_bp(0);
+            });
+            self.no_struct_lit = saved;
+            self.expect(SyntaxKind::RBracket);
+            return self.complete(m, SyntaxKind::ArrayExpr);
         }
         let m = self.start();
         self.error_here("expected expression");
@@ -490,31 +912,162 @@ impl Parser {
         self.complete(m, SyntaxKind::Error)
     }
 
+    fn parse_paren_or_tuple(&mut self) -> CompletedMarker {
+        let m = self.start();
+        self.bump_any();
+        self.eat_trivia();
+        if self.at(SyntaxKind::RParen) {
+            self.bump_any();
+            return self.complete(m, SyntaxKind::TupleExpr);
+        }
+        self.parse_expr_bp(0);
+        self.eat_trivia();
+        if !self.at(SyntaxKind::Comma) {
+            self.expect(SyntaxKind::RParen);
+            return self.complete(m, SyntaxKind::ParenExpr);
+        }
+        self.bump_any();
+        self.parse_comma_separated(SyntaxKind::RParen, |p| {
+            p.parse_expr_bp(0);
+        });
+        self.expect(SyntaxKind::RParen);
+        self.complete(m, SyntaxKind::TupleExpr)
+    }
+
+    /// `{` after a name starts a struct literal when it is followed by `}`, `..`, or `field:`.
+    fn at_struct_lit(&self) -> bool {
+        if self.no_struct_lit || self.nth_non_trivia(0) != SyntaxKind::LBrace {
+            return false;
+        }
+        match self.nth_non_trivia(1) {
+            SyntaxKind::RBrace | SyntaxKind::Range => true,
+            SyntaxKind::Ident => self.nth_non_trivia(2) == SyntaxKind::Colon,
+            _ => false,
+        }
+    }
+
+    /// `Name { field: ...` where a struct literal is not allowed, e.g. an `if` condition.
+    fn at_restricted_struct_lit(&self) -> bool {
+        self.no_struct_lit
+            && self.nth_non_trivia(0) == SyntaxKind::LBrace
+            && self.nth_non_trivia(1) == SyntaxKind::Ident
+            && self.nth_non_trivia(2) == SyntaxKind::Colon
+    }
+
+    /// `{ field: expr, ..., ..base }`; the spread must come last.
+    fn parse_struct_lit_fields(&mut self) {
+        let saved = std::mem::replace(&mut self.no_struct_lit, false);
+        self.expect(SyntaxKind::LBrace);
+        let mut spread_seen = false;
+        self.parse_comma_separated(SyntaxKind::RBrace, |p| {
+            if spread_seen {
+                p.error_here("expected '}' after struct literal spread");
+            }
+            if p.at(SyntaxKind::Range) {
+                spread_seen = true;
+                let spread = p.start();
+                p.bump_any();
+                p.parse_expr_bp(0);
+                p.complete(spread, SyntaxKind::StructLitSpread);
+                return;
+            }
+            let field = p.start();
+            p.parse_ident();
+            p.expect(SyntaxKind::Colon);
+            p.parse_expr_bp(0);
+            p.complete(field, SyntaxKind::StructLitField);
+        });
+        self.expect(SyntaxKind::RBrace);
+        self.no_struct_lit = saved;
+    }
+
+    fn parse_closure(&mut self) -> CompletedMarker {
+        let m = self.start();
+        let params = self.start();
+        if self.at(SyntaxKind::OrOr) {
+            self.bump_any();
+        } else {
+            self.bump_any();
+            self.parse_comma_separated(SyntaxKind::Pipe, |p| {
+                let param = p.start();
+                p.parse_ident();
+                p.eat_trivia();
+                if p.at(SyntaxKind::Colon) {
+                    p.bump_any();
+                    p.parse_type();
+                }
+                p.complete(param, SyntaxKind::Param);
+            });
+            self.expect(SyntaxKind::Pipe);
+        }
+        self.complete(params, SyntaxKind::ParamList);
+        self.parse_expr_bp(0);
+        self.complete(m, SyntaxKind::ClosureExpr)
+    }
+
     fn parse_if_expr(&mut self) -> CompletedMarker {
         let m = self.start();
         self.expect(SyntaxKind::KwIf);
-        self.parse_expr_bp(0);
+        let is_let = self.nth_non_trivia(0) == SyntaxKind::KwLet;
+        if is_let {
+            self.eat_trivia();
+            self.bump_any();
+            self.parse_pattern();
+            self.expect(SyntaxKind::Eq);
+            self.parse_restricted_expr();
+        } else {
+            self.parse_condition();
+        }
         self.parse_block();
-        if self.at(SyntaxKind::KwElse) {
+        if self.nth_non_trivia(0) == SyntaxKind::KwElse {
+            self.eat_trivia();
             self.bump_any();
+            self.eat_trivia();
             if self.at(SyntaxKind::KwIf) {
                 self.parse_if_expr();
             } else {
                 self.parse_block();
             }
         }
-        self.complete(m, SyntaxKind::IfExpr)
+        self.complete(m, if is_let { SyntaxKind::IfLetExpr } else { SyntaxKind::IfExpr })
+    }
+
+    /// Parses an `if`/`match` condition, recovering from a mistyped `=` for `==`.
+    fn parse_condition(&mut self) {
+        let cond = self.parse_restricted_expr();
+        if self.at(SyntaxKind::Eq) {
+            let m = cond.precede(self);
+            self.error_code_here("E0033", "did you mean `==`? assignment is not allowed in a condition");
+            self.bump_any();
+            self.parse_restricted_expr();
+            self.complete(m, SyntaxKind::Error);
+        }
+    }
+
+    /// An expression directly followed by a block, so a bare struct literal is not allowed.
+    fn parse_restricted_expr(&mut self) -> CompletedMarker {
+        let saved = std::mem::replace(&mut self.no_struct_lit, true);
+        let expr = self.parse_expr_bp(0);
+        self.no_struct_lit = saved;
+        expr
     }
 
     fn parse_match_expr(&mut self) -> CompletedMarker {
         let m = self.start();
         self.expect(SyntaxKind::KwMatch);
-        self.parse_expr_bp(0);
+        self.parse_condition();
         self.expect(SyntaxKind::LBrace);
         self.eat_trivia();
         while !self.at(SyntaxKind::RBrace) && !self.at(SyntaxKind::Eof) {
             let arm = self.start();
             self.parse_pattern();
+            if self.nth_non_trivia(0) == SyntaxKind::KwIf {
+                self.eat_trivia();
+                let guard = self.start();
+                self.bump_any();
+                self.parse_expr_bp(0);
+                self.complete(guard, SyntaxKind::MatchGuard);
+            }
             self.expect(SyntaxKind::FatArrow);
             self.parse_expr_bp(0);
             if self.at(SyntaxKind::Comma) {
@@ -545,6 +1098,10 @@ impl Parser {
 
     fn parse_literal(&mut self) -> CompletedMarker {
         let m = self.start();
+        if self.at_negative_number() {
+            self.bump_any();
+            self.eat_trivia();
+        }
         if self.current().is_literal() {
             self.bump_any();
         } else {
@@ -556,6 +1113,17 @@ impl Parser {
         self.complete(m, SyntaxKind::LiteralNode)
     }
 
+    fn is_block_like(&self, marker: CompletedMarker) -> bool {
+        matches!(
+            self.events[marker.pos],
+            Event::StartNode(SyntaxKind::IfExpr
+                    | SyntaxKind::IfLetExpr
+                    | SyntaxKind::MatchExpr
+                    | SyntaxKind::Block
+                    | SyntaxKind::LabeledBlock)
+        )
+    }
+
     fn start(&mut self) -> Marker {
         let pos = self.events.len();
         self.events.push(Event::StartNode(SyntaxKind::Tomestone));
@@ -573,7 +1141,13 @@ impl Parser {
         if self.at(kind) {
             self.bump_any();
         } else {
-            self.error_here(&format!("expected {:?}", kind));
+            match self.unclosed_delim(kind) {
+                Some((open, related)) => {
+                    let message = format!("expected {}: unclosed `{}` opened here", describe_token(kind), open);
+                    self.push_error_at(None, &message, Some(related));
+                }
+                None => self.error_here(&format!("expected {}", describe_token(kind))),
+            }
             let m = self.start();
             if !self.at(SyntaxKind::Eof) {
                 self.bump_any();
@@ -588,6 +1162,13 @@ impl Parser {
         }
     }
 
+    /// Eats trivia up to a `///` comment, which is left to open the next field or variant node.
+    fn eat_trivia_before_doc(&mut self) {
+        while self.current().is_trivia() && self.current() != SyntaxKind::DocComment {
+            self.bump_any();
+        }
+    }
+
     fn current(&self) -> SyntaxKind {
         self.tokens.get(self.pos).map(|t| t.kind).unwrap_or(SyntaxKind::Eof)
     }
@@ -596,13 +1177,70 @@ impl Parser {
         self.current() == kind
     }
 
-    fn nth(&self, n: usize) -> SyntaxKind {
-        self.tokens.get(self.pos + n).map(|t| t.kind).unwrap_or(SyntaxKind::Eof)
+    fn nth_non_trivia(&self, n: usize) -> SyntaxKind {
+        self.tokens[self.pos.min(self.tokens.len())..]
+            .iter()
+            .map(|t| t.kind)
+            .filter(|k| !k.is_trivia())
+            .nth(n)
+            .unwrap_or(SyntaxKind::Eof)
+    }
+
+    /// A `-` before a numeric literal, even with whitespace between, starts a negative literal;
+    /// [`jalm_syntax::literal_value`] folds the two into one value.
+    fn at_negative_number(&self) -> bool {
+        self.at(SyntaxKind::Minus) && matches!(self.nth_non_trivia(1), SyntaxKind::Int | SyntaxKind::Float)
+    }
+
+    fn current_text(&self) -> &str {
+        self.tokens.get(self.pos).map(|t| t.text.as_str()).unwrap_or("")
+    }
+
+    /// Returns the item keyword following an optional `pub` / `pub(...)` prefix.
+    fn item_keyword(&self) -> SyntaxKind {
+        let mut kinds = self.tokens[self.pos.min(self.tokens.len())..]
+            .iter()
+            .map(|t| t.kind)
+            .filter(|k| !k.is_trivia())
+            .peekable();
+        // Attribute arguments are strings and `key = "value"` pairs, so the first `)` ends them.
+        while kinds.next_if_eq(&SyntaxKind::At).is_some() {
+            kinds.next_if_eq(&SyntaxKind::Ident);
+            if kinds.next_if_eq(&SyntaxKind::LParen).is_some() {
+                kinds.by_ref().find(|k| matches!(k, SyntaxKind::RParen | SyntaxKind::Eof));
+            }
+        }
+        if kinds.next_if_eq(&SyntaxKind::KwPub).is_some() && kinds.next_if_eq(&SyntaxKind::LParen).is_some() {
+            kinds.by_ref().find(|k| matches!(k, SyntaxKind::RParen | SyntaxKind::Eof));
+        }
+        kinds.next().unwrap_or(SyntaxKind::Eof)
+    }
+
+    /// Pops the innermost open delimiter if `closer` is the delimiter that closes it.
+    fn unclosed_delim(&mut self, closer: SyntaxKind) -> Option<(&'static str, Span)> {
+        let (open, _) = self.open_delims.last()?;
+        let text = match (open, closer) {
+            (SyntaxKind::LBrace, SyntaxKind::RBrace) => "{",
+            (SyntaxKind::LParen, SyntaxKind::RParen) => "(",
+            (SyntaxKind::LBracket, SyntaxKind::RBracket) => "[",
+            _ => return None,
+        };
+        self.open_delims.pop().map(|(_, span)| (text, span))
     }
 
     fn bump_any(&mut self) {
         let token = self.tokens.get(self.pos).cloned();
         if let Some(token) = token {
+            match token.kind {
+                SyntaxKind::LBrace | SyntaxKind::LParen | SyntaxKind::LBracket => {
+                    let span = Span { start: token.span.start, end: token.span.end };
+                    self.open_delims.push((token.kind, span));
+                }
+                SyntaxKind::RBrace | SyntaxKind::RParen | SyntaxKind::RBracket => {
+                    self.unclosed_delim(token.kind);
+                }
+                _ => {}
+            }
             if token.kind != SyntaxKind::Eof {
                 self.events.push(Event::Token(token.kind, token.text));
             }
@@ -611,10 +1249,24 @@ impl Parser {
     }
 
     fn error_here(&mut self, message: &str) {
+        self.push_error(None, message);
+    }
+
+    fn error_code_here(&mut self, code: &str, message: &str) {
+        self.push_error(Some(code), message);
+    }
+
+    fn push_error(&mut self, code: Option<&str>, message: &str) {
+        self.push_error_at(code, message, None);
+    }
+
+    fn push_error_at(&mut self, code: Option<&str>, message: &str, related: Option<Span>) {
         let span = self.tokens.get(self.pos).map(|t| t.span.clone()).unwrap_or(0..0);
         self.errors.push(ParseError {
+            code: code.map(str::to_string),
             message: message.to_string(),
             span: Span { start: span.start, end: span.end },
+            related,
         });
     }
 }
@@ -638,3 +1290,116 @@ fn infix_binding_power(kind: SyntaxKind) -> Option<(u8, u8)> {
     };
     Some((l, r))
 }
+
+/// How `kind` is named in "expected ..." messages: the token's text in backticks, or a
+/// description for tokens without fixed text. The match has no catch-all arm, so a new
+/// kind must be given a name here before it can appear in a diagnostic.
+fn describe_token(kind: SyntaxKind) -> String {
+    let text = match kind {
+        SyntaxKind::Eof => return "end of file".to_string(),
+        SyntaxKind::Whitespace => return "whitespace".to_string(),
+        SyntaxKind::Comment => return "a comment".to_string(),
+        SyntaxKind::DocComment => return "a doc comment".to_string(),
+        SyntaxKind::Shebang => return "a `#!` line".to_string(),
+        SyntaxKind::CfgDisabled => return "an item disabled by `@cfg`".to_string(),
+        SyntaxKind::ErrorToken => return "an unrecognized character".to_string(),
+        SyntaxKind::Ident => return "an identifier".to_string(),
+        SyntaxKind::Label => return "a label".to_string(),
+        SyntaxKind::Int => return "an integer".to_string(),
+        SyntaxKind::Float => return "a float".to_string(),
+        SyntaxKind::String => return "a string".to_string(),
+        SyntaxKind::Bytes => return "a byte string".to_string(),
+        SyntaxKind::Tomestone | SyntaxKind::Root | SyntaxKind::ModuleDecl | SyntaxKind::UseDecl
+        | SyntaxKind::UsePath | SyntaxKind::UseGroup | SyntaxKind::UseGroupItem | SyntaxKind::FnDecl
+        | SyntaxKind::ParamList | SyntaxKind::Param | SyntaxKind::GenericParamList | SyntaxKind::Type
+        | SyntaxKind::ArrayType | SyntaxKind::EffectSet | SyntaxKind::EffectAliasDecl | SyntaxKind::ConstDecl
+        | SyntaxKind::TypeAlias | SyntaxKind::ImplBlock | SyntaxKind::Visibility | SyntaxKind::Attribute
+        | SyntaxKind::AttrKeyValue | SyntaxKind::ModuleAttribute | SyntaxKind::StructDecl
+        | SyntaxKind::StructField | SyntaxKind::EnumDecl | SyntaxKind::EnumVariant | SyntaxKind::Block
+        | SyntaxKind::StmtList | SyntaxKind::LetStmt | SyntaxKind::ReturnStmt | SyntaxKind::BreakStmt
+        | SyntaxKind::ExprStmt | SyntaxKind::IfExpr | SyntaxKind::IfLetExpr | SyntaxKind::MatchExpr
+        | SyntaxKind::MatchArm | SyntaxKind::MatchGuard | SyntaxKind::CallExpr | SyntaxKind::TypeArgList
+        | SyntaxKind::MemberExpr | SyntaxKind::BinExpr | SyntaxKind::AssignExpr | SyntaxKind::ParenExpr
+        | SyntaxKind::TupleExpr | SyntaxKind::ArrayExpr | SyntaxKind::AwaitExpr | SyntaxKind::PathExpr
+        | SyntaxKind::ClosureExpr | SyntaxKind::StructLit | SyntaxKind::StructLitField
+        | SyntaxKind::StructLitSpread | SyntaxKind::LabeledBlock | SyntaxKind::IdentNode
+        | SyntaxKind::LiteralNode | SyntaxKind::Pattern | SyntaxKind::Error => return "a syntax node".to_string(),
+        SyntaxKind::Underscore => "_",
+        SyntaxKind::KwMod => "mod",
+        SyntaxKind::KwUse => "use",
+        SyntaxKind::KwFn => "fn",
+        SyntaxKind::KwAsync => "async",
+        SyntaxKind::KwStruct => "struct",
+        SyntaxKind::KwEnum => "enum",
+        SyntaxKind::KwMatch => "match",
+        SyntaxKind::KwIf => "if",
+        SyntaxKind::KwElse => "else",
+        SyntaxKind::KwFor => "for",
+        SyntaxKind::KwIn => "in",
+        SyntaxKind::KwReturn => "return",
+        SyntaxKind::KwBreak => "break",
+        SyntaxKind::KwLet => "let",
+        SyntaxKind::KwMut => "mut",
+        SyntaxKind::KwTrue => "true",
+        SyntaxKind::KwFalse => "false",
+        SyntaxKind::KwScope => "scope",
+        SyntaxKind::KwSpawn => "spawn",
+        SyntaxKind::KwJoin => "join",
+        SyntaxKind::KwAwait => "await",
+        SyntaxKind::KwAs => "as",
+        SyntaxKind::KwPub => "pub",
+        SyntaxKind::KwConst => "const",
+        SyntaxKind::KwType => "type",
+        SyntaxKind::KwImpl => "impl",
+        SyntaxKind::LParen => "(",
+        SyntaxKind::RParen => ")",
+        SyntaxKind::LBrace => "{",
+        SyntaxKind::RBrace => "}",
+        SyntaxKind::LBracket => "[",
+        SyntaxKind::RBracket => "]",
+        SyntaxKind::Comma => ",",
+        SyntaxKind::Semi => ";",
+        SyntaxKind::Colon => ":",
+        SyntaxKind::Dot => ".",
+        SyntaxKind::ColonColon => "::",
+        SyntaxKind::Arrow => "->",
+        SyntaxKind::FatArrow => "=>",
+        SyntaxKind::At => "@",
+        SyntaxKind::Question => "?",
+        SyntaxKind::QuestionQuestion => "??",
+        SyntaxKind::Bang => "!",
+        SyntaxKind::Plus => "+",
+        SyntaxKind::Minus => "-",
+        SyntaxKind::Star => "*",
+        SyntaxKind::Slash => "/",
+        SyntaxKind::Percent => "%",
+        SyntaxKind::Eq => "=",
+        SyntaxKind::EqEq => "==",
+        SyntaxKind::Neq => "!=",
+        SyntaxKind::Lt => "<",
+        SyntaxKind::Lte => "<=",
+        SyntaxKind::Gt => ">",
+        SyntaxKind::Gte => ">=",
+        SyntaxKind::AndAnd => "&&",
+        SyntaxKind::OrOr => "||",
+        SyntaxKind::Amp => "&",
+        SyntaxKind::Pipe => "|",
+        SyntaxKind::Caret => "^",
+        SyntaxKind::Tilde => "~",
+        SyntaxKind::Shl => "<<",
+        SyntaxKind::Shr => ">>",
+        SyntaxKind::ShlEq => "<<=",
+        SyntaxKind::ShrEq => ">>=",
+        SyntaxKind::PlusEq => "+=",
+        SyntaxKind::MinusEq => "-=",
+        SyntaxKind::StarEq => "*=",
+        SyntaxKind::SlashEq => "/=",
+        SyntaxKind::PercentEq => "%=",
+        SyntaxKind::AmpEq => "&=",
+        SyntaxKind::PipeEq => "|=",
+        SyntaxKind::CaretEq => "^=",
+        SyntaxKind::Range => "..",
+        SyntaxKind::RangeEq => "..=",
+    };
+    format!("`{text}`")
+}
@@ -76,6 +76,7 @@ pub extern "C" fn jalm_alloc(size: usize) -> *mut u8 {
 }
 
 #[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
 pub extern "C" fn jalm_realloc(ptr: *mut u8, old_size: usize, new_size: usize) -> *mut u8 {
     if ptr.is_null() {
         return jalm_alloc(new_size);
@@ -107,6 +108,7 @@ pub extern "C" fn jalm_bytes_alloc(len: usize) -> *mut u8 {
 }
 
 #[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
 pub extern "C" fn jalm_bytes_clone(src: *const u8, len: usize) -> *mut u8 {
     if src.is_null() {
         return core::ptr::null_mut();
@@ -122,6 +124,7 @@ pub extern "C" fn jalm_bytes_clone(src: *const u8, len: usize) -> *mut u8 {
 }
 
 #[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
 pub extern "C" fn jalm_memcpy(dst: *mut u8, src: *const u8, len: usize) -> *mut u8 {
     if dst.is_null() || src.is_null() {
         return core::ptr::null_mut();
@@ -133,6 +136,7 @@ pub extern "C" fn jalm_memcpy(dst: *mut u8, src: *const u8, len: usize) -> *mut
 }
 
 #[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
 pub extern "C" fn jalm_memset(dst: *mut u8, value: u8, len: usize) -> *mut u8 {
     if dst.is_null() {
         return core::ptr::null_mut();
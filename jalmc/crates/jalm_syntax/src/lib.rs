@@ -2,6 +2,9 @@ use logos::Logos;
 use rowan::{GreenNode, Language};
 use std::ops::Range;
 
+/// Version of this crate, reported by `jalmt version --verbose`.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[repr(u16)]
 pub enum SyntaxKind {
@@ -9,9 +12,17 @@ pub enum SyntaxKind {
     Eof,
     Whitespace,
     Comment,
+    /// A `///` line comment documenting the item or field that follows it.
+    DocComment,
+    /// A `#!...` line at the very start of a file.
+    Shebang,
+    /// The text of an item whose `@cfg(feature = "...")` names a disabled feature.
+    CfgDisabled,
     ErrorToken,
 
     Ident,
+    /// `'ident`, naming a block that `break 'ident` can exit.
+    Label,
     Int,
     Float,
     String,
@@ -30,6 +41,7 @@ pub enum SyntaxKind {
     KwFor,
     KwIn,
     KwReturn,
+    KwBreak,
     KwLet,
     KwMut,
     KwTrue,
@@ -40,6 +52,9 @@ pub enum SyntaxKind {
     KwAwait,
     KwAs,
     KwPub,
+    KwConst,
+    KwType,
+    KwImpl,
 
     LParen,
     RParen,
@@ -54,6 +69,7 @@ pub enum SyntaxKind {
     ColonColon,
     Arrow,
     FatArrow,
+    At,
 
     Question,
     QuestionQuestion,
@@ -101,11 +117,28 @@ pub enum SyntaxKind {
     ModuleDecl,
     UseDecl,
     UsePath,
+    /// `{a as b, c}` at the end of a `UsePath`; each member is a `UseGroupItem`.
+    UseGroup,
+    UseGroupItem,
     FnDecl,
     ParamList,
     Param,
+    /// `<T, U>` after a function's name; each parameter is an `IdentNode`.
+    GenericParamList,
     Type,
+    /// `[T; N]` or `[T]`, inside a `Type`; `N` is a constant expression.
+    ArrayType,
     EffectSet,
+    EffectAliasDecl,
+    ConstDecl,
+    /// `type Name = Type;`
+    TypeAlias,
+    /// `impl Name { fn ... }`; each method is a `FnDecl` child.
+    ImplBlock,
+    Visibility,
+    Attribute,
+    AttrKeyValue,
+    ModuleAttribute,
     StructDecl,
     StructField,
     EnumDecl,
@@ -114,14 +147,29 @@ pub enum SyntaxKind {
     StmtList,
     LetStmt,
     ReturnStmt,
+    BreakStmt,
     ExprStmt,
     IfExpr,
+    IfLetExpr,
     MatchExpr,
     MatchArm,
+    MatchGuard,
     CallExpr,
+    /// `::<T, U>` between a call's callee and its arguments; one `Type` per argument.
+    TypeArgList,
     MemberExpr,
     BinExpr,
+    AssignExpr,
     ParenExpr,
+    TupleExpr,
+    ArrayExpr,
+    AwaitExpr,
+    PathExpr,
+    ClosureExpr,
+    StructLit,
+    StructLitField,
+    StructLitSpread,
+    LabeledBlock,
     IdentNode,
     LiteralNode,
     Pattern,
@@ -130,12 +178,36 @@ pub enum SyntaxKind {
 
 impl SyntaxKind {
     pub fn is_trivia(self) -> bool {
-        matches!(self, SyntaxKind::Whitespace | SyntaxKind::Comment)
+        matches!(self, SyntaxKind::Whitespace | SyntaxKind::Comment | SyntaxKind::DocComment | SyntaxKind::Shebang | SyntaxKind::CfgDisabled)
     }
 
     pub fn is_literal(self) -> bool {
         matches!(self, SyntaxKind::Int | SyntaxKind::Float | SyntaxKind::String | SyntaxKind::Bytes | SyntaxKind::KwTrue | SyntaxKind::KwFalse)
     }
+
+    pub fn is_assign_op(self) -> bool {
+        matches!(
+            self,
+            SyntaxKind::Eq
+                | SyntaxKind::PlusEq
+                | SyntaxKind::MinusEq
+                | SyntaxKind::StarEq
+                | SyntaxKind::SlashEq
+                | SyntaxKind::PercentEq
+        )
+    }
+
+    /// The binary operator a compound assignment applies, e.g. `+` for `+=`.
+    pub fn compound_op(self) -> Option<SyntaxKind> {
+        match self {
+            SyntaxKind::PlusEq => Some(SyntaxKind::Plus),
+            SyntaxKind::MinusEq => Some(SyntaxKind::Minus),
+            SyntaxKind::StarEq => Some(SyntaxKind::Star),
+            SyntaxKind::SlashEq => Some(SyntaxKind::Slash),
+            SyntaxKind::PercentEq => Some(SyntaxKind::Percent),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -172,7 +244,7 @@ enum LexKind {
     #[regex(r"//[^\n]*")]
     LineComment,
 
-    #[regex(r"/\*([^*]|\*+[^*/])*\*+/")]
+    #[token("/*", lex_block_comment)]
     BlockComment,
 
     #[token("mod")]
@@ -199,6 +271,8 @@ enum LexKind {
     KwIn,
     #[token("return")]
     KwReturn,
+    #[token("break")]
+    KwBreak,
     #[token("let")]
     KwLet,
     #[token("mut")]
@@ -219,6 +293,12 @@ enum LexKind {
     KwAs,
     #[token("pub")]
     KwPub,
+    #[token("const")]
+    KwConst,
+    #[token("type")]
+    KwType,
+    #[token("impl")]
+    KwImpl,
 
     #[token("(")]
     LParen,
@@ -246,6 +326,8 @@ enum LexKind {
     Arrow,
     #[token("=>")]
     FatArrow,
+    #[token("@")]
+    At,
 
     #[token("??")]
     QuestionQuestion,
@@ -328,18 +410,47 @@ enum LexKind {
     #[token("_", priority = 3)]
     Underscore,
 
-    #[regex(r"[0-9]([0-9_])*\.[0-9]([0-9_])*")]
+    #[regex(r"[0-9]([0-9_])*\.[0-9]([0-9_])*([eE][+-]?[0-9]([0-9_])*)?")]
+    #[regex(r"[0-9]([0-9_])*[eE][+-]?[0-9]([0-9_])*")]
     Float,
     #[regex(r"[0-9]([0-9_])*")]
+    #[regex(r"0x[0-9a-fA-F]([0-9a-fA-F_])*")]
+    #[regex(r"0o[0-7]([0-7_])*")]
+    #[regex(r"0b[01]([01_])*")]
     Int,
 
     #[regex(r#"b\"([^\"\\]|\\.)*\""#)]
     Bytes,
     #[regex(r#"\"([^\"\\]|\\.)*\""#)]
     String,
+    #[regex(r#"r#*\""#, lex_raw_string)]
+    RawString,
 
     #[regex(r"[A-Za-z_][A-Za-z0-9_]*", priority = 1)]
     Ident,
+    #[regex(r"'[A-Za-z_][A-Za-z0-9_]*")]
+    Label,
+}
+
+/// Consumes a block comment up to the first `*/`, or to end of input when it is
+/// never closed; see [`is_unterminated_block_comment`].
+fn lex_block_comment(lex: &mut logos::Lexer<LexKind>) {
+    let len = lex.remainder().find("*/").map(|pos| pos + 2).unwrap_or(lex.remainder().len());
+    lex.bump(len);
+}
+
+/// Consumes the body of `r"..."` / `r#"..."#`: the literal ends at the first `"`
+/// followed by as many `#` as the opening delimiter had.
+fn lex_raw_string(lex: &mut logos::Lexer<LexKind>) -> bool {
+    let hashes = lex.slice().len() - 2;
+    let closing = format!("\"{}", "#".repeat(hashes));
+    match lex.remainder().find(&closing) {
+        Some(pos) => {
+            lex.bump(pos + closing.len());
+            true
+        }
+        None => false,
+    }
 }
 
 fn lex_kind_to_syntax(kind: LexKind) -> SyntaxKind {
@@ -359,6 +470,7 @@ fn lex_kind_to_syntax(kind: LexKind) -> SyntaxKind {
         LexKind::KwFor => SyntaxKind::KwFor,
         LexKind::KwIn => SyntaxKind::KwIn,
         LexKind::KwReturn => SyntaxKind::KwReturn,
+        LexKind::KwBreak => SyntaxKind::KwBreak,
         LexKind::KwLet => SyntaxKind::KwLet,
         LexKind::KwMut => SyntaxKind::KwMut,
         LexKind::KwTrue => SyntaxKind::KwTrue,
@@ -369,6 +481,9 @@ fn lex_kind_to_syntax(kind: LexKind) -> SyntaxKind {
         LexKind::KwAwait => SyntaxKind::KwAwait,
         LexKind::KwAs => SyntaxKind::KwAs,
         LexKind::KwPub => SyntaxKind::KwPub,
+        LexKind::KwConst => SyntaxKind::KwConst,
+        LexKind::KwType => SyntaxKind::KwType,
+        LexKind::KwImpl => SyntaxKind::KwImpl,
 
         LexKind::LParen => SyntaxKind::LParen,
         LexKind::RParen => SyntaxKind::RParen,
@@ -383,6 +498,7 @@ fn lex_kind_to_syntax(kind: LexKind) -> SyntaxKind {
         LexKind::ColonColon => SyntaxKind::ColonColon,
         LexKind::Arrow => SyntaxKind::Arrow,
         LexKind::FatArrow => SyntaxKind::FatArrow,
+        LexKind::At => SyntaxKind::At,
 
         LexKind::Question => SyntaxKind::Question,
         LexKind::QuestionQuestion => SyntaxKind::QuestionQuestion,
@@ -430,19 +546,29 @@ fn lex_kind_to_syntax(kind: LexKind) -> SyntaxKind {
 
         LexKind::Float => SyntaxKind::Float,
         LexKind::Int => SyntaxKind::Int,
-        LexKind::String => SyntaxKind::String,
+        LexKind::String | LexKind::RawString => SyntaxKind::String,
         LexKind::Bytes => SyntaxKind::Bytes,
         LexKind::Ident => SyntaxKind::Ident,
+        LexKind::Label => SyntaxKind::Label,
     }
 }
 
 pub fn lex(source: &str) -> Vec<Token> {
-    let mut lexer = LexKind::lexer(source);
     let mut tokens = Vec::new();
+    // `#!/usr/bin/env jalmt run` on the first line is trivia; `#` anywhere else is an error.
+    let mut offset = 0;
+    if source.starts_with("#!") {
+        // The line ends before `\r\n` as well as `\n`, leaving the `\r` to the whitespace after it.
+        offset = source.find(['\r', '\n']).unwrap_or(source.len());
+        tokens.push(Token { kind: SyntaxKind::Shebang, text: source[..offset].to_string(), span: 0..offset });
+    }
+    let mut lexer = LexKind::lexer(&source[offset..]);
     while let Some(result) = lexer.next() {
-        let span = lexer.span();
+        let span = lexer.span().start + offset..lexer.span().end + offset;
         let text = source[span.clone()].to_string();
         let kind = match result {
+            // `////...` is an ordinary comment, as in Rust.
+            Ok(LexKind::LineComment) if text.starts_with("///") && !text.starts_with("////") => SyntaxKind::DocComment,
             Ok(kind) => lex_kind_to_syntax(kind),
             Err(()) => SyntaxKind::ErrorToken,
         };
@@ -451,30 +577,150 @@ pub fn lex(source: &str) -> Vec<Token> {
     tokens
 }
 
+/// Returns true for a `/* ...` comment token that runs to end of input without `*/`.
+pub fn is_unterminated_block_comment(text: &str) -> bool {
+    text.starts_with("/*") && (text.len() < 4 || !text.ends_with("*/"))
+}
+
+/// Value of an integer literal such as `-1_000`, `0xFF`, `0o17`, or `0b1010`.
+pub fn int_literal_value(text: &str) -> Option<i128> {
+    let (negative, text) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text),
+    };
+    let digits = text.replace('_', "");
+    let (radix, digits) = match digits.get(..2) {
+        Some("0x") => (16, &digits[2..]),
+        Some("0o") => (8, &digits[2..]),
+        Some("0b") => (2, &digits[2..]),
+        _ => (10, &digits[..]),
+    };
+    let value = i128::from_str_radix(digits, radix).ok()?;
+    Some(if negative { -value } else { value })
+}
+
+/// Decodes a `b"..."` literal. Escapes are `\xNN`, `\\`, `\"`, `\n`, `\t`, and `\0`;
+/// an invalid escape is returned as its byte range within `text`.
+pub fn decode_byte_string(text: &str) -> Result<Vec<u8>, std::ops::Range<usize>> {
+    let body = text
+        .strip_prefix("b\"")
+        .and_then(|t| t.strip_suffix('"'))
+        .ok_or(0..text.len())?;
+    decode_escapes(body, 2)
+}
+
+/// Decodes a plain `"..."` literal with the escapes of [`decode_byte_string`], or returns a
+/// raw `r"..."`/`r#"..."#` literal's contents as written. Escapes that do not form UTF-8,
+/// such as `\xff`, are an error over the whole literal.
+pub fn decode_string(text: &str) -> Result<String, std::ops::Range<usize>> {
+    if let Some(raw) = text.strip_prefix('r') {
+        let hashes = raw.len() - raw.trim_start_matches('#').len();
+        return raw
+            .get(hashes..raw.len().saturating_sub(hashes))
+            .and_then(|t| t.strip_prefix('"')?.strip_suffix('"'))
+            .map(str::to_string)
+            .ok_or(0..text.len());
+    }
+    let body = text.strip_prefix('"').and_then(|t| t.strip_suffix('"')).ok_or(0..text.len())?;
+    String::from_utf8(decode_escapes(body, 1)?).map_err(|_| 0..text.len())
+}
+
+/// Decodes the escapes in a literal's `body`, which starts `base` bytes into the literal.
+fn decode_escapes(body: &str, base: usize) -> Result<Vec<u8>, std::ops::Range<usize>> {
+    let mut out = Vec::new();
+    let mut chars = body.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if c != '\\' {
+            let mut buf = [0; 4];
+            out.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            continue;
+        }
+        let escape_end = |end: usize| base + i..base + end;
+        match chars.next() {
+            Some((_, '\\')) => out.push(b'\\'),
+            Some((_, '"')) => out.push(b'"'),
+            Some((_, 'n')) => out.push(b'\n'),
+            Some((_, 't')) => out.push(b'\t'),
+            Some((_, '0')) => out.push(0),
+            Some((x, 'x')) => {
+                let hex: String = (0..2).filter_map(|_| chars.next_if(|(_, h)| h.is_ascii_hexdigit())).map(|(_, h)| h).collect();
+                if hex.len() != 2 {
+                    return Err(escape_end(x + 1 + hex.len()));
+                }
+                out.push(u8::from_str_radix(&hex, 16).expect("two hex digits"));
+            }
+            Some((j, other)) => return Err(escape_end(j + other.len_utf8())),
+            None => return Err(escape_end(i + 1)),
+        }
+    }
+    Ok(out)
+}
+
+/// Normalizes `\r\n` line endings inside a multi-line literal to `\n`.
+pub fn normalize_literal_newlines(text: &str) -> String {
+    text.replace("\r\n", "\n")
+}
+
+/// Returns the literal token kind and text of a `LiteralNode`, folding a leading
+/// `-` into numeric literals so `-1`, or `- 1`, reads as a single negative value.
+pub fn literal_value(node: &SyntaxNode) -> Option<(SyntaxKind, String)> {
+    let mut negative = false;
+    for el in node.children_with_tokens() {
+        if let SyntaxElement::Token(t) = el {
+            match t.kind() {
+                SyntaxKind::Minus => negative = true,
+                kind if kind.is_trivia() => {}
+                kind if kind.is_literal() => {
+                    let text = if negative { format!("-{}", t.text()) } else { t.text().to_string() };
+                    return Some((kind, text));
+                }
+                _ => return None,
+            }
+        }
+    }
+    None
+}
+
 pub fn to_string_lossless(node: &SyntaxNode) -> String {
     node.text().to_string()
 }
 
 pub fn dump_tree(node: &SyntaxNode) -> String {
     let mut out = String::new();
-    dump_tree_impl(node, 0, &mut out);
+    dump_tree_impl(node, 0, &mut out, false);
+    out
+}
+
+/// Like [`dump_tree`], but appends `@start..end` to every node and token line.
+pub fn dump_tree_with_spans(node: &SyntaxNode) -> String {
+    let mut out = String::new();
+    dump_tree_impl(node, 0, &mut out, true);
     out
 }
 
-fn dump_tree_impl(node: &SyntaxNode, depth: usize, out: &mut String) {
+fn dump_tree_impl(node: &SyntaxNode, depth: usize, out: &mut String, spans: bool) {
     let indent = "  ".repeat(depth);
-    out.push_str(&format!("{}{:?}\n", indent, node.kind()));
+    out.push_str(&format!("{}{:?}{}\n", indent, node.kind(), span_suffix(node.text_range(), spans)));
     for child in node.children_with_tokens() {
         match child {
-            rowan::NodeOrToken::Node(n) => dump_tree_impl(&n, depth + 1, out),
+            rowan::NodeOrToken::Node(n) => dump_tree_impl(&n, depth + 1, out, spans),
             rowan::NodeOrToken::Token(t) => {
-                let text = t.text().replace('\n', "\\n");
-                out.push_str(&format!("{}  {:?} '{}'\n", indent, t.kind(), text));
+                let text = t.text().replace('\r', "\\r").replace('\n', "\\n");
+                let suffix = span_suffix(t.text_range(), spans);
+                out.push_str(&format!("{}  {:?} '{}'{}\n", indent, t.kind(), text, suffix));
             }
         }
     }
 }
 
+fn span_suffix(range: rowan::TextRange, spans: bool) -> String {
+    if spans {
+        format!(" @{}..{}", u32::from(range.start()), u32::from(range.end()))
+    } else {
+        String::new()
+    }
+}
+
 pub fn build_green(events: Vec<crate::parser_events::Event>) -> GreenNode {
     use crate::parser_events::Event;
     let mut builder = rowan::GreenNodeBuilder::new();
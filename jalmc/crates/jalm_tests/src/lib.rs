@@ -1,5 +1,5 @@
 use jalm_parser::parse;
-use jalm_syntax::{dump_tree, to_string_lossless};
+use jalm_syntax::{dump_tree, to_string_lossless, SyntaxElement, SyntaxNode};
 use serde_json::json;
 
 pub fn round_trip(source: &str) -> (String, String) {
@@ -16,3 +16,54 @@ pub fn diagnostics_json(source: &str) -> serde_json::Value {
         "errors": parsed.errors,
     })
 }
+
+/// True when `a` and `b` have the same nodes and non-trivia tokens, ignoring whitespace
+/// and comments.
+pub fn tree_eq_ignoring_trivia(a: &SyntaxNode, b: &SyntaxNode) -> bool {
+    tree_diff(a, b).is_none()
+}
+
+/// The first structural difference between `a` and `b`, ignoring trivia, as
+/// `path: left vs right`, where `path` lists the enclosing node kinds from the root.
+pub fn tree_diff(a: &SyntaxNode, b: &SyntaxNode) -> Option<String> {
+    let mut path = Vec::new();
+    node_diff(a, b, &mut path)
+}
+
+fn node_diff(a: &SyntaxNode, b: &SyntaxNode, path: &mut Vec<String>) -> Option<String> {
+    if a.kind() != b.kind() {
+        return Some(format!("{}: node {:?} vs node {:?}", path_text(path), a.kind(), b.kind()));
+    }
+    path.push(format!("{:?}", a.kind()));
+    let mut left = a.children_with_tokens().filter(|el| !el.kind().is_trivia());
+    let mut right = b.children_with_tokens().filter(|el| !el.kind().is_trivia());
+    loop {
+        let diff = match (left.next(), right.next()) {
+            (None, None) => break,
+            (Some(SyntaxElement::Node(l)), Some(SyntaxElement::Node(r))) => node_diff(&l, &r, path),
+            (Some(SyntaxElement::Token(l)), Some(SyntaxElement::Token(r))) if l.kind() == r.kind() && l.text() == r.text() => None,
+            (l, r) => Some(format!("{}: {} vs {}", path_text(path), element_text(l.as_ref()), element_text(r.as_ref()))),
+        };
+        if diff.is_some() {
+            return diff;
+        }
+    }
+    path.pop();
+    None
+}
+
+fn path_text(path: &[String]) -> String {
+    if path.is_empty() {
+        "<root>".to_string()
+    } else {
+        path.join(" > ")
+    }
+}
+
+fn element_text(el: Option<&SyntaxElement>) -> String {
+    match el {
+        Some(SyntaxElement::Node(n)) => format!("node {:?}", n.kind()),
+        Some(SyntaxElement::Token(t)) => format!("token {:?} {:?}", t.kind(), t.text()),
+        None => "nothing".to_string(),
+    }
+}
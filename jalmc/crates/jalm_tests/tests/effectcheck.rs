@@ -1,5 +1,5 @@
 use insta::assert_json_snapshot;
-use jalm_effectcheck::check;
+use jalm_effectcheck::{check, check_parsed, check_parsed_with_options, CheckOptions};
 
 #[test]
 fn effectcheck_ok() {
@@ -45,3 +45,162 @@ fn effectcheck_missing_net_http() {
 ]
 "###);
 }
+
+#[test]
+fn effectcheck_effect_in_closure() {
+    let src = "fn f() -> i64 {\n  let load = |path| fs::read(path);\n  1\n}";
+    let diags = check(src).diagnostics;
+    assert_json_snapshot!(diags, @r###"
+[
+  {
+    "code": "E1006",
+    "message": "effect used in closure not declared by enclosing function",
+    "span": {
+      "start": 36,
+      "end": 40
+    },
+    "required": "fs"
+  }
+]
+"###);
+    assert!(check("fn f() -> i64 !{fs} {\n  let load = |path| fs::read(path);\n  1\n}").diagnostics.is_empty());
+}
+
+#[test]
+fn effectcheck_effect_alias_expands() {
+    let src = "effect set io_net = {io, net};\neffect set all = {io_net, fs};\nfn f() -> i64 !{all} {\n  http::get(1);\n  fs::read(2);\n  1\n}";
+    assert!(check(src).diagnostics.is_empty());
+    let src = "effect set io_net = {io, net};\nfn f() -> i64 !{io_net} {\n  fs::read(2);\n  1\n}";
+    let codes: Vec<String> = check(src).diagnostics.into_iter().map(|d| d.code).collect();
+    assert_eq!(codes, ["E1001"]);
+}
+
+#[test]
+fn effectcheck_cyclic_effect_alias() {
+    let src = "effect set a = {io, b};\neffect set b = {a};\nfn f() -> i64 !{a} { 1 }";
+    let diags = check(src).diagnostics;
+    assert_json_snapshot!(diags, @r###"
+[
+  {
+    "code": "E1007",
+    "message": "cyclic effect alias",
+    "span": {
+      "start": 11,
+      "end": 12
+    },
+    "required": "a"
+  },
+  {
+    "code": "E1007",
+    "message": "cyclic effect alias",
+    "span": {
+      "start": 35,
+      "end": 36
+    },
+    "required": "b"
+  }
+]
+"###);
+}
+
+#[test]
+fn check_parsed_matches_check() {
+    let src = "fn f() -> i64 { net::get(1); fs::read(2) }";
+    let parsed = jalm_parser::parse(src);
+    let diags = check_parsed(&parsed).diagnostics;
+    assert_eq!(diags.len(), 2);
+    assert_eq!(diags, check(src).diagnostics);
+}
+
+#[test]
+fn effectcheck_method_call_requires_callee_effects() {
+    let src = "fn flush(b: i64) !{io} { log::info(b); }\nfn main() { buffer.flush(); }";
+    let diags = check(src).diagnostics;
+    assert_eq!(diags.len(), 1);
+    assert_eq!(diags[0].code, "E1001");
+    assert_eq!(diags[0].required, "io");
+    assert_eq!(&src[diags[0].span.start..diags[0].span.end], "flush");
+    assert!(check("fn flush(b: i64) !{io} { log::info(b); }\nfn main() !{io} { buffer.flush(); }").diagnostics.is_empty());
+}
+
+#[test]
+fn effectcheck_method_call_resolves_through_receiver_type() {
+    let decls = "struct A { x: i64; }\nstruct B { x: i64; }\nimpl A {\n  fn go(self) !{io} { log::info(self.x); }\n}\nimpl B {\n  fn go(self) { }\n}\n";
+    let src = format!("{decls}fn f(a: A) {{ a.go(); }}");
+    let diags = check(&src).diagnostics;
+    assert_eq!(diags.len(), 1);
+    assert_eq!((diags[0].code.as_str(), diags[0].required.as_str()), ("E1001", "io"));
+    assert_eq!(&src[diags[0].span.start..diags[0].span.end], "go");
+    assert!(check(&format!("{decls}fn f(b: B) {{ b.go(); }}")).diagnostics.is_empty());
+    assert!(check(&format!("{decls}fn f() {{ let b = B {{ x: 1 }}; b.go(); }}")).diagnostics.is_empty());
+    assert_eq!(check(&format!("{decls}fn f() {{ A::go(A {{ x: 1 }}); }}")).diagnostics.len(), 1);
+    assert!(check(&format!("{decls}fn f() {{ B::go(B {{ x: 1 }}); }}")).diagnostics.is_empty());
+}
+
+#[test]
+fn effectcheck_unknown_namespace_only_in_strict_mode() {
+    let src = "use std::math;\nenum Color { Red; }\nfn f() -> i64 {\n  let c = Color::Red;\n  foo::bar();\n  math::max(1, 2)\n}";
+    let parsed = jalm_parser::parse(src);
+    assert!(check_parsed(&parsed).diagnostics.is_empty());
+    let diags = check_parsed_with_options(&parsed, &CheckOptions { strict_effects: true }).diagnostics;
+    assert_json_snapshot!(diags, @r###"
+[
+  {
+    "code": "E1008",
+    "message": "unknown effect namespace",
+    "span": {
+      "start": 75,
+      "end": 78
+    },
+    "required": "foo"
+  }
+]
+"###);
+}
+
+#[test]
+fn effectcheck_await_in_async_fn_is_allowed() {
+    let src = "async fn fetch() -> i64 { 1 }\nasync fn load() -> i64 { await fetch() }";
+    assert!(check(src).diagnostics.is_empty());
+}
+
+#[test]
+fn effectcheck_await_in_sync_fn_requires_async() {
+    let src = "async fn fetch() -> i64 { 1 }\nfn load() -> i64 { await fetch() }";
+    let diags = check(src).diagnostics;
+    assert_json_snapshot!(diags, @r###"
+[
+  {
+    "code": "E1009",
+    "message": "await requires async context",
+    "span": {
+      "start": 49,
+      "end": 54
+    },
+    "required": "async"
+  }
+]
+"###);
+    // The type checker has no opinion: the capability is the effect checker's concern.
+    assert!(jalm_typecheck::check(src).diagnostics.is_empty());
+}
+
+#[test]
+fn effectcheck_sees_effects_through_path_calls() {
+    let src = "fn f() -> i64 { text::len(fs::read(1)) }";
+    let diags = check(src).diagnostics;
+    assert_eq!(diags.len(), 1);
+    assert_eq!(diags[0].required, "fs");
+    assert_eq!(&src[diags[0].span.start..diags[0].span.end], "fs::");
+}
+
+#[test]
+fn effectcheck_match_guard_calls_require_effects() {
+    let src = "fn log_check(n: i64) -> bool !{io} { log::info(n); true }\nfn f(x: i64) -> i64 {\n  match x {\n    n if log_check(n) => n,\n    _ => 0,\n  }\n}";
+    let diags = check(src).diagnostics;
+    assert_eq!(diags.len(), 1);
+    assert_eq!(diags[0].code, "E1001");
+    assert_eq!(diags[0].required, "io");
+    assert_eq!(&src[diags[0].span.start..diags[0].span.end], "log_check");
+    assert!(check(&src.replace("fn f(x: i64) -> i64 {", "fn f(x: i64) -> i64 !{io} {")).diagnostics.is_empty());
+}
@@ -1,6 +1,9 @@
-use jalm_tests::{diagnostics_json, round_trip};
+use jalm_tests::{diagnostics_json, round_trip, tree_diff, tree_eq_ignoring_trivia};
 use insta::{assert_json_snapshot, assert_snapshot};
-use jalm_formatter::format_source;
+use jalm_formatter::{format_range, format_source, format_source_with_options, FormatOptions};
+use jalm_ast::Item;
+use jalm_parser::{parse, parse_expr, parse_with_features};
+use jalm_syntax::{dump_tree, dump_tree_with_spans, SyntaxKind};
 
 #[test]
 fn round_trip_snapshot_basic() {
@@ -63,14 +66,14 @@ fn diagnostics_missing_tokens() {
 {
   "errors": [
     {
-      "message": "expected Semi",
+      "message": "expected `;`",
       "span": {
         "end": 20,
         "start": 19
       }
     },
     {
-      "message": "expected RBrace",
+      "message": "expected `}`",
       "span": {
         "end": 20,
         "start": 20
@@ -96,14 +99,14 @@ fn diagnostics_bad_tokens() {
       }
     },
     {
-      "message": "expected Semi",
+      "message": "expected `;`",
       "span": {
         "end": 20,
         "start": 19
       }
     },
     {
-      "message": "expected RBrace",
+      "message": "expected `}`",
       "span": {
         "end": 20,
         "start": 20
@@ -122,7 +125,11 @@ fn diagnostics_missing_rbrace() {
 {
   "errors": [
     {
-      "message": "expected RBrace",
+      "message": "expected `}`: unclosed `{` opened here",
+      "related": {
+        "end": 12,
+        "start": 11
+      },
       "span": {
         "end": 20,
         "start": 20
@@ -158,3 +165,817 @@ fn f(a: i64) -> i64 {
 }
 "###);
 }
+
+#[test]
+fn round_trip_restricted_visibility() {
+    let src = "pub(crate) fn f() -> i64 { 1 }\npub(super) struct S { x: i64; }\npub (super) fn g() {}\npub(crate) struct T { y: bool; }";
+    let (lossless, _tree) = round_trip(src);
+    assert_eq!(lossless, src);
+    assert!(diagnostics_json(src)["errors"].as_array().unwrap().is_empty());
+    let formatted = format_source(src).expect("format");
+    assert_snapshot!(formatted, @r###"
+pub(crate) fn f() -> i64 {
+  1
+}
+
+pub(super) struct S {
+  x: i64;
+}
+
+pub(super) fn g() {}
+
+pub(crate) struct T {
+  y: bool;
+}
+"###);
+}
+
+#[test]
+fn diagnostics_unknown_visibility_restriction() {
+    let src = "pub(world) fn f() {}";
+    let diags = diagnostics_json(src);
+    assert_json_snapshot!(diags, @r###"
+{
+  "errors": [
+    {
+      "message": "expected 'crate' or 'super' in visibility restriction",
+      "span": {
+        "end": 9,
+        "start": 4
+      }
+    }
+  ]
+}
+"###);
+}
+
+#[test]
+fn round_trip_boolean_literals() {
+    let src = "fn f(b: bool) -> bool { let t = true; if false { t } else { match b { true => false, false => t, } } }";
+    let (lossless, tree) = round_trip(src);
+    assert_eq!(lossless, src);
+    assert_eq!(tree.matches("KwTrue 'true'").count(), 2);
+    assert_eq!(tree.matches("KwFalse 'false'").count(), 3);
+    assert!(!tree.contains("Error"));
+    let formatted = format_source(src).expect("format");
+    assert_snapshot!(formatted, @r###"
+fn f(b: bool) -> bool {
+  let t = true;
+  if false {
+    t
+  } else {
+    match b {
+      true => false,
+      false => t,
+    }
+  }
+}
+"###);
+}
+
+#[test]
+fn round_trip_multiline_string_literal() {
+    let src = "fn f() -> string { \"first\nsecond\" }";
+    let (lossless, tree) = round_trip(src);
+    assert_eq!(lossless, src);
+    assert!(tree.contains("String '\"first\\nsecond\"'"));
+
+    let crlf = "fn f() -> string { \"first\r\nsecond\" }";
+    let (lossless, tree) = round_trip(crlf);
+    assert_eq!(lossless, crlf);
+    assert!(tree.contains("String '\"first\\r\\nsecond\"'"));
+    let formatted = format_source(crlf).expect("format");
+    assert_snapshot!(formatted, @r###"
+fn f() -> string {
+  "first
+second"
+}
+"###);
+}
+
+#[test]
+fn lex_raw_string_literals() {
+    let tokens = jalm_syntax::lex(r#"r"\n""#);
+    assert_eq!(tokens.len(), 1);
+    assert_eq!(tokens[0].kind, jalm_syntax::SyntaxKind::String);
+    assert_eq!(tokens[0].text, r#"r"\n""#);
+
+    let src = r###"r#"has "quotes""# r##"a "# b"##"###;
+    let kinds: Vec<_> = jalm_syntax::lex(src).into_iter().map(|t| (t.kind, t.text)).collect();
+    assert_eq!(
+        kinds,
+        vec![
+            (jalm_syntax::SyntaxKind::String, r###"r#"has "quotes""#"###.to_string()),
+            (jalm_syntax::SyntaxKind::Whitespace, " ".to_string()),
+            (jalm_syntax::SyntaxKind::String, r###"r##"a "# b"##"###.to_string()),
+        ]
+    );
+
+    let src = r###"fn f() -> string { r#"C:\path "x""# }"###;
+    let (lossless, _tree) = round_trip(src);
+    assert_eq!(lossless, src);
+    assert!(diagnostics_json(src)["errors"].as_array().unwrap().is_empty());
+}
+
+#[test]
+fn diagnostics_assignment_in_condition() {
+    let src = "fn f(x: i64) { if x = 5 { } }";
+    let diags = diagnostics_json(src);
+    assert_json_snapshot!(diags, @r###"
+{
+  "errors": [
+    {
+      "code": "E0033",
+      "message": "did you mean `==`? assignment is not allowed in a condition",
+      "span": {
+        "end": 21,
+        "start": 20
+      }
+    }
+  ]
+}
+"###);
+    let (lossless, _tree) = round_trip(src);
+    assert_eq!(lossless, src);
+}
+
+#[test]
+fn ast_root_items_in_order() {
+    let src = "mod app;\nuse std::io;\nstruct P { x: i64; }\nenum E { A; }\nfn main() {}\n";
+    let parsed = parse(src);
+    assert!(parsed.errors.is_empty());
+    let kinds: Vec<&str> = parsed
+        .ast()
+        .items()
+        .map(|item| match item {
+            Item::Module(_) => "module",
+            Item::Use(_) => "use",
+            Item::Struct(_) => "struct",
+            Item::Enum(_) => "enum",
+            Item::Fn(_) => "fn",
+            Item::EffectAlias(_) => "effect alias",
+            Item::Const(_) => "const",
+            Item::TypeAlias(_) => "type alias",
+            Item::Impl(_) => "impl",
+            Item::ModuleAttribute(_) => "module attribute",
+        })
+        .collect();
+    assert_eq!(kinds, ["module", "use", "struct", "enum", "fn"]);
+    let last = parsed.ast().items().last().unwrap();
+    assert_eq!(last.syntax().text().to_string(), "fn main() {}");
+}
+
+#[test]
+fn negative_literals_fold_in_patterns_and_exprs() {
+    let src = "fn f(x: i64) -> i64 {\n  match x {\n    -1 => 0,\n    _ => x * -2,\n  }\n}";
+    let (lossless, tree) = round_trip(src);
+    assert_eq!(lossless, src);
+    assert!(tree.contains("Pattern\n              LiteralNode\n                Minus '-'\n                Int '1'"));
+    assert_eq!(format_source(src).unwrap(), src);
+
+    let spaced = "fn f(x: i64) -> i64 {\n  match x {\n    - 1 => 0,\n    _ => x * - 2,\n  }\n}";
+    let (lossless, tree) = round_trip(spaced);
+    assert_eq!(lossless, spaced);
+    assert!(tree.contains("LiteralNode\n                Minus '-'\n                Whitespace ' '\n                Int '1'"));
+    assert_eq!(format_source(spaced).unwrap(), src);
+}
+
+#[test]
+fn formatter_formats_selected_range_only() {
+    let src = "fn a( x:i64 )->i64{x+1}\n\n// keep\nfn b( y:i64 )->i64{y*2}\n";
+    let start = src.find("y*2").unwrap();
+    let out = format_range(src, start..start + 3).unwrap();
+    assert_snapshot!(out, @r###"
+fn a( x:i64 )->i64{x+1}
+
+// keep
+fn b(y: i64) -> i64 {
+  y * 2
+}
+"###);
+    let first = src.find("x+1").unwrap();
+    let out = format_range(src, first..first).unwrap();
+    assert!(out.starts_with("fn a(x: i64) -> i64 {\n  x + 1\n}\n\n// keep\nfn b( y:i64 )->i64{y*2}\n"));
+}
+
+#[test]
+fn assignment_statements_round_trip_and_format() {
+    let src = "fn f(mut x: i64) -> i64 {\n  x = x + 1;\n  x *= 2;\n  x\n}";
+    let parsed = parse(src);
+    assert!(parsed.errors.is_empty());
+    let (lossless, tree) = round_trip(src);
+    assert_eq!(lossless, src);
+    assert_eq!(tree.matches("AssignExpr").count(), 2);
+    assert_eq!(format_source("fn f(mut x: i64) -> i64 { x=x+1; x*=2; x }").unwrap(), src);
+}
+
+#[test]
+fn attributes_round_trip_and_format() {
+    let src = "@export( \"foo\" )\nfn a() -> i64 { 1 }\n\n@extern(\"env\",\"log\") fn log(x: i64);\n";
+    let parsed = parse(src);
+    assert!(parsed.errors.is_empty());
+    let (lossless, tree) = round_trip(src);
+    assert_eq!(lossless, src);
+    assert_eq!(tree.matches("Attribute").count(), 2);
+    assert_snapshot!(format_source(src).unwrap(), @r###"
+@export("foo")
+fn a() -> i64 {
+  1
+}
+
+@extern("env", "log")
+fn log(x: i64);
+"###);
+}
+
+#[test]
+fn return_without_semicolon_before_rbrace() {
+    let src = "fn f() -> i64 { return 1 }";
+    assert_json_snapshot!(diagnostics_json(src), @r###"
+{
+  "errors": []
+}
+"###);
+    let (lossless, _tree) = round_trip(src);
+    assert_eq!(lossless, src);
+    assert_eq!(format_source(src).unwrap(), "fn f() -> i64 {\n  return 1;\n}");
+    assert!(parse("fn f() { return }").errors.is_empty());
+}
+
+#[test]
+fn diagnostics_unclosed_nested_block_points_at_its_brace() {
+    let src = "fn f() {\n  if true {\n    let x = 1;\n}";
+    let parsed = parse(src);
+    assert_eq!(parsed.errors.len(), 1);
+    let related = parsed.errors[0].related.as_ref().expect("opening delimiter span");
+    assert_eq!(related.start, src.find('{').unwrap());
+    assert_eq!(parsed.errors[0].message, "expected `}`: unclosed `{` opened here");
+}
+
+#[test]
+fn dump_tree_with_spans_matches_node_ranges() {
+    let src = "fn a() {}";
+    let root = parse(src).syntax();
+    let dump = dump_tree_with_spans(&root);
+    assert_snapshot!(dump, @r###"
+Root @0..9
+  FnDecl @0..9
+    KwFn 'fn' @0..2
+    Whitespace ' ' @2..3
+    IdentNode @3..4
+      Ident 'a' @3..4
+    LParen '(' @4..5
+    ParamList @5..6
+      RParen ')' @5..6
+    Whitespace ' ' @6..7
+    Block @7..9
+      LBrace '{' @7..8
+      StmtList @8..8
+      RBrace '}' @8..9
+"###);
+    let ident = root.descendants().find(|n| n.kind() == SyntaxKind::IdentNode).unwrap();
+    assert_eq!(&src[ident.text_range()], "a");
+    assert_eq!(dump_tree(&root), round_trip(src).1);
+}
+
+#[test]
+fn diagnostics_unterminated_block_comment() {
+    let src = "fn f() {}\n/* never closed\nfn g() { x + }";
+    let diags = diagnostics_json(src);
+    assert_json_snapshot!(diags, @r###"
+{
+  "errors": [
+    {
+      "code": "E0034",
+      "message": "unterminated block comment",
+      "span": {
+        "end": 12,
+        "start": 10
+      }
+    }
+  ]
+}
+"###);
+    let (lossless, tree) = round_trip(src);
+    assert_eq!(lossless, src);
+    assert!(!tree.contains("ErrorToken"));
+    assert!(tree.contains("Comment '/* never closed\\nfn g() { x + }'"));
+    assert!(parse("/* a */ fn f() {} /**/").errors.is_empty());
+}
+
+#[test]
+fn default_parameter_values_round_trip() {
+    let src = "fn f(x: i64, y: i64 = 1 + 2) -> i64 {\n  x + y\n}";
+    let parsed = parse(src);
+    assert!(parsed.errors.is_empty());
+    let (lossless, tree) = round_trip(src);
+    assert_eq!(lossless, src);
+    assert!(tree.contains("Param\n        IdentNode\n          Ident 'y'"));
+    assert_eq!(format_source("fn f(x:i64, y:i64=1+2)->i64{x+y}").unwrap(), src);
+}
+
+#[test]
+fn tuple_destructuring_let_round_trip() {
+    let src = "fn f() -> i64 {\n  let (a, b): (i64, bool) = (1, true);\n  a\n}";
+    let parsed = parse(src);
+    assert!(parsed.errors.is_empty());
+    let (lossless, tree) = round_trip(src);
+    assert_eq!(lossless, src);
+    assert!(tree.contains("TupleExpr"));
+    assert!(tree.contains("Pattern\n            LParen '('\n            Pattern\n              IdentNode\n                Ident 'a'"));
+    assert_eq!(format_source("fn f() -> i64 {\n  let (a,b):(i64,bool)=(1,true);\n  a\n}").unwrap(), src);
+    assert_eq!(format_source("fn f() {\n  let (x,) = (1 ,);\n}").unwrap(), "fn f() {\n  let (x,) = (1,);\n}");
+}
+
+#[test]
+fn block_level_use_round_trip() {
+    let src = "fn f() -> i64 {\n  use std::math::max as m;\n  let x = 1;\n  x\n}";
+    let parsed = parse(src);
+    assert!(parsed.errors.is_empty());
+    let (lossless, tree) = round_trip(src);
+    assert_eq!(lossless, src);
+    assert!(tree.contains("\n        UseDecl\n"));
+    assert_eq!(format_source("fn f() -> i64 {\n use std::math::max as m;\nlet x = 1; x }").unwrap(), src);
+}
+
+#[test]
+fn closure_expr_round_trip() {
+    let src = "fn f() {\n  let add = |a, b: i64| a + b;\n  let load = || fs::read(p);\n}";
+    let parsed = parse(src);
+    assert!(parsed.errors.is_empty());
+    let (lossless, tree) = round_trip(src);
+    assert_eq!(lossless, src);
+    assert!(tree.contains("ClosureExpr\n            ParamList\n              Pipe '|'"));
+    assert_eq!(format_source("fn f() {\n  let add = |a,b:i64|a+b;\n  let load = ||fs::read(p);\n}").unwrap(), src);
+}
+
+#[test]
+fn format_error_exposes_parse_errors() {
+    let err = format_source("fn f() -> i64 { 1 + }").unwrap_err();
+    let spans: Vec<(usize, usize)> = err.errors().iter().map(|e| (e.span.start, e.span.end)).collect();
+    assert_eq!(spans, [(20, 21), (21, 21)]);
+    assert_eq!(
+        err.to_string(),
+        "cannot format source with 2 parse error(s)\n  20..21: expected expression\n  21..21: expected `}`"
+    );
+    let _: &dyn std::error::Error = &err;
+}
+
+#[test]
+fn effect_alias_round_trip() {
+    let src = "effect set io_net = {io, net};\n\nfn f() !{io_net} {}";
+    let parsed = parse(src);
+    assert!(parsed.errors.is_empty());
+    let (lossless, tree) = round_trip(src);
+    assert_eq!(lossless, src);
+    assert!(tree.contains("EffectAliasDecl\n    Ident 'effect'"));
+    assert_eq!(format_source("effect set io_net={io,net};\nfn f() !{io_net} {}").unwrap(), src);
+}
+
+#[test]
+fn grouped_use_round_trip() {
+    let src = "use std::math::{max as m, min};\n\nfn f() -> i64 {\n  m(1, min(2, 3))\n}";
+    let parsed = parse(src);
+    assert!(parsed.errors.is_empty());
+    let (lossless, tree) = round_trip(src);
+    assert_eq!(lossless, src);
+    assert!(tree.contains("UseGroupItem\n          IdentNode\n            Ident 'max'"));
+    assert_eq!(format_source("use std::math::{ max  as m ,min, };\nfn f() -> i64 { m(1, min(2, 3)) }").unwrap(), src);
+}
+
+#[test]
+fn if_let_round_trip() {
+    let src = "fn f(opt: i64) -> i64 {\n  if let Some(x) = opt {\n    x\n  } else if let (a, b) = pair {\n    a + b\n  } else {\n    0\n  }\n}";
+    let parsed = parse(src);
+    assert!(parsed.errors.is_empty());
+    let (lossless, tree) = round_trip(src);
+    assert_eq!(lossless, src);
+    assert!(tree.contains("IfLetExpr\n            KwIf 'if'"));
+    assert_eq!(
+        format_source("fn f(opt: i64) -> i64 { if let Some( x )=opt { x } else if let (a,b) = pair { a+b } else { 0 } }").unwrap(),
+        src
+    );
+}
+
+#[test]
+fn numeric_literal_surface_round_trip() {
+    let src = "fn f() -> i64 {\n  let h: i64 = 0xFF;\n  let o: i64 = 0o17;\n  let b: i64 = 0b1010;\n  let big: f64 = 1e10;\n  let small: f64 = 1.5e-3;\n  h + o + b\n}";
+    let (lossless, tree) = round_trip(src);
+    assert_eq!(lossless, src);
+    for int in ["0xFF", "0o17", "0b1010"] {
+        assert!(tree.contains(&format!("Int '{int}'")), "{int} lexes as one Int");
+    }
+    for float in ["1e10", "1.5e-3"] {
+        assert!(tree.contains(&format!("Float '{float}'")), "{float} lexes as one Float");
+    }
+    let formatted = format_source(src).expect("format");
+    assert_eq!(formatted, src);
+    assert_eq!(format_source(&formatted).expect("reformat"), src);
+    assert!(jalm_typecheck::check(src).diagnostics.is_empty());
+    let diags = jalm_typecheck::check("fn f() -> i64 {\n  let n: i64 = 1e10;\n  n\n}").diagnostics;
+    assert_eq!(diags[0].code, "E0003");
+    assert_eq!(diags[0].actual.as_deref(), Some("f64"));
+}
+
+#[test]
+fn struct_literal_with_spread_round_trip() {
+    let src = "fn f(base: Point) -> Point {\n  let p = Point { x: 1, ..base };\n  if p == (Point { x: 1, y: 2 }) {\n    return Point {};\n  }\n  p\n}";
+    let parsed = parse(src);
+    assert!(parsed.errors.is_empty());
+    let (lossless, tree) = round_trip(src);
+    assert_eq!(lossless, src);
+    assert!(tree.contains("StructLitSpread\n              Range '..'"));
+    assert_eq!(
+        format_source("fn f(base: Point) -> Point {\n  let p = Point{x:1,..base};\n  if p == (Point{x:1,y:2}) { return Point{}; }\n  p\n}").unwrap(),
+        src
+    );
+    // In a condition `{` opens the body, so a bare struct literal is not parsed.
+    let tree = dump_tree(&parse("fn f() { if p == Point { x } }").syntax());
+    assert!(!tree.contains("StructLit"));
+}
+
+#[test]
+fn ast_leading_and_trailing_trivia() {
+    let src = "// adds two numbers\nfn add() {} // done\nfn next() {}";
+    let parsed = parse(src);
+    let items: Vec<_> = parsed.ast().items().collect();
+    let texts = |tokens: Vec<jalm_syntax::SyntaxToken>| tokens.iter().map(|t| t.text().to_string()).collect::<Vec<_>>();
+    assert_eq!(texts(jalm_ast::leading_trivia(items[0].syntax())), ["// adds two numbers", "\n"]);
+    assert_eq!(texts(jalm_ast::trailing_trivia(items[0].syntax())), [" ", "// done", "\n"]);
+    assert_eq!(jalm_ast::leading_trivia(items[0].syntax())[0].kind(), SyntaxKind::Comment);
+    assert!(jalm_ast::trailing_trivia(items[1].syntax()).is_empty());
+}
+
+#[test]
+fn ast_use_bound_names() {
+    let parsed = parse("use std::io;\nuse std::math::max as m;\nuse std::fs::{read, write as put};");
+    let names: Vec<Vec<String>> = parsed
+        .ast()
+        .items()
+        .map(|item| match item {
+            Item::Use(import) => import.bound_names().iter().map(|n| n.text().to_string()).collect(),
+            _ => Vec::new(),
+        })
+        .collect();
+    assert_eq!(names, [vec!["io"], vec!["m"], vec!["read", "put"]]);
+}
+
+#[test]
+fn module_attribute_round_trip() {
+    let src = "@no_host;\n\n@export(\"run\")\nfn main() {}";
+    let parsed = parse(src);
+    assert!(parsed.errors.is_empty());
+    let (lossless, tree) = round_trip(src);
+    assert_eq!(lossless, src);
+    assert!(tree.contains("ModuleAttribute\n    Attribute\n      At '@'"));
+    assert_eq!(format_source("@no_host ;\n@export(\"run\") fn main() {}").unwrap(), src);
+}
+
+#[test]
+fn shebang_line_round_trip() {
+    let src = "#!/usr/bin/env jalmt run\nfn main() -> i64 {\n  0\n}";
+    let parsed = parse(src);
+    assert!(parsed.errors.is_empty());
+    let (lossless, tree) = round_trip(src);
+    assert_eq!(lossless, src);
+    assert!(tree.starts_with("Root\n  Shebang '#!/usr/bin/env jalmt run'\n"));
+    assert_eq!(format_source(src).unwrap(), src);
+    let crlf = src.replace('\n', "\r\n");
+    let (lossless, tree) = round_trip(&crlf);
+    assert_eq!(lossless, crlf);
+    assert!(tree.starts_with("Root\n  Shebang '#!/usr/bin/env jalmt run'\n  Whitespace '\\r\\n'\n"));
+    assert_eq!(format_source(&crlf).unwrap(), src);
+    // Only the very first line may be a shebang.
+    assert!(!parse("fn main() {}\n#!/bin/sh\n").errors.is_empty());
+}
+
+#[test]
+fn labeled_block_and_break_round_trip() {
+    let src = "fn f(c: bool) -> i64 {\n  let x = 'outer: {\n    if c {\n      break 'outer 1;\n    }\n    2\n  };\n  'done: {\n    break 'done;\n  }\n  x\n}";
+    assert!(parse(src).errors.is_empty());
+    let (lossless, tree) = round_trip(src);
+    assert_eq!(lossless, src);
+    assert!(tree.contains("LabeledBlock\n"));
+    assert!(tree.contains("Label ''outer'"));
+    assert!(tree.contains("BreakStmt\n"));
+    assert_eq!(format_source(src).unwrap(), src);
+}
+
+#[test]
+fn field_and_variant_doc_comments_round_trip() {
+    let src = "struct User {\n  /// the user id\n  id: i64;\n  // not documentation\n  /// display name\n  ///   second line\n  name: string;\n}\n\nenum Shape {\n  /// a circle\n  Circle(f64);\n  Empty;\n}";
+    let parsed = parse(src);
+    assert!(parsed.errors.is_empty());
+    let (lossless, tree) = round_trip(src);
+    assert_eq!(lossless, src);
+    assert!(tree.contains("StructField\n      DocComment '/// the user id'"));
+    let fields: Vec<_> = parsed.syntax().descendants().filter(|n| n.kind() == SyntaxKind::StructField).collect();
+    assert_eq!(jalm_ast::doc_comment(&fields[0]).as_deref(), Some("the user id"));
+    assert_eq!(jalm_ast::doc_comment(&fields[1]).as_deref(), Some("display name\n  second line"));
+    let variants: Vec<_> = parsed.syntax().descendants().filter(|n| n.kind() == SyntaxKind::EnumVariant).collect();
+    assert_eq!(jalm_ast::doc_comment(&variants[0]).as_deref(), Some("a circle"));
+    assert_eq!(jalm_ast::doc_comment(&variants[1]), None);
+}
+
+#[test]
+fn format_keeps_field_doc_comments_above_their_field() {
+    let src = "struct User {\n      /// the user id\n id: i64;\n/// display name\n    name: string;\n}";
+    assert_eq!(
+        format_source(src).unwrap(),
+        "struct User {\n  /// the user id\n  id: i64;\n  /// display name\n  name: string;\n}"
+    );
+}
+
+#[test]
+fn trailing_commas_round_trip() {
+    let src = "effect set io = {fs, net,};\nenum Shape {\n  Rect(f64, f64,);\n}\n@extern(\"env\", \"log\",)\nfn log(v: i64,) !{io,};\nfn f() {\n  let t = (1, 2,);\n  let g = |a, b,| a;\n  let p = Point { x: 1, ..base, };\n  log(1,);\n}";
+    let parsed = parse(src);
+    assert!(parsed.errors.is_empty(), "{:?}", parsed.errors);
+    let (lossless, _) = round_trip(src);
+    assert_eq!(lossless, src);
+    let formatted = format_source(src).unwrap();
+    assert!(formatted.contains("Rect(f64, f64);"));
+    assert!(formatted.contains("!{io}"));
+    // A spread still has to be the last entry.
+    assert!(!parse("fn f() { Point { ..base, x: 1 } }").errors.is_empty());
+}
+
+#[test]
+fn parse_reports_is_ok_and_error_count() {
+    let parsed = parse("fn main() -> i64 { let x = 1 x }");
+    assert!(!parsed.is_ok());
+    assert_eq!(parsed.error_count(), parsed.errors().len());
+    assert!(parsed.error_count() > 0);
+    assert!(parse("fn main() -> i64 { let x = 1; x }").is_ok());
+}
+
+#[test]
+fn let_else_round_trip() {
+    let src = "fn f(opt: Option) -> i64 {\n  let Some(x) = opt else {\n    return 0;\n  };\n  x\n}";
+    assert!(parse(src).is_ok());
+    let (lossless, tree) = round_trip(src);
+    assert_eq!(lossless, src);
+    assert!(tree.contains("KwElse 'else'"));
+    assert_eq!(format_source(src).unwrap(), src);
+    assert_eq!(format_source("fn f(opt: Option) -> i64 { let Some(x) = opt else { return 0; }; x }").unwrap(), src);
+}
+
+#[test]
+fn comma_separated_struct_fields_round_trip() {
+    let formatted = "struct S {\n  x: i64;\n  y: bool;\n}";
+    for src in ["struct S { x: i64, y: bool }", "struct S { x: i64, y: bool, }", "struct S { x: i64; y: bool }", formatted] {
+        let parsed = parse(src);
+        assert!(parsed.is_ok(), "{src}: {:?}", parsed.errors());
+        let (lossless, tree) = round_trip(src);
+        assert_eq!(lossless, src);
+        assert_eq!(tree.matches("StructField\n").count(), 2);
+        assert_eq!(format_source(src).unwrap(), formatted);
+    }
+    assert!(!parse("struct S { x: i64 y: bool }").is_ok());
+}
+
+#[test]
+fn match_guard_round_trip() {
+    let src = "fn f(n: i64) -> i64 {\n  match n {\n    x if x > 0 => x,\n    _ => 0,\n  }\n}";
+    assert!(parse(src).is_ok());
+    let (lossless, tree) = round_trip(src);
+    assert_eq!(lossless, src);
+    assert!(tree.contains("MatchGuard\n"));
+    assert_eq!(format_source(src).unwrap(), src);
+    assert_eq!(format_source("fn f(n: i64) -> i64 { match n { x   if x>0=>x, _=>0, } }").unwrap(), src);
+}
+
+#[test]
+fn cfg_attribute_round_trip() {
+    let src = "@cfg(feature = \"extra\")\n@export(\"extra\")\nfn extra() -> i64 {\n  2\n}";
+    let parsed = parse(src);
+    assert!(parsed.is_ok());
+    let (lossless, tree) = round_trip(src);
+    assert_eq!(lossless, src);
+    assert!(tree.contains("AttrKeyValue\n"));
+    assert_eq!(format_source(src).unwrap(), src);
+    let item = parsed.syntax().first_child().unwrap();
+    assert_eq!(jalm_ast::cfg_feature(&item).as_deref(), Some("extra"));
+}
+
+#[test]
+fn cfg_gates_every_item_kind() {
+    let src = "@cfg(feature = \"extra\")\nstruct Extra {\n  x: i64;\n}\n\n@cfg(feature = \"extra\")\nimpl Extra {\n  fn get(self) -> i64 {\n    self.x\n  }\n}\n\n@cfg(feature = \"extra\")\nconst LIMIT: i64 = 3;\n\nfn main() -> i64 {\n  0\n}";
+    let parsed = parse(src);
+    assert!(parsed.is_ok());
+    assert_eq!(round_trip(src).0, src);
+    assert_eq!(format_source(src).unwrap(), src);
+    let kinds = |parsed: &jalm_parser::Parse| parsed.syntax().children().map(|n| n.kind()).collect::<Vec<_>>();
+    assert_eq!(kinds(&parsed), [SyntaxKind::StructDecl, SyntaxKind::ImplBlock, SyntaxKind::ConstDecl, SyntaxKind::FnDecl]);
+    assert!(jalm_typecheck::check_parsed(&parsed).diagnostics.is_empty());
+
+    // Disabled items stay in the text as trivia, so spans after them are unchanged.
+    let gated = parse_with_features(src, &[]);
+    assert_eq!(gated.syntax().text().to_string(), src);
+    assert_eq!(kinds(&gated), [SyntaxKind::FnDecl]);
+    assert_eq!(gated.syntax().children_with_tokens().filter(|t| t.kind() == SyntaxKind::CfgDisabled).count(), 3);
+    let enabled = parse_with_features(src, &["extra".to_string()]);
+    assert_eq!(kinds(&enabled), kinds(&parsed));
+}
+
+#[test]
+fn array_type_round_trip() {
+    let src = "fn f(xs: [i64]) -> i64 {\n  let a: [i64; 3] = [1, 2, 3];\n  0\n}";
+    assert!(parse(src).is_ok());
+    let (lossless, tree) = round_trip(src);
+    assert_eq!(lossless, src);
+    assert_eq!(tree.matches("ArrayType\n").count(), 2);
+    assert!(tree.contains("ArrayExpr\n"));
+    assert_eq!(format_source(src).unwrap(), src);
+    assert_eq!(format_source("fn f(xs: [ i64 ]) -> i64 { let a: [i64;3] = [1,2,3,]; 0 }").unwrap(), src);
+}
+
+#[test]
+fn formatter_drops_redundant_parens() {
+    let src = "fn f(a: i64, b: i64, c: i64) -> i64 {\n  let x = ((a + b));\n  let y = (a + b) * c;\n  let z = a - (b - c);\n  let w = (a < b) == (b < c);\n  let v = (a < b) < c;\n  return (x);\n}";
+    let formatted = "fn f(a: i64, b: i64, c: i64) -> i64 {\n  let x = a + b;\n  let y = (a + b) * c;\n  let z = a - (b - c);\n  let w = (a < b) == (b < c);\n  let v = (a < b) < c;\n  return x;\n}";
+    assert_eq!(format_source(src).unwrap(), formatted);
+    assert_eq!(format_source(formatted).unwrap(), formatted);
+    // A struct literal in a condition keeps its parentheses.
+    let cond = "fn f(p: P) -> i64 {\n  if (p == P { x: 1 }) {\n    1\n  } else {\n    0\n  }\n}";
+    assert_eq!(format_source(cond).unwrap(), cond);
+}
+
+#[test]
+fn parse_expr_accepts_a_single_expression() {
+    let parsed = parse_expr("x + 3");
+    assert!(parsed.is_ok());
+    assert!(dump_tree(&parsed.syntax()).contains("BinExpr\n"));
+    assert!(!parse_expr("let x = 2;").is_ok());
+    assert!(!parse_expr("x + 3 y").is_ok());
+}
+
+#[test]
+fn const_decl_round_trip() {
+    let src = "pub const N: i64 = 4;\n\nfn f() -> i64 {\n  let a: [i64; N * 2] = [1, 2, 3, 4, 5, 6, 7, 8];\n  N\n}";
+    let parsed = parse(src);
+    assert!(parsed.is_ok());
+    let (lossless, tree) = round_trip(src);
+    assert_eq!(lossless, src);
+    assert!(tree.contains("ConstDecl\n"));
+    assert!(matches!(parsed.ast().items().next(), Some(Item::Const(_))));
+    assert_eq!(format_source(src).unwrap(), src);
+    assert_eq!(format_source("pub const N:i64=4;\nfn f() -> i64 { let a: [i64;N*2] = [1,2,3,4,5,6,7,8]; N }").unwrap(), src);
+}
+
+#[test]
+fn await_expr_round_trip() {
+    let src = "async fn f() -> i64 {\n  let a = await fetch(1).value;\n  await (a) + 1\n}";
+    let (lossless, tree) = round_trip(src);
+    assert_eq!(lossless, src);
+    assert_eq!(tree.matches("AwaitExpr\n").count(), 2);
+    assert_eq!(format_source(src).unwrap(), "async fn f() -> i64 {\n  let a = await fetch(1).value;\n  await a + 1\n}");
+}
+
+#[test]
+fn path_expr_round_trip() {
+    let src = "fn f(x: i64) -> i64 {\n  let c = a::b;\n  math::sqrt(x) + a::b::c()\n}";
+    let (lossless, tree) = round_trip(src);
+    assert_eq!(lossless, src);
+    assert_eq!(tree.matches("PathExpr\n").count(), 3);
+    assert_eq!(tree.matches("CallExpr\n").count(), 2);
+    assert_eq!(format_source(src).unwrap(), src);
+    assert_eq!(format_source("fn f(x: i64) -> i64 {\n  let c = a :: b;\n  math:: sqrt(x) + a::b ::c()\n}").unwrap(), src);
+}
+
+#[test]
+fn struct_literal_in_condition_needs_parentheses() {
+    let src = "fn f() {\n  if Point { x: 1 } {}\n}";
+    assert_json_snapshot!(diagnostics_json(src), @r###"
+{
+  "errors": [
+    {
+      "code": "E0043",
+      "message": "ambiguous struct literal in condition; wrap in parentheses",
+      "span": {
+        "end": 21,
+        "start": 20
+      }
+    }
+  ]
+}
+"###);
+    let src = "fn f() {\n  if (Point { x: 1 }).valid {}\n}";
+    assert!(parse(src).is_ok());
+    let (lossless, tree) = round_trip(src);
+    assert_eq!(lossless, src);
+    assert!(tree.contains("StructLit\n"));
+}
+
+#[test]
+fn impl_block_round_trip() {
+    let src = "impl Point {\n  fn norm(self) -> i64 {\n    0\n  }\n\n  pub fn scaled(mut self, k: i64) -> Point {\n    self\n  }\n}";
+    assert!(parse(src).is_ok());
+    let (lossless, tree) = round_trip(src);
+    assert_eq!(lossless, src);
+    assert!(tree.contains("ImplBlock\n    KwImpl 'impl'"));
+    assert!(tree.contains("Param\n          IdentNode\n            Ident 'self'\n        RParen ')'"));
+    let Some(Item::Impl(block)) = parse(src).ast().items().next() else {
+        panic!("expected an impl block");
+    };
+    assert_eq!(block.methods().count(), 2);
+    assert_eq!(format_source("impl Point{fn norm(self)->i64{0}\npub fn scaled(mut self,k:i64)->Point{self}}").unwrap(), src);
+}
+
+#[test]
+fn pub_on_local_use_is_rejected() {
+    let src = "fn f() -> i64 {\n  pub use math::abs;\n  abs(-1)\n}";
+    assert_json_snapshot!(diagnostics_json(src), @r###"
+{
+  "errors": [
+    {
+      "code": "E0047",
+      "message": "`pub` not allowed on a local item",
+      "span": {
+        "end": 21,
+        "start": 18
+      }
+    }
+  ]
+}
+"###);
+    let (lossless, tree) = round_trip(src);
+    assert_eq!(lossless, src);
+    assert!(tree.contains("UseDecl\n          Visibility\n            KwPub 'pub'"));
+}
+
+#[test]
+fn formatter_compact_blocks_keep_short_bodies_inline() {
+    let options = FormatOptions { compact_blocks: true, ..FormatOptions::default() };
+    let src = "fn f() -> i64 {\n  1\n}\n\nfn g(c: bool) -> i64 {\n  let x = 1;\n  if c { x } else { 0 }\n}";
+    let compact = "fn f() -> i64 { 1 }\n\nfn g(c: bool) -> i64 {\n  let x = 1;\n  if c { x } else { 0 }\n}";
+    assert_eq!(format_source_with_options(src, &options).unwrap(), compact);
+    assert_eq!(format_source_with_options(compact, &options).unwrap(), compact);
+    // Off by default, and a line past `max_width` still breaks.
+    assert_eq!(format_source(compact).unwrap(), format_source(src).unwrap());
+    let narrow = FormatOptions { compact_blocks: true, max_width: 18 };
+    assert_eq!(format_source_with_options("fn f() -> i64 { 1 }", &narrow).unwrap(), "fn f() -> i64 {\n  1\n}");
+}
+
+#[test]
+fn enum_discriminant_round_trip() {
+    let src = "enum Color {\n  Red = 1;\n  Green;\n  Blue = -1;\n}";
+    assert!(parse(src).is_ok());
+    let (lossless, tree) = round_trip(src);
+    assert_eq!(lossless, src);
+    assert_eq!(tree.matches("LiteralNode\n").count(), 2);
+    assert_eq!(format_source(src).unwrap(), src);
+    assert_eq!(format_source("enum Color { Red=1; Green; Blue = -1 ; }").unwrap(), src);
+    assert!(!parse("enum E { A(i64) = 1; }").is_ok());
+}
+
+#[test]
+fn type_alias_round_trip() {
+    let src = "type Id = i64;\n\npub type Pair = (Id, bool);\n\nfn f(x: Id) -> Id {\n  x\n}";
+    assert!(parse(src).is_ok());
+    let (lossless, tree) = round_trip(src);
+    assert_eq!(lossless, src);
+    assert!(tree.contains("TypeAlias\n    KwType 'type'"));
+    assert!(matches!(parse(src).ast().items().next(), Some(Item::TypeAlias(_))));
+    assert_eq!(format_source("type Id=i64;\npub type Pair=(Id,bool);\nfn f(x: Id) -> Id { x }").unwrap(), src);
+}
+
+#[test]
+fn tree_comparison_ignores_trivia() {
+    let a = parse("fn f()->i64{a+b}").syntax();
+    let b = parse("fn f() -> i64 {\n  // sum\n  a + b\n}").syntax();
+    assert!(tree_eq_ignoring_trivia(&a, &b));
+    assert_eq!(tree_diff(&a, &b), None);
+
+    let c = parse("fn f() -> i64 {\n  a - b\n}").syntax();
+    assert!(!tree_eq_ignoring_trivia(&a, &c));
+    assert_eq!(tree_diff(&a, &c).unwrap(), "Root > FnDecl > Block > StmtList > BinExpr: token Plus \"+\" vs token Minus \"-\"");
+
+    let d = parse("fn f() -> i64 {\n  (a + b)\n}").syntax();
+    assert_eq!(tree_diff(&a, &d).unwrap(), "Root > FnDecl > Block > StmtList: node BinExpr vs node ParenExpr");
+}
+
+#[test]
+fn expected_token_errors_name_the_token() {
+    let first_error = |src: &str| parse(src).errors[0].message.clone();
+    assert_eq!(first_error("fn f(a: i64 -> i64 {}"), "expected `)`: unclosed `(` opened here");
+    assert_eq!(first_error("const N: i64 3;"), "expected `=`");
+    assert_eq!(first_error("fn f(x: i64) -> i64 {\n  match x { 1 2, }\n}"), "expected `=>`");
+    assert_eq!(first_error("struct P { x i64; }"), "expected `:`");
+}
+
+#[test]
+fn generic_call_round_trip() {
+    let src = "fn id<T>(x: T) -> T {\n  x\n}\n\nfn f() -> i64 {\n  id::<i64>(1) + m::pick::<i64, bool>(2, true)\n}";
+    assert!(parse(src).is_ok());
+    let (lossless, tree) = round_trip(src);
+    assert_eq!(lossless, src);
+    assert!(tree.contains("GenericParamList\n      Lt '<'"));
+    assert_eq!(tree.matches("TypeArgList\n").count(), 2);
+    assert_eq!(format_source("fn id<T>(x:T)->T{x}\nfn f()->i64{id :: < i64 > (1)+m::pick::<i64,bool,>(2,true)}").unwrap(), src);
+
+    // An unclosed parameter list names the `>` it is missing.
+    let parsed = parse("fn id<T, U(x: T) -> T {\n  x\n}");
+    assert_eq!(parsed.errors[0].message, "expected `>`");
+    assert_eq!((parsed.errors[0].span.start, parsed.errors[0].span.end), (10, 11));
+}
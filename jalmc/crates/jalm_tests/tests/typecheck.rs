@@ -1,5 +1,6 @@
 use insta::assert_json_snapshot;
-use jalm_typecheck::check;
+use jalm_typecheck::{check, check_parsed, check_with};
+use std::ops::ControlFlow;
 
 #[test]
 fn typecheck_ok() {
@@ -47,3 +48,1086 @@ fn typecheck_undefined_var() {
 ]
 "###);
 }
+
+#[test]
+fn typecheck_division_by_literal_zero() {
+    let src = "fn f(x: i64) -> i64 { let a = 1 / 0; x % 0 }";
+    let diags = check(src).diagnostics;
+    assert_json_snapshot!(diags, @r###"
+[
+  {
+    "code": "E0032",
+    "message": "division by zero",
+    "span": {
+      "start": 34,
+      "end": 35
+    },
+    "expected": null,
+    "actual": null
+  },
+  {
+    "code": "E0032",
+    "message": "division by zero",
+    "span": {
+      "start": 41,
+      "end": 42
+    },
+    "expected": null,
+    "actual": null
+  }
+]
+"###);
+}
+
+#[test]
+fn typecheck_nonzero_divisor_ok() {
+    let src = "fn f(x: i64) -> i64 { x / 2 % 3 }";
+    let diags = check(src).diagnostics;
+    assert!(diags.is_empty());
+}
+
+#[test]
+fn typecheck_body_ending_in_return_ok() {
+    let src = "fn main() -> i64 {\n  return 0;\n}\n";
+    let diags = check(src).diagnostics;
+    assert!(diags.is_empty());
+}
+
+#[test]
+fn typecheck_boolean_literals() {
+    let src = "fn f(b: bool) -> bool { let t = true; if false { t } else { match b { true => false, false => t, } } }";
+    assert!(check(src).diagnostics.is_empty());
+
+    let src = "fn g() -> i64 { true }";
+    let diags = check(src).diagnostics;
+    assert_json_snapshot!(diags, @r###"
+[
+  {
+    "code": "E0004",
+    "message": "type mismatch",
+    "span": {
+      "start": 14,
+      "end": 22
+    },
+    "expected": "i64",
+    "actual": "bool"
+  }
+]
+"###);
+}
+
+#[test]
+fn typecheck_call_arguments_are_checked() {
+    let src = "fn f() -> i64 { foo(x) }";
+    let diags = check(src).diagnostics;
+    assert_json_snapshot!(diags, @r###"
+[
+  {
+    "code": "E0001",
+    "message": "undefined variable",
+    "span": {
+      "start": 20,
+      "end": 21
+    },
+    "expected": null,
+    "actual": "x"
+  }
+]
+"###);
+}
+
+#[test]
+fn typecheck_negative_literals() {
+    let src = "fn f(x: i64) -> f64 { let a: i64 = -1; let b: f64 = -2.5; match x { -1 => b, _ => b } }";
+    let diags = check(src).diagnostics;
+    assert!(diags.is_empty(), "{diags:?}");
+
+    // The folded literal keeps its type: `-1` is not a `bool`, and `-2.5` is not an `i64`.
+    let src = "fn f(x: bool) -> i64 {\n  let a: bool = -1;\n  let b: i64 = -2.5;\n  match x {\n    -1 => 0,\n    _ => 1,\n  }\n}";
+    let diags = check(src).diagnostics;
+    assert_json_snapshot!(diags, @r###"
+[
+  {
+    "code": "E0003",
+    "message": "type mismatch",
+    "span": {
+      "start": 25,
+      "end": 42
+    },
+    "expected": "bool",
+    "actual": "i64"
+  },
+  {
+    "code": "E0003",
+    "message": "type mismatch",
+    "span": {
+      "start": 45,
+      "end": 63
+    },
+    "expected": "i64",
+    "actual": "f64"
+  },
+  {
+    "code": "E0037",
+    "message": "pattern type mismatch",
+    "span": {
+      "start": 80,
+      "end": 82
+    },
+    "expected": "bool",
+    "actual": "i64"
+  }
+]
+"###);
+}
+
+#[test]
+fn typecheck_needless_mut_param() {
+    let src = "fn f(mut x: i64) -> i64 { return x; }";
+    let result = check(src);
+    assert!(!result.has_errors());
+    assert_json_snapshot!(result.diagnostics, @r###"
+[
+  {
+    "code": "W0004",
+    "message": "parameter does not need to be mutable",
+    "span": {
+      "start": 5,
+      "end": 15
+    },
+    "expected": null,
+    "actual": "x"
+  }
+]
+"###);
+}
+
+#[test]
+fn typecheck_assigned_mut_has_no_warning() {
+    let src = "fn f(mut x: i64) -> i64 { let mut y = 1; x = x + 1; y += x; return y; }";
+    let diags = check(src).diagnostics;
+    assert!(diags.is_empty(), "{diags:?}");
+}
+
+#[test]
+fn typecheck_needless_let_mut() {
+    let src = "fn f() -> i64 { let mut y = 1; return y; }";
+    let diags = check(src).diagnostics;
+    assert_eq!(diags.len(), 1);
+    assert_eq!(diags[0].code, "W0004");
+    assert_eq!(diags[0].message, "variable does not need to be mutable");
+    assert_eq!(&src[diags[0].span.start..diags[0].span.end], "y");
+}
+
+#[test]
+fn typecheck_wide_integer_arithmetic() {
+    let src = "fn f(a: i128, b: i128, c: u128) -> bool { let s: i128 = a + b * a; let t = c % c; s < a && t == c }";
+    let diags = check(src).diagnostics;
+    assert!(diags.is_empty(), "{diags:?}");
+}
+
+#[test]
+fn typecheck_wide_integers_do_not_mix() {
+    let src = "fn f(a: i128, b: u128) -> i128 { a + b }";
+    let diags = check(src).diagnostics;
+    assert_eq!(diags.len(), 1);
+    assert_eq!(diags[0].code, "E0003");
+    assert_eq!(diags[0].actual.as_deref(), Some("u128"));
+}
+
+#[test]
+fn typecheck_default_parameter_type() {
+    assert!(check("fn f(a: i64, b: i64 = a * 2) -> i64 { a + b }").diagnostics.is_empty());
+    let diags = check("fn f(flag: bool = 1) -> bool { flag }").diagnostics;
+    assert_eq!(diags.len(), 1);
+    assert_eq!(diags[0].code, "E0003");
+    assert_eq!(diags[0].expected.as_deref(), Some("bool"));
+    assert_eq!(diags[0].actual.as_deref(), Some("i64"));
+}
+
+#[test]
+fn typecheck_diagnostics_are_in_source_order() {
+    // The line 1 warning is only found when the function scope closes,
+    // after the errors on lines 2 and 3 have been reported.
+    let src = "fn f(mut a: i64) -> bool {\n  let b: bool = 1;\n  missing\n}";
+    let diags = check(src).diagnostics;
+    let codes: Vec<&str> = diags.iter().map(|d| d.code.as_str()).collect();
+    assert_eq!(codes, ["W0004", "E0003", "E0001"]);
+    assert!(diags.windows(2).all(|w| w[0].span.start <= w[1].span.start));
+}
+
+#[test]
+fn typecheck_invalid_byte_escape() {
+    assert!(check(r#"fn f() -> bytes { b"\x00\\\"\n" }"#).diagnostics.is_empty());
+    let src = r#"fn f() -> bytes { b"ok\xZZ" }"#;
+    let diags = check(src).diagnostics;
+    assert_json_snapshot!(diags, @r###"
+[
+  {
+    "code": "E0049",
+    "message": "invalid byte escape",
+    "span": {
+      "start": 22,
+      "end": 24
+    },
+    "expected": null,
+    "actual": "\\x"
+  }
+]
+"###);
+}
+
+#[test]
+fn typecheck_tuple_destructuring() {
+    let src = "fn f() -> bool {\n  let (a, b) = (1, true);\n  let c: i64 = a;\n  b\n}";
+    assert!(check(src).diagnostics.is_empty());
+    let src = "fn f() -> i64 {\n  let (a, b) = (1, 2, 3);\n  a\n}";
+    let diags = check(src).diagnostics;
+    assert_json_snapshot!(diags, @r###"
+[
+  {
+    "code": "E0035",
+    "message": "tuple arity mismatch",
+    "span": {
+      "start": 22,
+      "end": 28
+    },
+    "expected": "3",
+    "actual": "2"
+  }
+]
+"###);
+}
+
+#[test]
+fn typecheck_block_level_use_is_scoped_to_block() {
+    assert!(check("fn f() -> i64 {\n  use std::math::max;\n  max\n}").diagnostics.is_empty());
+    let src = "fn f() -> i64 {\n  {\n    use std::math::max;\n    max(1, 2);\n  }\n  max\n}";
+    let diags = check(src).diagnostics;
+    assert_eq!(diags.len(), 1);
+    assert_eq!(diags[0].code, "E0001");
+    assert_eq!(diags[0].actual.as_deref(), Some("max"));
+}
+
+#[test]
+fn typecheck_let_shadowing_parameter() {
+    let src = "fn f(x: i64) -> i64 {\n  let x = x + 1;\n  x\n}";
+    let diags = check(src).diagnostics;
+    assert_json_snapshot!(diags, @r###"
+[
+  {
+    "code": "W0005",
+    "message": "local binding shadows parameter",
+    "span": {
+      "start": 28,
+      "end": 29
+    },
+    "expected": null,
+    "actual": "x"
+  }
+]
+"###);
+    let repeated = check("fn f(x: i64) -> i64 {\n  let x = x + 1;\n  let x = x * 2;\n  x\n}").diagnostics;
+    assert_eq!(repeated.iter().map(|d| (d.code.as_str(), d.span.start)).collect::<Vec<_>>(), [("W0005", 28)]);
+    assert!(check("fn f(x: i64) -> i64 {\n  let y = 1;\n  let y = y + x;\n  y\n}").diagnostics.is_empty());
+    assert!(check("fn f(x: i64) -> i64 {\n  {\n    let x = 2;\n  }\n  x\n}").diagnostics.is_empty());
+}
+
+#[test]
+fn typecheck_integer_literals_take_expected_type() {
+    assert!(check("fn f() -> i32 {\n  let x: i32 = 1;\n  x\n}").diagnostics.is_empty());
+    assert!(check("fn f(y: i32) -> i32 {\n  let x: i32 = y + 1;\n  let z: i32 = 2 * y;\n  z + x\n}").diagnostics.is_empty());
+    // Unconstrained literals still default to `i64`.
+    let diags = check("fn f(y: i32) -> i32 {\n  let one = 1;\n  y + one\n}").diagnostics;
+    assert_eq!(diags.len(), 1);
+    assert_eq!(diags[0].code, "E0003");
+    assert_eq!(diags[0].expected.as_deref(), Some("i32"));
+    assert_eq!(diags[0].actual.as_deref(), Some("i64"));
+    let diags = check("fn f() -> bool { 1 }").diagnostics;
+    assert_eq!(diags[0].code, "E0004");
+}
+
+#[test]
+fn typecheck_if_let_scopes_bindings_to_then_branch() {
+    assert!(check("fn f(opt: i64) -> i64 {\n  if let Some(x) = opt { x } else { 0 }\n}").diagnostics.is_empty());
+    assert!(check("fn f() -> i64 {\n  if let (a, b) = (1, 2) { a + b } else { 0 }\n}").diagnostics.is_empty());
+    let diags = check("fn f(opt: i64) -> i64 {\n  if let Some(x) = opt { 1 } else { 0 };\n  x\n}").diagnostics;
+    assert_eq!(diags.len(), 1);
+    assert_eq!(diags[0].code, "E0001");
+    assert_eq!(diags[0].actual.as_deref(), Some("x"));
+    // The pattern is checked against the scrutinee's type.
+    let diags = check("fn f() -> i64 {\n  if let (a, b) = (1, 2, 3) { a } else { 0 }\n}").diagnostics;
+    assert!(diags.iter().any(|d| d.code == "E0035"));
+    let diags = check("fn f(n: i64) -> i64 {\n  if let (a, b) = n { a } else { 0 }\n}").diagnostics;
+    assert!(diags.iter().any(|d| d.code == "E0003" && d.actual.as_deref() == Some("i64")));
+}
+
+#[test]
+fn check_parsed_matches_check() {
+    let src = "fn f(x: i64) -> bool {\n  let y: bool = x;\n  z\n}";
+    let parsed = jalm_parser::parse(src);
+    let diags = check_parsed(&parsed).diagnostics;
+    assert!(!diags.is_empty());
+    assert_eq!(diags, check(src).diagnostics);
+}
+
+#[test]
+fn typecheck_unused_import() {
+    let src = "use std::math::max as m;\nuse std::io;\n\nfn f() -> i64 {\n  io::print(1);\n  1\n}";
+    let diags = check(src).diagnostics;
+    assert_json_snapshot!(diags, @r###"
+[
+  {
+    "code": "W0006",
+    "message": "unused import",
+    "span": {
+      "start": 22,
+      "end": 23
+    },
+    "expected": null,
+    "actual": "m"
+  }
+]
+"###);
+    assert!(check("fn f() -> i64 {\n  m(1, 2)\n}\n\nuse std::math::max as m;").diagnostics.is_empty());
+    assert!(check("fn f() -> i64 {\n  use std::math::max;\n  max\n}").diagnostics.is_empty());
+}
+
+#[test]
+fn typecheck_grouped_use_binds_aliases_and_names() {
+    let src = "use std::math::{max as m, min, abs};\n\nfn f() -> i64 {\n  m(1, min(2, 3))\n}";
+    let diags = check(src).diagnostics;
+    assert_json_snapshot!(diags, @r###"
+[
+  {
+    "code": "W0006",
+    "message": "unused import",
+    "span": {
+      "start": 31,
+      "end": 34
+    },
+    "expected": null,
+    "actual": "abs"
+  }
+]
+"###);
+    assert!(check("use std::math::{max as m, min};\n\nfn f() -> i64 {\n  m(1, min(2, 3));\n  max\n}").has_errors());
+}
+
+#[test]
+fn typecheck_chained_comparison() {
+    let src = "fn f(a: i64, b: i64, c: i64) -> bool {\n  a < b < c\n}";
+    let diags = check(src).diagnostics;
+    assert_json_snapshot!(diags, @r###"
+[
+  {
+    "code": "E0036",
+    "message": "chained comparison is not allowed; use `&&`",
+    "span": {
+      "start": 41,
+      "end": 51
+    },
+    "expected": null,
+    "actual": null
+  }
+]
+"###);
+    assert!(check("fn f(a: i64, b: i64, c: i64) -> bool {\n  a < b && b < c\n}").diagnostics.is_empty());
+}
+
+#[test]
+fn typecheck_struct_literal_spread() {
+    let decl = "struct Point { x: i64; y: i64; }\n";
+    assert!(check(&format!("{decl}fn f(base: Point) -> Point {{\n  Point {{ x: 1, ..base }}\n}}")).diagnostics.is_empty());
+    let src = format!("{decl}fn f() -> Point {{\n  Point {{ x: 1, ..2 }}\n}}");
+    let diags = check(&src).diagnostics;
+    assert_json_snapshot!(diags, @r###"
+[
+  {
+    "code": "E0003",
+    "message": "type mismatch",
+    "span": {
+      "start": 69,
+      "end": 70
+    },
+    "expected": "Point",
+    "actual": "i64"
+  }
+]
+"###);
+    let diags = check(&format!("{decl}fn f() -> Point {{\n  Point {{ x: 1 }}\n}}")).diagnostics;
+    assert_eq!(diags.len(), 1);
+    assert_eq!(diags[0].code, "E0045");
+    assert_eq!(diags[0].actual.as_deref(), Some("y"));
+}
+
+#[test]
+fn typecheck_struct_literal_missing_fields() {
+    let decl = "struct Point { x: i64; y: i64; z: i64; }\n";
+    let src = format!("{decl}fn f() -> Point {{\n  Point {{ x: 1 }}\n}}");
+    let diags = check(&src).diagnostics;
+    assert_json_snapshot!(diags, @r###"
+[
+  {
+    "code": "E0045",
+    "message": "missing fields `y`, `z` in initializer of `Point`",
+    "span": {
+      "start": 61,
+      "end": 75
+    },
+    "expected": null,
+    "actual": "y, z"
+  }
+]
+"###);
+    assert!(check(&format!("{decl}fn f() -> Point {{\n  Point {{ x: 1, y: 2, z: 3 }}\n}}")).diagnostics.is_empty());
+    assert!(check(&format!("{decl}fn f(p: Point) -> Point {{\n  Point {{ x: 1, ..p }}\n}}")).diagnostics.is_empty());
+}
+
+#[test]
+fn typecheck_struct_literal_unknown_field() {
+    let src = "struct Point { x: i64; y: i64; }\nfn f() -> Point {\n  Point { x: 1, y: 2, w: 3 }\n}";
+    let diags = check(src).diagnostics;
+    assert_json_snapshot!(diags, @r###"
+[
+  {
+    "code": "E0056",
+    "message": "no field `w` on struct `Point`",
+    "span": {
+      "start": 73,
+      "end": 74
+    },
+    "expected": null,
+    "actual": "w"
+  }
+]
+"###);
+}
+
+#[test]
+fn typecheck_match_pattern_type() {
+    let src = "fn f(n: i64) -> i64 {\n  match n { \"hi\" => 1, _ => 0, }\n}";
+    let diags = check(src).diagnostics;
+    assert_json_snapshot!(diags, @r###"
+[
+  {
+    "code": "E0037",
+    "message": "pattern type mismatch",
+    "span": {
+      "start": 34,
+      "end": 38
+    },
+    "expected": "i64",
+    "actual": "string"
+  }
+]
+"###);
+    assert!(check("fn f(n: i64) -> i64 {\n  match n { 1 => 10, -2 => 20, _ => 0, }\n}").diagnostics.is_empty());
+    assert!(check("fn f(b: bool) -> i64 {\n  match b { true => 1, false => 0, }\n}").diagnostics.is_empty());
+}
+
+#[test]
+fn typecheck_value_returned_from_unit_function() {
+    let diags = check("fn f() { return 5; }").diagnostics;
+    assert_json_snapshot!(diags, @r###"
+[
+  {
+    "code": "E0038",
+    "message": "returning a value from a unit function",
+    "span": {
+      "start": 16,
+      "end": 17
+    },
+    "expected": "()",
+    "actual": "i64"
+  }
+]
+"###);
+    assert!(check("fn f() { return; }").diagnostics.is_empty());
+    assert!(check("fn g() {}\nfn f() { return g(); }").diagnostics.is_empty());
+    let diverging = "@extern(\"env\", \"jalm_panic\") fn abort(code: i64) -> never;\nfn f() { return abort(1); }";
+    assert!(check(diverging).diagnostics.is_empty());
+}
+
+#[test]
+fn typecheck_call_has_declared_return_type() {
+    let diags = check("fn g() -> bool {\n  true\n}\n\nfn f() -> i64 {\n  g()\n}").diagnostics;
+    assert_eq!(diags.len(), 1);
+    assert_eq!((diags[0].code.as_str(), diags[0].actual.as_deref()), ("E0004", Some("bool")));
+    // A local closure of the same name shadows the function.
+    assert!(check("fn g() -> bool {\n  true\n}\n\nfn f() -> i64 {\n  let g = |x: i64| x;\n  g(1)\n}").diagnostics.is_empty());
+    let parsed = jalm_parser::parse("fn g() -> bool {\n  true\n}\n\nfn main() {\n  g()\n}");
+    assert_eq!(jalm_typecheck::body_type(&parsed, "main").as_deref(), Some("bool"));
+}
+
+#[test]
+fn typecheck_never_branch_unifies_with_i64() {
+    let prelude = "@extern(\"env\", \"jalm_panic\") fn abort(code: i64) -> never;\nfn die() { abort(2); }\n";
+    let ok = format!("{prelude}fn f(c: bool) {{ let x = if c {{ 1 }} else {{ die() }}; let y: i64 = x; }}");
+    assert!(check(&ok).diagnostics.is_empty());
+    let arms = format!("{prelude}fn g(n: i64) -> i64 {{ match n {{ 0 => abort(1), _ => n, }} }}");
+    assert!(check(&arms).diagnostics.is_empty());
+    let bad = format!("{prelude}fn h(c: bool) {{ let x = if c {{ 1 }} else {{ die() }}; let z: bool = x; }}");
+    let diags = check(&bad).diagnostics;
+    assert_eq!(diags.len(), 1);
+    assert_eq!(diags[0].code, "E0003");
+    assert_eq!(diags[0].expected.as_deref(), Some("bool"));
+    assert_eq!(diags[0].actual.as_deref(), Some("i64"));
+}
+
+#[test]
+fn typecheck_never_function_must_diverge() {
+    assert!(check("fn f() -> never { panic(1) }").diagnostics.is_empty());
+    let diags = check("fn f() -> never { 1 }").diagnostics;
+    assert_eq!(diags.len(), 1);
+    assert_eq!(diags[0].code, "E0004");
+    assert_eq!(diags[0].expected.as_deref(), Some("never"));
+    assert_eq!(diags[0].actual.as_deref(), Some("i64"));
+}
+
+#[test]
+fn typecheck_panic_satisfies_any_type() {
+    assert!(check("fn f() -> bool { panic(1) }").diagnostics.is_empty());
+    assert!(check("fn f(c: bool) { let s: string = if c { \"ok\" } else { panic() }; }").diagnostics.is_empty());
+}
+
+#[test]
+fn typecheck_assert_requires_bool() {
+    assert!(check("fn f(n: i64) { assert(n == 1); }").diagnostics.is_empty());
+    let diags = check("fn f(n: i64) { assert(n); }").diagnostics;
+    assert_eq!(diags.len(), 1);
+    assert_eq!(diags[0].code, "E0003");
+    assert_eq!(diags[0].expected.as_deref(), Some("bool"));
+    assert_eq!(diags[0].actual.as_deref(), Some("i64"));
+    // `assert` returns unit, so it cannot stand in for a value.
+    assert!(check("fn f() { let x: i64 = assert(true); }").diagnostics.iter().any(|d| d.code == "E0003"));
+}
+
+#[test]
+fn typecheck_labeled_break() {
+    let src = "fn f(c: bool) -> i64 {\n  let x = 'outer: {\n    if c { break 'outer 1; }\n    2\n  };\n  x\n}";
+    assert!(check(src).diagnostics.is_empty());
+    let mismatch = "fn f(c: bool) -> i64 {\n  'outer: {\n    if c { break 'outer true; }\n    2\n  }\n}";
+    assert!(check(mismatch).diagnostics.iter().any(|d| d.code == "E0003"));
+}
+
+#[test]
+fn typecheck_unknown_label() {
+    let diags = check("fn f() {\n  'done: { break 'missing; }\n}").diagnostics;
+    assert_json_snapshot!(diags, @r###"
+[
+  {
+    "code": "E0039",
+    "message": "unknown label",
+    "span": {
+      "start": 26,
+      "end": 34
+    },
+    "expected": null,
+    "actual": "'missing"
+  }
+]
+"###);
+}
+
+#[test]
+fn typecheck_break_outside_loop() {
+    assert!(check("fn f() {\n  'done: { break; }\n}").diagnostics.is_empty());
+    let diags = check("fn f() {\n  break;\n}").diagnostics;
+    assert_json_snapshot!(diags, @r###"
+[
+  {
+    "code": "E0055",
+    "message": "break outside loop",
+    "span": {
+      "start": 11,
+      "end": 17
+    },
+    "expected": null,
+    "actual": null
+  }
+]
+"###);
+}
+
+#[test]
+fn typecheck_use_before_definition() {
+    let diags = check("fn f() {\n  let x = 1;\n  let y = z;\n  let z = 2;\n}").diagnostics;
+    assert_json_snapshot!(diags, @r###"
+[
+  {
+    "code": "E0040",
+    "message": "use before definition",
+    "span": {
+      "start": 32,
+      "end": 33
+    },
+    "expected": null,
+    "actual": "z"
+  }
+]
+"###);
+    // A name bound only by its own initializer is still simply undefined.
+    assert_eq!(check("fn f() {\n  let x = x;\n}").diagnostics[0].code, "E0001");
+    // Functions may be called before they are defined.
+    assert!(check("fn a() -> i64 { b() }\nfn b() -> i64 { 1 }").diagnostics.is_empty());
+}
+
+#[test]
+fn check_with_streams_check_diagnostics_in_batches() {
+    let src = "fn a() -> bool { 1 }\nfn b(x: i64) {\n  let y: bool = x;\n  let z = w;\n}\nfn c() { return 5; }";
+    let mut streamed = Vec::new();
+    check_with(src, &mut |diag| {
+        streamed.push(diag);
+        ControlFlow::Continue(())
+    });
+    let expected = check(src).diagnostics;
+    assert_eq!(expected.len(), 4);
+    assert_eq!(streamed, expected);
+
+    let mut first = Vec::new();
+    check_with(src, &mut |diag| {
+        first.push(diag.code);
+        ControlFlow::Break(())
+    });
+    assert_eq!(first, ["E0004"]);
+
+    // An unused import is streamed in a last batch after the functions; `check` sorts it first.
+    let src = "use std::io;\nfn a() -> bool { 1 }";
+    let mut streamed = Vec::new();
+    check_with(src, &mut |diag| {
+        streamed.push(diag);
+        ControlFlow::Continue(())
+    });
+    let codes: Vec<&str> = streamed.iter().map(|d| d.code.as_str()).collect();
+    assert_eq!(codes, ["E0004", "W0006"]);
+    let expected = check(src).diagnostics;
+    let codes: Vec<&str> = expected.iter().map(|d| d.code.as_str()).collect();
+    assert_eq!(codes, ["W0006", "E0004"]);
+    streamed.sort_by(|a, b| (a.span.start, &a.code).cmp(&(b.span.start, &b.code)));
+    assert_eq!(streamed, expected);
+}
+
+#[test]
+fn typecheck_let_else_must_diverge() {
+    let ok = "enum Option { Some(i64); None; }\nfn f(opt: Option) -> i64 {\n  let Some(x) = opt else { return 0; };\n  let Some(y) = opt else { panic(); };\n  x\n}";
+    assert!(check(ok).diagnostics.is_empty());
+    let diags = check("fn f(opt: Option) -> i64 {\n  let Some(x) = opt else { 1 };\n  x\n}").diagnostics;
+    assert_json_snapshot!(diags, @r###"
+[
+  {
+    "code": "E0050",
+    "message": "`let ... else` block does not diverge",
+    "span": {
+      "start": 52,
+      "end": 57
+    },
+    "expected": "never",
+    "actual": "i64"
+  }
+]
+"###);
+}
+
+#[test]
+fn typecheck_returning_branch_unifies_with_value_branch() {
+    let src = "fn f(c: bool) -> i64 {\n  let x = if c { return 0; } else { 1 };\n  let y = match c { true => { return 2; }, _ => 3, };\n  x + y\n}";
+    assert!(check(src).diagnostics.is_empty());
+    let diags = check("fn f(c: bool) -> i64 {\n  let x: bool = if c { return 0; } else { 1 };\n  0\n}").diagnostics;
+    assert_eq!(diags.len(), 1);
+    assert_eq!((diags[0].code.as_str(), diags[0].expected.as_deref(), diags[0].actual.as_deref()), ("E0003", Some("bool"), Some("i64")));
+}
+
+#[test]
+fn typecheck_match_guard_sees_bindings_and_must_be_bool() {
+    assert!(check("fn f(n: i64) -> i64 {\n  match n { x if x > 0 => x, _ => 0, }\n}").diagnostics.is_empty());
+    let diags = check("fn f(n: i64) -> i64 {\n  match n { x if x => 1, _ => 0, }\n}").diagnostics;
+    assert_json_snapshot!(diags, @r###"
+[
+  {
+    "code": "E0005",
+    "message": "type mismatch",
+    "span": {
+      "start": 39,
+      "end": 40
+    },
+    "expected": "bool",
+    "actual": "i64"
+  }
+]
+"###);
+}
+
+#[test]
+fn typecheck_explicit_call_to_main() {
+    let diags = check("fn main() -> i64 {\n  0\n}\n\nfn retry() -> i64 {\n  main()\n}").diagnostics;
+    assert_json_snapshot!(diags, @r###"
+[
+  {
+    "code": "W0007",
+    "message": "explicit call to `main`",
+    "span": {
+      "start": 48,
+      "end": 54
+    },
+    "expected": null,
+    "actual": null
+  }
+]
+"###);
+    assert!(!check("fn main() -> i64 {\n  main()\n}").has_errors());
+    assert!(check("fn helper() -> i64 {\n  1\n}\n\nfn main() -> i64 {\n  helper()\n}").diagnostics.is_empty());
+}
+
+#[test]
+fn typecheck_array_length_mismatch() {
+    assert!(check("fn main() -> i64 {\n  let a: [i64; 3] = [1, 2, 3];\n  0\n}").diagnostics.is_empty());
+    let diags = check("fn main() -> i64 {\n  let a: [i64; 2] = [1, 2, 3];\n  0\n}").diagnostics;
+    assert_json_snapshot!(diags, @r###"
+[
+  {
+    "code": "E0041",
+    "message": "array length mismatch",
+    "span": {
+      "start": 21,
+      "end": 49
+    },
+    "expected": "[i64; 2]",
+    "actual": "[i64; 3]"
+  }
+]
+"###);
+}
+
+#[test]
+fn typecheck_array_size_from_const() {
+    assert!(check("const N: i64 = 3;\n\nfn main() -> i64 {\n  let a: [i64; N] = [1, 2, 3];\n  N\n}").diagnostics.is_empty());
+    let diags = check("fn main() -> i64 {\n  let n = 3;\n  let a: [i64; n] = [1, 2, 3];\n  n\n}").diagnostics;
+    assert_json_snapshot!(diags, @r###"
+[
+  {
+    "code": "E0042",
+    "message": "array size must be a constant",
+    "span": {
+      "start": 47,
+      "end": 48
+    },
+    "expected": null,
+    "actual": "n"
+  }
+]
+"###);
+    // A `const` whose initializer calls a function has no compile-time value to size an array with.
+    let src = "fn three() -> i64 {\n  3\n}\n\nconst N: i64 = three();\n\nfn main() -> i64 {\n  let a: [i64; N] = [1, 2, 3];\n  0\n}";
+    let diags = check(src).diagnostics;
+    assert_json_snapshot!(diags, @r###"
+[
+  {
+    "code": "E0042",
+    "message": "array size must be a constant",
+    "span": {
+      "start": 86,
+      "end": 87
+    },
+    "expected": null,
+    "actual": "N"
+  }
+]
+"###);
+}
+
+#[test]
+fn typecheck_widens_i32_and_f32_but_does_not_narrow() {
+    let src = "fn f(a: i32, b: f32) -> f64 {\n  let x: i64 = a;\n  let mut y: i64 = 0;\n  y = a;\n  b\n}";
+    assert!(check(src).diagnostics.is_empty());
+    let diags = check("fn f(a: i64) -> i32 {\n  let x: i32 = a;\n  x\n}").diagnostics;
+    assert_json_snapshot!(diags, @r###"
+[
+  {
+    "code": "E0003",
+    "message": "type mismatch",
+    "span": {
+      "start": 24,
+      "end": 39
+    },
+    "expected": "i32",
+    "actual": "i64"
+  }
+]
+"###);
+    // Compound assignment is arithmetic, so its operands must already agree.
+    let diags = check("fn f(a: i32) {\n  let mut y: i64 = 0;\n  y += a;\n}").diagnostics;
+    assert_eq!(diags.iter().map(|d| d.code.as_str()).collect::<Vec<_>>(), ["E0003"]);
+}
+
+#[test]
+fn typecheck_duplicate_enum_discriminant() {
+    assert!(check("enum Color {\n  Red = 1;\n  Green;\n  Blue = 4;\n}").diagnostics.is_empty());
+    // `Green` takes 2 implicitly, so `Blue = 2` collides with it.
+    let diags = check("enum Color {\n  Red = 1;\n  Green;\n  Blue = 2;\n}").diagnostics;
+    assert_json_snapshot!(diags, @r###"
+[
+  {
+    "code": "E0044",
+    "message": "duplicate discriminant",
+    "span": {
+      "start": 42,
+      "end": 43
+    },
+    "expected": null,
+    "actual": "2"
+  }
+]
+"###);
+}
+
+#[test]
+fn typecheck_enum_discriminant_out_of_range() {
+    let diags = check("enum Color {\n  Red = 3000000000;\n  Green;\n}").diagnostics;
+    assert_eq!(diags.len(), 2);
+    assert!(diags.iter().all(|d| d.code == "E0052" && d.expected.as_deref() == Some("i32")));
+    assert_eq!(diags[0].actual.as_deref(), Some("3000000000"));
+}
+
+#[test]
+fn typecheck_fieldless_variants_have_their_enum_type() {
+    let src = "enum Color {\n  Red;\n  Green;\n}\n\nfn f() -> Color {\n  Color::Red\n}\n\nfn g() -> bool {\n  Color::Red == Color::Green\n}";
+    assert!(check(src).diagnostics.is_empty());
+    let diags = check("enum Color {\n  Red;\n}\n\nfn f() -> bool {\n  Color::Red\n}").diagnostics;
+    assert_eq!(diags.len(), 1);
+    assert_eq!((diags[0].code.as_str(), diags[0].actual.as_deref()), ("E0004", Some("Color")));
+}
+
+#[test]
+fn typecheck_type_aliases_resolve_transitively() {
+    let src = "type Id = i64;\ntype Key = Id;\n\nfn f(k: Key) -> bool {\n  let n: i64 = k;\n  n\n}";
+    let diags = check(src).diagnostics;
+    assert_eq!(diags.len(), 1);
+    assert_eq!((diags[0].code.as_str(), diags[0].actual.as_deref()), ("E0004", Some("i64")));
+    // `C` only refers to the cycle, so it is not reported itself.
+    let diags = check("type A = B;\ntype B = (A, i64);\ntype C = A;").diagnostics;
+    assert_json_snapshot!(diags, @r###"
+[
+  {
+    "code": "E0046",
+    "message": "cyclic type alias",
+    "span": {
+      "start": 5,
+      "end": 6
+    },
+    "expected": null,
+    "actual": "A"
+  },
+  {
+    "code": "E0046",
+    "message": "cyclic type alias",
+    "span": {
+      "start": 17,
+      "end": 18
+    },
+    "expected": null,
+    "actual": "B"
+  }
+]
+"###);
+}
+
+#[test]
+fn typecheck_deprecated_function_call_warns() {
+    let src = "@deprecated(\"use g instead\")\nfn f() -> i64 {\n  1\n}\n\nfn g() -> i64 {\n  2\n}\n\nfn h() -> i64 {\n  let a = f();\n  g()\n}";
+    let diags = check(src).diagnostics;
+    assert_json_snapshot!(diags, @r###"
+[
+  {
+    "code": "W0008",
+    "message": "use of deprecated function `f`: use g instead",
+    "span": {
+      "start": 101,
+      "end": 102
+    },
+    "expected": null,
+    "actual": "f"
+  }
+]
+"###);
+    // A local named `f` shadows the deprecated function.
+    assert!(check("@deprecated\nfn f() -> i64 {\n  1\n}\n\nfn h() -> i64 {\n  let f = |x| x;\n  f(1)\n}").diagnostics.is_empty());
+}
+
+#[test]
+fn typecheck_return_in_closure_targets_the_closure() {
+    let src = "fn f(x: i64) -> i64 {\n  let positive = |n: i64| {\n    if n > 0 {\n      return true;\n    }\n    false\n  };\n  x\n}";
+    assert!(check(src).diagnostics.is_empty());
+    // The body's final value must agree with what the closure returns.
+    let src = "fn f(x: i64) -> i64 {\n  let positive = |n: i64| {\n    if n > 0 {\n      return true;\n    }\n    n\n  };\n  x\n}";
+    let diags = check(src).diagnostics;
+    assert_json_snapshot!(diags, @r###"
+[
+  {
+    "code": "E0004",
+    "message": "type mismatch",
+    "span": {
+      "start": 48,
+      "end": 99
+    },
+    "expected": "bool",
+    "actual": "i64"
+  }
+]
+"###);
+}
+
+#[test]
+fn typecheck_instantiated_generic_call() {
+    let src = "fn pair<A, B>(a: A, b: B) -> (A, B) {\n  (a, b)\n}\n\nfn f() {\n  let p: (i64, bool) = pair::<i64, bool>(1, true);\n  let q: (i64, bool) = pair::<bool, i64>(false, 2);\n  let r = pair::<i64, bool>(1, 2);\n}";
+    let diags = check(src).diagnostics;
+    assert_json_snapshot!(diags, @r###"
+[
+  {
+    "code": "E0003",
+    "message": "type mismatch",
+    "span": {
+      "start": 112,
+      "end": 161
+    },
+    "expected": "(i64, bool)",
+    "actual": "(bool, i64)"
+  },
+  {
+    "code": "E0003",
+    "message": "type mismatch",
+    "span": {
+      "start": 193,
+      "end": 194
+    },
+    "expected": "bool",
+    "actual": "i64"
+  }
+]
+"###);
+    let src = "fn id<T>(x: T) -> T {\n  x\n}\n\nfn k(x: i64) -> i64 {\n  x\n}\n\nfn f() {\n  id::<i64, bool>(1);\n  k::<i64>(1);\n  id::<i64>();\n}";
+    let diags = check(src).diagnostics;
+    assert_json_snapshot!(diags, @r###"
+[
+  {
+    "code": "E0053",
+    "message": "`id` expects 1 type arguments, found 2",
+    "span": {
+      "start": 71,
+      "end": 84
+    },
+    "expected": "1",
+    "actual": "2"
+  },
+  {
+    "code": "E0054",
+    "message": "`k` is not generic but is given type arguments",
+    "span": {
+      "start": 92,
+      "end": 99
+    },
+    "expected": "0",
+    "actual": "1"
+  },
+  {
+    "code": "E0051",
+    "message": "`id` expects 1 arguments, found 0",
+    "span": {
+      "start": 106,
+      "end": 117
+    },
+    "expected": "1",
+    "actual": "0"
+  }
+]
+"###);
+}
+
+#[test]
+fn typecheck_method_calls_resolve_through_impl() {
+    let src = "struct Point {\n  x: i64;\n}\n\nimpl Point {\n  fn distance(self, other: Point) -> i64 {\n    0\n  }\n}\n\nfn f(p: Point, q: Point) {\n  let d: i64 = p.distance(q);\n  let e: bool = p.distance(q);\n  let g: i64 = p.distance(1);\n  let h: i64 = p.distance();\n  let i: i64 = p.distance(q, 1);\n}";
+    let diags = check(src).diagnostics;
+    assert_json_snapshot!(diags, @r###"
+[
+  {
+    "code": "E0003",
+    "message": "type mismatch",
+    "span": {
+      "start": 156,
+      "end": 184
+    },
+    "expected": "bool",
+    "actual": "i64"
+  },
+  {
+    "code": "E0003",
+    "message": "type mismatch",
+    "span": {
+      "start": 211,
+      "end": 212
+    },
+    "expected": "Point",
+    "actual": "i64"
+  },
+  {
+    "code": "E0051",
+    "message": "`distance` expects 1 arguments, found 0",
+    "span": {
+      "start": 230,
+      "end": 242
+    },
+    "expected": "1",
+    "actual": "0"
+  },
+  {
+    "code": "E0051",
+    "message": "`distance` expects 1 arguments, found 2",
+    "span": {
+      "start": 259,
+      "end": 275
+    },
+    "expected": "1",
+    "actual": "2"
+  }
+]
+"###);
+}
+
+#[test]
+fn typecheck_unknown_method_is_e0048() {
+    // `x` is both a field and a method; `y` is only a field, so `p.y()` has no method to call.
+    let src = "struct Point {\n  x: i64;\n  y: i64;\n}\n\nimpl Point {\n  fn x(self) -> bool {\n    true\n  }\n}\n\nfn f(p: Point) {\n  let a: bool = p.x();\n  let b = p.y();\n  let c = p.distance();\n}";
+    let diags = check(src).diagnostics;
+    assert_json_snapshot!(diags, @r###"
+[
+  {
+    "code": "E0048",
+    "message": "no method `y` on `Point`",
+    "span": {
+      "start": 142,
+      "end": 143
+    },
+    "expected": null,
+    "actual": "y"
+  },
+  {
+    "code": "E0048",
+    "message": "no method `distance` on `Point`",
+    "span": {
+      "start": 159,
+      "end": 167
+    },
+    "expected": null,
+    "actual": "distance"
+  }
+]
+"###);
+}
+
+#[test]
+fn typecheck_field_access_has_the_field_type() {
+    let src = "struct Point {\n  x: i64;\n}\n\nfn f(p: Point) -> bool {\n  p.x + 1\n}";
+    let diags = check(src).diagnostics;
+    assert_eq!(diags.len(), 1);
+    assert_eq!((diags[0].code.as_str(), diags[0].actual.as_deref()), ("E0004", Some("i64")));
+}
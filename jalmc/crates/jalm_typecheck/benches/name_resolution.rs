@@ -0,0 +1,28 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use jalm_parser::parse;
+use jalm_typecheck::check_parsed;
+
+/// Functions of `lets` bindings each, where every binding reads the two before it and a
+/// nested block shadows a few, so checking is dominated by scope lookups.
+fn generate_program(functions: usize, lets: usize) -> String {
+    let mut source = String::new();
+    for f in 0..functions {
+        source.push_str(&format!("fn f{f}(a: i64, b: i64) -> i64 {{\n  let v0: i64 = a;\n  let v1: i64 = b;\n"));
+        for i in 2..lets {
+            source.push_str(&format!("  let v{i}: i64 = v{} + v{} + a;\n", i - 1, i - 2));
+        }
+        source.push_str(&format!("  {{\n    let v0: i64 = v{} - b;\n    v0 + v1\n  }}\n}}\n\n", lets - 1));
+    }
+    source
+}
+
+fn bench_name_resolution(c: &mut Criterion) {
+    let source = generate_program(50, 100);
+    let parsed = parse(&source);
+    assert!(parsed.is_ok(), "generated program should parse");
+    assert!(check_parsed(&parsed).diagnostics.is_empty(), "generated program should type-check");
+    c.bench_function("check_name_heavy", |b| b.iter(|| check_parsed(&parsed)));
+}
+
+criterion_group!(benches, bench_name_resolution);
+criterion_main!(benches);
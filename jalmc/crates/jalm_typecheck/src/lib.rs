@@ -1,8 +1,13 @@
-use jalm_parser::parse;
-use jalm_syntax::{SyntaxElement, SyntaxKind, SyntaxNode};
-use rowan::TextRange;
+use jalm_ast::{AstNode, Impl, Import, Item, Root};
+use jalm_parser::{parse, Parse};
+use jalm_syntax::{decode_byte_string, int_literal_value, literal_value, SyntaxElement, SyntaxKind, SyntaxNode, SyntaxToken};
+use rowan::{TextRange, TextSize};
 use serde::Serialize;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::ops::ControlFlow;
+
+/// Version of this crate, reported by `jalmt version --verbose`.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct Diagnostic {
@@ -23,12 +28,24 @@ pub struct Span {
 enum Type {
     I64,
     I32,
+    U64,
+    I128,
+    U128,
     F64,
+    F32,
+    /// An integer literal not yet tied to a concrete integer type; defaults to `i64`.
+    IntLiteral,
     Bool,
     String,
     Bytes,
     Unit,
+    Tuple(Vec<Type>),
+    /// `[T; N]`, or `[T]` when the length is not fixed.
+    Array(Box<Type>, Option<usize>),
     Named(String),
+    /// The type of an expression that never produces a value, such as a call to a
+    /// function that always panics; it fits wherever any type is expected.
+    Never,
     Unknown,
     Error,
 }
@@ -36,170 +53,1077 @@ enum Type {
 impl Type {
     fn name(&self) -> String {
         match self {
-            Type::I64 => "i64".to_string(),
+            Type::I64 | Type::IntLiteral => "i64".to_string(),
             Type::I32 => "i32".to_string(),
+            Type::U64 => "u64".to_string(),
+            Type::I128 => "i128".to_string(),
+            Type::U128 => "u128".to_string(),
             Type::F64 => "f64".to_string(),
+            Type::F32 => "f32".to_string(),
             Type::Bool => "bool".to_string(),
             Type::String => "string".to_string(),
             Type::Bytes => "bytes".to_string(),
             Type::Unit => "()".to_string(),
+            Type::Tuple(elems) => match elems.as_slice() {
+                [single] => format!("({},)", single.name()),
+                _ => format!("({})", elems.iter().map(Type::name).collect::<Vec<_>>().join(", ")),
+            },
+            Type::Array(elem, Some(len)) => format!("[{}; {}]", elem.name(), len),
+            Type::Array(elem, None) => format!("[{}]", elem.name()),
             Type::Named(name) => name.clone(),
+            Type::Never => "never".to_string(),
             Type::Unknown => "<unknown>".to_string(),
             Type::Error => "<error>".to_string(),
         }
     }
 }
 
+impl Diagnostic {
+    /// `W`-prefixed codes are warnings and do not fail a build.
+    pub fn is_warning(&self) -> bool {
+        self.code.starts_with('W')
+    }
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct CheckResult {
     pub diagnostics: Vec<Diagnostic>,
 }
 
+impl CheckResult {
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics.iter().any(|d| !d.is_warning())
+    }
+}
+
+/// Checks `source`. Diagnostics are sorted by position; [`check_with`] streams the same ones
+/// in batches instead.
 pub fn check(source: &str) -> CheckResult {
-    let parsed = parse(source);
-    let root = parsed.syntax();
+    check_parsed(&parse(source))
+}
+
+/// Like [`check`], for a caller that has already parsed the source.
+pub fn check_parsed(parsed: &Parse) -> CheckResult {
+    let mut diagnostics = Vec::new();
+    check_parsed_with(parsed, &mut |diag| {
+        diagnostics.push(diag);
+        ControlFlow::Continue(())
+    });
+    sort_diagnostics(&mut diagnostics);
+    CheckResult { diagnostics }
+}
+
+/// Checks `source`, handing each diagnostic to `emit` as soon as the item that produced
+/// it has been checked. Diagnostics arrive one batch per function, in source order; those
+/// about top-level declarations form a batch before the functions, and unused imports,
+/// known only once every function is checked, a last batch after them. Each batch is
+/// sorted by position. Returning `ControlFlow::Break` from `emit` stops the check.
+pub fn check_with(source: &str, emit: &mut dyn FnMut(Diagnostic) -> ControlFlow<()>) {
+    check_parsed_with(&parse(source), emit);
+}
+
+/// Like [`check_with`], for a caller that has already parsed the source.
+pub fn check_parsed_with(parsed: &Parse, emit: &mut dyn FnMut(Diagnostic) -> ControlFlow<()>) {
+    let root = parsed.ast();
+    let _ = Checker::new().check_root(&root, emit);
+}
+
+/// Type of the value function `name`'s body ends with, such as `"bool"` or `"(i64, i64)"`, as
+/// the checker infers it; `None` when there is no such function or its type is not known.
+/// Diagnostics are not reported.
+pub fn body_type(parsed: &Parse, name: &str) -> Option<String> {
+    let mut checker = Checker::new();
+    let _ = checker.check_root(&parsed.ast(), &mut |_| ControlFlow::Continue(()));
+    let ty = checker.body_types.remove(name)?;
+    (!matches!(ty, Type::Unknown | Type::Error | Type::Never)).then(|| ty.name())
+}
+
+/// Declared return type of top-level function `name`, with aliases resolved, such as `"bool"`;
+/// `"()"` when it declares none, and `None` when there is no such function.
+pub fn return_type(parsed: &Parse, name: &str) -> Option<String> {
     let mut checker = Checker::new();
-    checker.check_root(&root);
-    CheckResult {
-        diagnostics: checker.diagnostics,
+    let _ = checker.check_root(&parsed.ast(), &mut |_| ControlFlow::Continue(()));
+    checker.functions.remove(name).map(|ty| ty.name())
+}
+
+fn sort_diagnostics(diagnostics: &mut [Diagnostic]) {
+    diagnostics.sort_by(|a, b| (a.span.start, &a.code).cmp(&(b.span.start, &b.code)));
+}
+
+/// An interned identifier; compare and hash it instead of the name it stands for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Symbol(u32);
+
+/// Maps each distinct identifier to a [`Symbol`] and back.
+#[derive(Default)]
+struct Interner {
+    names: Vec<String>,
+    symbols: HashMap<String, Symbol>,
+}
+
+impl Interner {
+    fn intern(&mut self, name: &str) -> Symbol {
+        if let Some(&symbol) = self.symbols.get(name) {
+            return symbol;
+        }
+        let symbol = Symbol(self.names.len() as u32);
+        self.names.push(name.to_string());
+        self.symbols.insert(name.to_string(), symbol);
+        symbol
+    }
+
+    fn resolve(&self, symbol: Symbol) -> &str {
+        &self.names[symbol.0 as usize]
     }
 }
 
 struct Checker {
-    scopes: Vec<HashMap<String, Type>>,
+    /// Identifiers are interned once, where they are read; every scope is keyed by symbol.
+    interner: Interner,
+    scopes: Vec<HashMap<Symbol, Type>>,
+    mut_bindings: Vec<Vec<MutBinding>>,
+    imports: Vec<Vec<ImportBinding>>,
+    /// Declared fields of each `struct`, by struct name.
+    structs: HashMap<String, Vec<(String, Type)>>,
+    /// The enum each fieldless variant path, such as `Color::Red`, belongs to.
+    variants: HashMap<String, String>,
+    /// Values of the `const` items whose initializers are constant expressions.
+    consts: HashMap<String, i128>,
+    /// `type` aliases with their targets fully expanded; a cyclic alias stands for `Error`.
+    aliases: HashMap<String, Type>,
+    /// Functions that never return: declared `-> never`, or every path ends in such a call.
+    diverging: HashSet<String>,
+    /// Functions marked `@deprecated`, with the attribute's note if it has one.
+    deprecated: HashMap<String, Option<String>>,
+    /// Signatures of functions declared with `<T, ...>`, checked at calls that name their type arguments.
+    generics: HashMap<String, GenericSig>,
+    /// Declared return type of each top-level function, by name.
+    functions: HashMap<String, Type>,
+    /// Type of each top-level function's body, for [`body_type`].
+    body_types: HashMap<String, Type>,
+    /// Methods taking `self` in each type's `impl` blocks, by type name and then method name.
+    methods: HashMap<String, HashMap<String, MethodSig>>,
+    /// The type a `self` parameter has: the `impl` whose methods are being checked.
+    self_type: Option<Type>,
+    /// Set unless the file defines its own `assert`, which then shadows the builtin.
+    builtin_assert: bool,
+    /// Index in `scopes` of the current function's parameters.
+    param_scope: Option<usize>,
     current_return: Type,
+    /// Enclosing `'label: { ... }` blocks, innermost last, with the type of the first `break` value seen.
+    labels: Vec<(String, Option<Type>)>,
+    /// Names bound by the `let`s of each enclosing block, with where each `let` starts.
+    block_lets: Vec<Vec<(Symbol, TextSize)>>,
     diagnostics: Vec<Diagnostic>,
 }
 
+/// A generic function's type parameters and its signature in terms of them.
+struct GenericSig {
+    params: Vec<String>,
+    inputs: Vec<Type>,
+    /// How many leading parameters have no default and must be passed.
+    required: usize,
+    output: Type,
+}
+
+/// A method's parameter types after the `self` receiver, and its return type.
+struct MethodSig {
+    inputs: Vec<Type>,
+    output: Type,
+}
+
+/// A `mut` parameter or `let mut` binding, tracked until its scope ends.
+struct MutBinding {
+    name: Symbol,
+    node: SyntaxNode,
+    is_param: bool,
+    assigned: bool,
+}
+
+/// A name brought in by `use`, tracked until its scope ends.
+struct ImportBinding {
+    name: Symbol,
+    node: SyntaxNode,
+    used: bool,
+}
+
 impl Checker {
     fn new() -> Self {
         Self {
+            interner: Interner::default(),
             scopes: vec![HashMap::new()],
+            mut_bindings: vec![Vec::new()],
+            imports: vec![Vec::new()],
+            structs: HashMap::new(),
+            variants: HashMap::new(),
+            consts: HashMap::new(),
+            aliases: HashMap::new(),
+            diverging: HashSet::new(),
+            deprecated: HashMap::new(),
+            generics: HashMap::new(),
+            functions: HashMap::new(),
+            body_types: HashMap::new(),
+            methods: HashMap::new(),
+            self_type: None,
+            builtin_assert: true,
+            param_scope: None,
             current_return: Type::Unit,
+            labels: Vec::new(),
+            block_lets: Vec::new(),
             diagnostics: Vec::new(),
         }
     }
 
-    fn check_root(&mut self, node: &SyntaxNode) {
-        for item in node.children() {
-            if item.kind() == SyntaxKind::FnDecl {
-                self.check_fn(&item);
+    fn check_root(&mut self, root: &Root, emit: &mut dyn FnMut(Diagnostic) -> ControlFlow<()>) -> ControlFlow<()> {
+        let consts: Vec<SyntaxNode> =
+            root.items().filter_map(|item| matches!(item, Item::Const(_)).then(|| item.syntax().clone())).collect();
+        self.consts = const_values(&consts);
+        self.declare_type_aliases(root);
+        // Top-level imports are visible to every function, wherever they appear in the file.
+        for item in root.items() {
+            match item {
+                Item::Use(import) => self.check_use(import.syntax()),
+                Item::Struct(decl) => self.declare_struct(decl.syntax()),
+                Item::Enum(decl) => {
+                    self.declare_enum(decl.syntax());
+                    self.check_discriminants(decl.syntax());
+                }
+                Item::Const(decl) => self.check_const(decl.syntax()),
+                Item::Fn(f) => {
+                    if let Some(name) = fn_name(f.syntax()) {
+                        let ret = find_return_type(f.syntax(), &self.consts).map(|ty| self.resolve_alias(ty)).unwrap_or(Type::Unit);
+                        self.functions.insert(name, ret);
+                    }
+                }
+                _ => {}
+            }
+        }
+        self.check_array_sizes(root.syntax());
+        self.diverging = diverging_fns(root, &self.consts);
+        self.deprecated = deprecated_fns(root);
+        self.declare_generics(root);
+        self.declare_methods(root);
+        self.builtin_assert = !root
+            .items()
+            .any(|item| matches!(item, Item::Fn(f) if fn_name(f.syntax()).as_deref() == Some("assert")));
+        self.flush(emit)?;
+        for item in root.items() {
+            match item {
+                Item::Fn(f) => {
+                    self.check_fn(f.syntax());
+                    self.flush(emit)?;
+                }
+                Item::Impl(block) => {
+                    self.self_type = impl_type_name(&block).map(Type::Named);
+                    for method in block.methods() {
+                        self.check_fn(method.syntax());
+                        self.flush(emit)?;
+                    }
+                    self.self_type = None;
+                }
+                _ => {}
+            }
+        }
+        for binding in self.imports.pop().unwrap_or_default() {
+            self.report_unused_import(binding);
+        }
+        self.flush(emit)
+    }
+
+    /// Hands the diagnostics collected so far to `emit`, in source order.
+    fn flush(&mut self, emit: &mut dyn FnMut(Diagnostic) -> ControlFlow<()>) -> ControlFlow<()> {
+        // Traversal can report an outer node after its children.
+        let mut batch = std::mem::take(&mut self.diagnostics);
+        sort_diagnostics(&mut batch);
+        batch.into_iter().try_for_each(emit)
+    }
+
+    /// `const NAME: T = expr;` binds `NAME` for every function, like a top-level import.
+    fn check_const(&mut self, node: &SyntaxNode) {
+        let Some(name) = ident_token(node) else {
+            return;
+        };
+        let ty = self.find_type(node).unwrap_or(Type::Unknown);
+        if let Some(init) = find_expr_after_token(node, SyntaxKind::Eq) {
+            let init_ty = self.check_expr(&init);
+            if init_ty != Type::Error && !type_compatible(&ty, &init_ty) {
+                self.type_mismatch(node, &ty, &init_ty, "E0003");
+            }
+        }
+        let name = self.interner.intern(name.text());
+        self.insert_var(name, ty);
+    }
+
+    /// The `N` of every `[T; N]` in the file must evaluate at compile time.
+    fn check_array_sizes(&mut self, root: &SyntaxNode) {
+        let sizes: Vec<SyntaxNode> = root
+            .descendants()
+            .filter(|n| n.kind() == SyntaxKind::ArrayType)
+            .filter_map(|array| array.children().find(|n| is_expr_kind(n.kind())))
+            .collect();
+        for size in sizes {
+            if const_value(&size, &self.consts).is_none() {
+                let text = size.text().to_string();
+                self.report(&size, "E0042", "array size must be a constant", None, Some(text));
             }
         }
     }
 
     fn check_fn(&mut self, node: &SyntaxNode) {
-        let ret = find_return_type(node).unwrap_or(Type::Unit);
+        let ret = find_return_type(node, &self.consts).map(|ty| self.resolve_alias(ty)).unwrap_or(Type::Unit);
         let saved_return = self.current_return.clone();
         self.current_return = ret;
         self.enter_scope();
+        let saved_param_scope = self.param_scope.replace(self.scopes.len() - 1);
         if let Some(params) = node.children().find(|n| n.kind() == SyntaxKind::ParamList) {
             for param in params.children().filter(|n| n.kind() == SyntaxKind::Param) {
-                if let (Some(name), Some(ty)) = (find_ident_in(&param), find_type_in(&param)) {
-                    self.insert_var(&name, ty);
+                let name = ident_token(&param);
+                let ty = self.find_type(&param).or_else(|| self.self_type.clone().filter(|_| name.as_ref().map(|t| t.text()) == Some("self")));
+                if let (Some(name), Some(ty)) = (name, ty) {
+                    // Defaults see only the parameters declared before them.
+                    if let Some(default) = find_expr_after_token(&param, SyntaxKind::Eq) {
+                        let default_ty = self.check_expr(&default);
+                        if default_ty != Type::Error && !coercible(&ty, &default_ty) {
+                            self.type_mismatch(&default, &ty, &default_ty, "E0003");
+                        }
+                    }
+                    let name = self.interner.intern(name.text());
+                    self.insert_var(name, ty);
+                    if has_mut(&param) {
+                        self.declare_mut(name, &param, true);
+                    }
                 }
             }
         }
         if let Some(block) = node.children().find(|n| n.kind() == SyntaxKind::Block) {
             let body_ty = self.check_block(&block);
             let expected = self.current_return.clone();
-            if body_ty != Type::Error && !type_compatible(&expected, &body_ty) {
+            if body_ty != Type::Error && !coercible(&expected, &body_ty) {
                 self.type_mismatch(&block, &expected, &body_ty, "E0004");
             }
+            if let Some(name) = fn_name(node).filter(|_| self.self_type.is_none()) {
+                self.body_types.insert(name, body_ty);
+            }
         }
         self.exit_scope();
+        self.param_scope = saved_param_scope;
         self.current_return = saved_return;
     }
 
     fn check_block(&mut self, node: &SyntaxNode) -> Type {
         let mut last = Type::Unit;
+        let mut diverges = false;
+        self.enter_scope();
         if let Some(stmts) = node.children().find(|n| n.kind() == SyntaxKind::StmtList) {
             let items: Vec<_> = stmts.children().collect();
+            let mut lets = Vec::new();
+            for stmt in items.iter().filter(|stmt| stmt.kind() == SyntaxKind::LetStmt) {
+                let start = stmt.text_range().start();
+                for pattern in stmt.children().filter(|n| n.kind() == SyntaxKind::Pattern) {
+                    for name in pattern.descendants().filter(|n| n.kind() == SyntaxKind::IdentNode).filter_map(|n| ident_token(&n)) {
+                        lets.push((self.interner.intern(name.text()), start));
+                    }
+                }
+            }
+            self.block_lets.push(lets);
             let len = items.len();
             for (idx, stmt) in items.into_iter().enumerate() {
                 if idx + 1 == len && is_expr_kind(stmt.kind()) && stmt.kind() != SyntaxKind::ExprStmt {
                     last = self.check_expr(&stmt);
-                } else {
-                    self.check_stmt(&stmt);
+                } else if self.check_stmt(&stmt) == Type::Never {
+                    diverges = true;
                 }
             }
+            self.block_lets.pop();
+        }
+        self.exit_scope();
+        // Nothing after a diverging statement runs, so the block never produces a value.
+        if diverges {
+            Type::Never
+        } else {
+            last
         }
-        last
     }
 
-    fn check_stmt(&mut self, node: &SyntaxNode) {
+    /// Checks a statement; an expression statement yields its expression's type, `return` and
+    /// `break` yield `never`, and anything else `()`.
+    fn check_stmt(&mut self, node: &SyntaxNode) -> Type {
         match node.kind() {
             SyntaxKind::LetStmt => self.check_let(node),
-            SyntaxKind::ReturnStmt => self.check_return(node),
+            SyntaxKind::ReturnStmt => {
+                self.check_return(node);
+                return Type::Never;
+            }
+            SyntaxKind::BreakStmt => return self.check_break(node),
+            SyntaxKind::UseDecl => self.check_use(node),
             SyntaxKind::ExprStmt => {
                 if let Some(expr) = node.children().find(|n| is_expr_kind(n.kind())) {
-                    self.check_expr(&expr);
+                    return self.check_expr(&expr);
                 }
             }
             _ => {
                 if is_expr_kind(node.kind()) {
-                    self.check_expr(node);
+                    return self.check_expr(node);
                 }
             }
         }
+        Type::Unit
     }
 
     fn check_let(&mut self, node: &SyntaxNode) {
-        let name = node
-            .children()
-            .find(|n| n.kind() == SyntaxKind::Pattern)
-            .and_then(|n| find_ident_in(&n));
+        let pattern = node.children().find(|n| n.kind() == SyntaxKind::Pattern);
         let ty_annot = node
             .children()
             .find(|n| n.kind() == SyntaxKind::Type)
-            .map(|n| type_from_node(&n));
+            .map(|n| self.resolve_alias(type_from_node(&n, &self.consts)));
         let expr = find_expr_after_token(node, SyntaxKind::Eq);
         let expr_ty = expr.map(|e| self.check_expr(&e)).unwrap_or(Type::Unknown);
-        if let Some(name) = name {
-            if let Some(annot) = ty_annot.clone() {
-                if !type_compatible(&annot, &expr_ty) {
+        let ty = match ty_annot {
+            Some(annot) => {
+                if !coercible(&annot, &expr_ty) {
                     self.type_mismatch(node, &annot, &expr_ty, "E0003");
                 }
-                self.insert_var(&name, annot);
-            } else {
-                self.insert_var(&name, expr_ty);
+                annot
+            }
+            None => default_int(expr_ty),
+        };
+        // The pattern's bindings are not in scope in the `else` block, which must leave the function.
+        if let Some(else_block) = find_expr_after_token(node, SyntaxKind::KwElse) {
+            let else_ty = self.check_block(&else_block);
+            if !matches!(else_ty, Type::Never | Type::Error) {
+                self.report(&else_block, "E0050", "`let ... else` block does not diverge", Some("never".to_string()), Some(else_ty.name()));
+            }
+        }
+        if let Some(pattern) = pattern {
+            self.bind_pattern(&pattern, ty, has_mut(node));
+        }
+    }
+
+    /// Binds the names in a `let` pattern, destructuring tuple patterns element by element.
+    fn bind_pattern(&mut self, pattern: &SyntaxNode, ty: Type, is_mut: bool) {
+        if !is_paren_list(pattern) {
+            if let Some(name) = pattern.children().find(|n| n.kind() == SyntaxKind::IdentNode).as_ref().and_then(ident_token) {
+                let symbol = self.interner.intern(name.text());
+                if self.shadows_param(symbol) {
+                    self.report(pattern, "W0005", "local binding shadows parameter", None, Some(name.text().to_string()));
+                }
+                self.insert_var(symbol, ty);
+                if is_mut {
+                    self.declare_mut(symbol, pattern, false);
+                }
+            }
+            return;
+        }
+        let subs: Vec<_> = pattern.children().filter(|n| n.kind() == SyntaxKind::Pattern).collect();
+        if pattern.children().any(|n| n.kind() == SyntaxKind::IdentNode) {
+            // enum variant pattern like `Some(x)`; payload types are not modelled yet
+            for sub in &subs {
+                self.bind_pattern(sub, Type::Unknown, is_mut);
+            }
+            return;
+        }
+        if subs.len() == 1 && !has_comma(pattern) {
+            // `(a)` only groups
+            self.bind_pattern(&subs[0], ty, is_mut);
+            return;
+        }
+        let elems = match ty {
+            Type::Tuple(elems) if elems.len() == subs.len() => elems,
+            Type::Unit if subs.is_empty() => Vec::new(),
+            Type::Unknown | Type::Error => vec![ty; subs.len()],
+            Type::Tuple(elems) => {
+                self.report(pattern, "E0035", "tuple arity mismatch", Some(elems.len().to_string()), Some(subs.len().to_string()));
+                vec![Type::Error; subs.len()]
+            }
+            other => {
+                let expected = Type::Tuple(vec![Type::Unknown; subs.len()]);
+                self.type_mismatch(pattern, &expected, &other, "E0003");
+                vec![Type::Error; subs.len()]
+            }
+        };
+        for (sub, elem) in subs.iter().zip(elems) {
+            self.bind_pattern(sub, elem, is_mut);
+        }
+    }
+
+    /// Brings imported names into the current scope; imported items are not resolved yet.
+    fn check_use(&mut self, node: &SyntaxNode) {
+        for name_node in Import::cast(node.clone()).map(|import| import.bound_names()).unwrap_or_default() {
+            if let Some(name) = ident_token(&name_node) {
+                let name = self.interner.intern(name.text());
+                self.insert_var(name, Type::Unknown);
+                if let Some(imports) = self.imports.last_mut() {
+                    imports.push(ImportBinding { name, node: name_node, used: false });
+                }
             }
         }
     }
 
     fn check_return(&mut self, node: &SyntaxNode) {
         let expr = node.children().find(|n| is_expr_kind(n.kind()));
-        let expr_ty = expr.map(|e| self.check_expr(&e)).unwrap_or(Type::Unit);
+        let expr_ty = expr.as_ref().map(|e| self.check_expr(e)).unwrap_or(Type::Unit);
         let expected = self.current_return.clone();
-        if !type_compatible(&expected, &expr_ty) {
+        // Only a closure's return type is unknown, until its first `return` sets it.
+        if expected == Type::Unknown {
+            if !matches!(expr_ty, Type::Unknown | Type::Never | Type::Error) {
+                self.current_return = expr_ty;
+            }
+            return;
+        }
+        let has_value = !matches!(expr_ty, Type::Unit | Type::Unknown | Type::Never | Type::Error);
+        if let Some(expr) = expr.filter(|_| expected == Type::Unit && has_value) {
+            self.report(&expr, "E0038", "returning a value from a unit function", Some(expected.name()), Some(expr_ty.name()));
+        } else if !coercible(&expected, &expr_ty) {
             self.type_mismatch(node, &expected, &expr_ty, "E0004");
         }
     }
 
+    fn check_break(&mut self, node: &SyntaxNode) -> Type {
+        let expr = node.children().find(|n| is_expr_kind(n.kind()));
+        let ty = expr.as_ref().map(|e| self.check_expr(e)).unwrap_or(Type::Unit);
+        let Some(label) = label_token(node) else {
+            // There are no loops yet, so a plain `break` needs a labeled block around it.
+            if self.labels.is_empty() {
+                self.report(node, "E0055", "break outside loop", None, None);
+            }
+            return Type::Never;
+        };
+        let Some((_, seen)) = self.labels.iter_mut().rev().find(|(name, _)| name == label.text()) else {
+            self.diagnostics.push(Diagnostic {
+                code: "E0039".to_string(),
+                message: "unknown label".to_string(),
+                span: span_of(label.text_range()),
+                expected: None,
+                actual: Some(label.text().to_string()),
+            });
+            return Type::Never;
+        };
+        match seen.clone() {
+            None => *seen = Some(ty),
+            Some(first) if !type_compatible(&first, &ty) => {
+                self.type_mismatch(expr.as_ref().unwrap_or(node), &first, &ty, "E0003");
+            }
+            Some(_) => {}
+        }
+        Type::Never
+    }
+
+    /// A labeled block has the type of its `break` values, which must agree with its tail.
+    fn check_labeled_block(&mut self, node: &SyntaxNode) -> Type {
+        let label = label_token(node).map(|t| t.text().to_string()).unwrap_or_default();
+        self.labels.push((label, None));
+        let body_ty = node.children().find(|n| n.kind() == SyntaxKind::Block).map(|b| self.check_block(&b)).unwrap_or(Type::Unit);
+        let break_ty = self.labels.pop().and_then(|(_, ty)| ty);
+        match break_ty {
+            None => body_ty,
+            Some(ty) if body_ty == Type::Never => ty,
+            Some(ty) if !type_compatible(&ty, &body_ty) => {
+                self.type_mismatch(node, &ty, &body_ty, "E0003");
+                Type::Error
+            }
+            Some(_) => body_ty,
+        }
+    }
+
     fn check_expr(&mut self, node: &SyntaxNode) -> Type {
         match node.kind() {
             SyntaxKind::IdentNode => {
-                if let Some(name) = find_ident_in(node) {
-                    self.lookup_var(&name).unwrap_or_else(|| {
-                        self.report(node, "E0001", "undefined variable", None, Some(name));
+                if let Some(name) = ident_token(node) {
+                    let symbol = self.interner.intern(name.text());
+                    self.mark_used(symbol);
+                    self.lookup_var(symbol).unwrap_or_else(|| {
+                        let name = name.text().to_string();
+                        if self.defined_later(symbol, node) {
+                            self.report(node, "E0040", "use before definition", None, Some(name));
+                        } else {
+                            self.report(node, "E0001", "undefined variable", None, Some(name));
+                        }
                         Type::Error
                     })
                 } else {
                     Type::Unknown
                 }
             }
-            SyntaxKind::LiteralNode => literal_type(node),
+            SyntaxKind::LiteralNode => self.check_literal(node),
             SyntaxKind::BinExpr => self.check_bin_expr(node),
-            SyntaxKind::CallExpr => Type::Unknown,
-            SyntaxKind::MemberExpr => Type::Unknown,
+            SyntaxKind::AssignExpr => self.check_assign(node),
+            SyntaxKind::CallExpr => self.check_call(node),
+            SyntaxKind::MemberExpr | SyntaxKind::PathExpr => {
+                // Path items are not resolved yet, but the leading name counts as a use.
+                let Some(name) = node.children().next().filter(|n| n.kind() == SyntaxKind::IdentNode).as_ref().and_then(ident_token) else {
+                    return Type::Unknown;
+                };
+                let name = self.interner.intern(name.text());
+                self.mark_used(name);
+                // `p.x` on a struct-typed local has the field's type.
+                let field = node.children().nth(1).as_ref().and_then(find_ident_in);
+                if node.kind() == SyntaxKind::PathExpr {
+                    // A fieldless variant such as `Color::Red` is a value of its enum.
+                    let path = format!("{}::{}", self.interner.resolve(name), field.as_deref().unwrap_or_default());
+                    if let Some(ty) = self.variants.get(&path).filter(|_| node.children().count() == 2) {
+                        return Type::Named(ty.clone());
+                    }
+                }
+                match (node.kind(), self.lookup_var(name), field) {
+                    (SyntaxKind::MemberExpr, Some(Type::Named(ty)), Some(field)) => self
+                        .structs
+                        .get(&ty)
+                        .and_then(|fields| fields.iter().find(|(name, _)| *name == field))
+                        .map(|(_, ty)| ty.clone())
+                        .unwrap_or(Type::Unknown),
+                    _ => Type::Unknown,
+                }
+            }
+            SyntaxKind::ClosureExpr => self.check_closure(node),
+            SyntaxKind::StructLit => self.check_struct_lit(node),
             SyntaxKind::IfExpr => self.check_if_expr(node),
+            SyntaxKind::IfLetExpr => self.check_if_let_expr(node),
             SyntaxKind::MatchExpr => self.check_match_expr(node),
             SyntaxKind::Block => self.check_block(node),
-            SyntaxKind::ParenExpr => node.children().find(|n| is_expr_kind(n.kind())).map(|e| self.check_expr(&e)).unwrap_or(Type::Unknown),
+            SyntaxKind::LabeledBlock => self.check_labeled_block(node),
+            // `await` yields the awaited value; there is no separate future type yet.
+            SyntaxKind::ParenExpr | SyntaxKind::AwaitExpr => node.children().find(|n| is_expr_kind(n.kind())).map(|e| self.check_expr(&e)).unwrap_or(Type::Unknown),
+            SyntaxKind::TupleExpr => {
+                let elems: Vec<_> = node.children().filter(|n| is_expr_kind(n.kind())).map(|e| self.check_expr(&e)).collect();
+                if elems.is_empty() {
+                    Type::Unit
+                } else {
+                    Type::Tuple(elems)
+                }
+            }
+            SyntaxKind::ArrayExpr => self.check_array_expr(node),
             _ => Type::Unknown,
         }
     }
 
+    /// `[a, b, c]`: every element has the type of the first.
+    fn check_array_expr(&mut self, node: &SyntaxNode) -> Type {
+        let mut elem_ty = Type::Unknown;
+        let mut len = 0;
+        for elem in node.children().filter(|n| is_expr_kind(n.kind())) {
+            let ty = self.check_expr(&elem);
+            if len == 0 {
+                elem_ty = ty;
+            } else if ty != Type::Error && elem_ty != Type::Error && !type_compatible(&elem_ty, &ty) {
+                self.type_mismatch(&elem, &elem_ty, &ty, "E0003");
+            } else if elem_ty == Type::IntLiteral {
+                elem_ty = ty;
+            }
+            len += 1;
+        }
+        Type::Array(Box::new(elem_ty), Some(len))
+    }
+
+    /// Expands every `type Name = T;` through the aliases `T` mentions. An alias that reaches
+    /// itself is reported as `E0046` on its name; one that only reaches another alias's cycle
+    /// stands for `Error` without a report of its own.
+    fn declare_type_aliases(&mut self, root: &Root) {
+        let direct: HashMap<String, (SyntaxNode, Type)> = root
+            .items()
+            .filter_map(|item| {
+                let Item::TypeAlias(decl) = item else {
+                    return None;
+                };
+                let name_node = decl.syntax().children().find(|n| n.kind() == SyntaxKind::IdentNode)?;
+                Some((find_ident_in(&name_node)?, (name_node, find_type_in(decl.syntax(), &self.consts)?)))
+            })
+            .collect();
+        for (name, (name_node, target)) in &direct {
+            let expanded = expand_alias(target, &direct, &mut vec![name.clone()]);
+            if expanded.as_ref().is_err_and(|cycle| cycle == name) {
+                self.report(name_node, "E0046", "cyclic type alias", None, Some(name.clone()));
+            }
+            self.aliases.insert(name.clone(), expanded.unwrap_or(Type::Error));
+        }
+    }
+
+    fn declare_generics(&mut self, root: &Root) {
+        for item in root.items() {
+            let Item::Fn(f) = item else {
+                continue;
+            };
+            let Some(generics) = f.syntax().children().find(|n| n.kind() == SyntaxKind::GenericParamList) else {
+                continue;
+            };
+            let Some(name) = f.syntax().children().find(|n| n.kind() == SyntaxKind::IdentNode).as_ref().and_then(find_ident_in)
+            else {
+                continue;
+            };
+            let params = generics.children().filter_map(|n| find_ident_in(&n)).collect();
+            let fn_params: Vec<SyntaxNode> = f
+                .syntax()
+                .children()
+                .find(|n| n.kind() == SyntaxKind::ParamList)
+                .map(|list| list.children().filter(|n| n.kind() == SyntaxKind::Param).collect())
+                .unwrap_or_default();
+            let inputs = fn_params.iter().map(|param| self.find_type(param).unwrap_or(Type::Unknown)).collect();
+            let required = fn_params.iter().take_while(|param| find_expr_after_token(param, SyntaxKind::Eq).is_none()).count();
+            let output = find_return_type(f.syntax(), &self.consts).map(|ty| self.resolve_alias(ty)).unwrap_or(Type::Unit);
+            self.generics.insert(name, GenericSig { params, inputs, required, output });
+        }
+    }
+
+    fn declare_methods(&mut self, root: &Root) {
+        for item in root.items() {
+            let Item::Impl(block) = item else {
+                continue;
+            };
+            let Some(type_name) = impl_type_name(&block) else {
+                continue;
+            };
+            for method in block.methods() {
+                let Some(name) = method.syntax().children().find(|n| n.kind() == SyntaxKind::IdentNode).as_ref().and_then(find_ident_in)
+                else {
+                    continue;
+                };
+                let params: Vec<SyntaxNode> = method
+                    .syntax()
+                    .children()
+                    .find(|n| n.kind() == SyntaxKind::ParamList)
+                    .map(|list| list.children().filter(|n| n.kind() == SyntaxKind::Param).collect())
+                    .unwrap_or_default();
+                // Functions without a `self` receiver cannot be called with `.`.
+                let Some((receiver, rest)) = params.split_first() else {
+                    continue;
+                };
+                if find_ident_in(receiver).as_deref() != Some("self") || self.find_type(receiver).is_some() {
+                    continue;
+                }
+                let inputs = rest.iter().map(|param| self.find_type(param).unwrap_or(Type::Unknown)).collect();
+                let output =
+                    find_return_type(method.syntax(), &self.consts).map(|ty| self.resolve_alias(ty)).unwrap_or(Type::Unit);
+                self.methods.entry(type_name.clone()).or_default().insert(name, MethodSig { inputs, output });
+            }
+        }
+    }
+
+    /// The type of the `Type` child of `node`, with aliases resolved.
+    fn find_type(&self, node: &SyntaxNode) -> Option<Type> {
+        find_type_in(node, &self.consts).map(|ty| self.resolve_alias(ty))
+    }
+
+    fn resolve_alias(&self, ty: Type) -> Type {
+        match ty {
+            Type::Named(name) => self.aliases.get(&name).cloned().unwrap_or(Type::Named(name)),
+            Type::Tuple(elems) => Type::Tuple(elems.into_iter().map(|ty| self.resolve_alias(ty)).collect()),
+            Type::Array(elem, len) => Type::Array(Box::new(self.resolve_alias(*elem)), len),
+            other => other,
+        }
+    }
+
+    fn declare_struct(&mut self, node: &SyntaxNode) {
+        let Some(name) = node.children().find(|n| n.kind() == SyntaxKind::IdentNode).as_ref().and_then(find_ident_in) else {
+            return;
+        };
+        let fields = node
+            .children()
+            .filter(|n| n.kind() == SyntaxKind::StructField)
+            .filter_map(|field| Some((find_ident_in(&field)?, self.find_type(&field)?)))
+            .collect();
+        self.structs.insert(name, fields);
+    }
+
+    fn declare_enum(&mut self, node: &SyntaxNode) {
+        let Some(name) = node.children().find(|n| n.kind() == SyntaxKind::IdentNode).as_ref().and_then(find_ident_in) else {
+            return;
+        };
+        for variant in node.children().filter(|n| n.kind() == SyntaxKind::EnumVariant) {
+            if variant.children().any(|n| n.kind() == SyntaxKind::Type) {
+                continue;
+            }
+            if let Some(variant) = variant.children().find(|n| n.kind() == SyntaxKind::IdentNode).as_ref().and_then(find_ident_in) {
+                self.variants.insert(format!("{name}::{variant}"), name.clone());
+            }
+        }
+    }
+
+    /// Reports `E0044` when two variants of an enum share a discriminant, and `E0052` when one
+    /// does not fit the `i32` it is stored as. A variant without `= N` takes the previous
+    /// variant's value plus one, starting from 0.
+    fn check_discriminants(&mut self, node: &SyntaxNode) {
+        let mut seen = HashSet::new();
+        let mut next = 0;
+        for variant in node.children().filter(|n| n.kind() == SyntaxKind::EnumVariant) {
+            let explicit = variant.children().find(|n| n.kind() == SyntaxKind::LiteralNode);
+            let value = explicit
+                .as_ref()
+                .and_then(literal_value)
+                .and_then(|(_, text)| int_literal_value(&text))
+                .unwrap_or(next);
+            if i32::try_from(value).is_err() {
+                self.report(explicit.as_ref().unwrap_or(&variant), "E0052", "discriminant out of range", Some("i32".to_string()), Some(value.to_string()));
+            } else if !seen.insert(value) {
+                self.report(explicit.as_ref().unwrap_or(&variant), "E0044", "duplicate discriminant", None, Some(value.to_string()));
+            }
+            next = value + 1;
+        }
+    }
+
+    /// `Name { field: expr, ..base }`: given fields match their declared types, and without a
+    /// `..base` of the same struct type every declared field must be given.
+    fn check_struct_lit(&mut self, node: &SyntaxNode) -> Type {
+        let Some(name) = node.children().next().as_ref().and_then(find_ident_in) else {
+            return Type::Unknown;
+        };
+        let declared = self.structs.get(&name).cloned();
+        let mut given = Vec::new();
+        for field in node.children().filter(|n| n.kind() == SyntaxKind::StructLitField) {
+            let mut kids = field.children();
+            let name_node = kids.next();
+            let field_name = name_node.as_ref().and_then(find_ident_in);
+            let value_ty = kids.next().map(|e| self.check_expr(&e)).unwrap_or(Type::Unknown);
+            let field_ty = declared.iter().flatten().find(|(n, _)| Some(n) == field_name.as_ref()).map(|(_, ty)| ty.clone());
+            match (field_ty, &declared, name_node) {
+                (Some(field_ty), _, _) if value_ty != Type::Error && !type_compatible(&field_ty, &value_ty) => {
+                    self.type_mismatch(&field, &field_ty, &value_ty, "E0003");
+                }
+                (Some(_), _, _) => {}
+                (None, Some(_), Some(name_node)) => {
+                    let message = format!("no field `{}` on struct `{name}`", field_name.clone().unwrap_or_default());
+                    self.report(&name_node, "E0056", &message, None, field_name.clone());
+                }
+                _ => {}
+            }
+            given.extend(field_name);
+        }
+        let ty = Type::Named(name);
+        let spread = node.children().find(|n| n.kind() == SyntaxKind::StructLitSpread).and_then(|s| s.children().next());
+        if let Some(base) = spread {
+            let base_ty = self.check_expr(&base);
+            if base_ty != Type::Error && !type_compatible(&ty, &base_ty) {
+                self.type_mismatch(&base, &ty, &base_ty, "E0003");
+            }
+        } else if let Some(declared) = declared {
+            let missing: Vec<_> = declared.into_iter().map(|(n, _)| n).filter(|n| !given.contains(n)).collect();
+            if !missing.is_empty() {
+                let fields = missing.iter().map(|n| format!("`{n}`")).collect::<Vec<_>>().join(", ");
+                let plural = if missing.len() == 1 { "" } else { "s" };
+                let message = format!("missing field{plural} {fields} in initializer of `{}`", ty.name());
+                self.report(node, "E0045", &message, None, Some(missing.join(", ")));
+            }
+        }
+        ty
+    }
+
+    /// Closures have no function type yet; only their body is checked. A `return` in the body
+    /// leaves the closure, so the first one fixes the closure's return type, which later ones and
+    /// the body's value must match.
+    fn check_closure(&mut self, node: &SyntaxNode) -> Type {
+        // A closure body cannot break out of the blocks around it.
+        let saved_labels = std::mem::take(&mut self.labels);
+        let saved_return = std::mem::replace(&mut self.current_return, Type::Unknown);
+        self.enter_scope();
+        if let Some(params) = node.children().find(|n| n.kind() == SyntaxKind::ParamList) {
+            for param in params.children().filter(|n| n.kind() == SyntaxKind::Param) {
+                if let Some(name) = ident_token(&param) {
+                    let ty = self.find_type(&param).unwrap_or(Type::Unknown);
+                    let name = self.interner.intern(name.text());
+                    self.insert_var(name, ty);
+                }
+            }
+        }
+        if let Some(body) = node.children().find(|n| is_expr_kind(n.kind())) {
+            let body_ty = self.check_expr(&body);
+            let returned = self.current_return.clone();
+            if body_ty != Type::Error && !type_compatible(&returned, &body_ty) {
+                self.type_mismatch(&body, &returned, &body_ty, "E0004");
+            }
+        }
+        self.exit_scope();
+        self.labels = saved_labels;
+        self.current_return = saved_return;
+        Type::Unknown
+    }
+
+    fn check_literal(&mut self, node: &SyntaxNode) -> Type {
+        for el in node.children_with_tokens() {
+            if let SyntaxElement::Token(t) = el {
+                if t.kind() != SyntaxKind::Bytes {
+                    continue;
+                }
+                if let Err(range) = decode_byte_string(t.text()) {
+                    let start = usize::from(t.text_range().start());
+                    self.diagnostics.push(Diagnostic {
+                        code: "E0049".to_string(),
+                        message: "invalid byte escape".to_string(),
+                        span: Span { start: start + range.start, end: start + range.end },
+                        expected: None,
+                        actual: Some(t.text()[range].to_string()),
+                    });
+                }
+            }
+        }
+        literal_type(node)
+    }
+
+    fn check_assign(&mut self, node: &SyntaxNode) -> Type {
+        let mut kids = node.children().filter(|n| is_expr_kind(n.kind()));
+        let (target, value) = match (kids.next(), kids.next()) {
+            (Some(target), Some(value)) => (target, value),
+            _ => return Type::Unit,
+        };
+        let target_ty = self.check_expr(&target);
+        let value_ty = self.check_expr(&value);
+        if target.kind() == SyntaxKind::IdentNode {
+            if let Some(name) = ident_token(&target) {
+                let name = self.interner.intern(name.text());
+                self.mark_assigned(name);
+            }
+        }
+        // Only plain `=` widens; `x += y` is arithmetic, whose operands must already agree.
+        let plain = node.children_with_tokens().any(|e| e.kind() == SyntaxKind::Eq);
+        let fits = if plain { coercible(&target_ty, &value_ty) } else { type_compatible(&target_ty, &value_ty) };
+        if target_ty != Type::Error && value_ty != Type::Error && !fits {
+            self.type_mismatch(node, &target_ty, &value_ty, "E0003");
+        }
+        Type::Unit
+    }
+
+    fn check_call(&mut self, node: &SyntaxNode) -> Type {
+        let mut kids = node.children();
+        if let Some(callee) = kids.next() {
+            if callee.kind() == SyntaxKind::MemberExpr {
+                return self.check_method_call(node, &callee, kids);
+            }
+            // Bare function names are not in scope yet; only check computed callees.
+            if callee.kind() != SyntaxKind::IdentNode {
+                self.check_expr(&callee);
+            } else if let Some(token) = ident_token(&callee) {
+                let name = token.text();
+                let symbol = self.interner.intern(name);
+                self.mark_used(symbol);
+                let local = self.lookup_var(symbol).is_some();
+                // The entry point is run by the host; calling it again recurses through the whole program.
+                if name == "main" && !local {
+                    self.report(node, "W0007", "explicit call to `main`", None, None);
+                }
+                if let Some(note) = self.deprecated.get(name).filter(|_| !local) {
+                    let message = match note {
+                        Some(note) => format!("use of deprecated function `{name}`: {note}"),
+                        None => format!("use of deprecated function `{name}`"),
+                    };
+                    self.report(&callee, "W0008", &message, None, Some(name.to_string()));
+                }
+                if self.diverging.contains(name) {
+                    for arg in kids.filter(|n| is_expr_kind(n.kind())) {
+                        self.check_expr(&arg);
+                    }
+                    return Type::Never;
+                }
+                if name == "assert" && self.builtin_assert {
+                    for arg in kids.filter(|n| is_expr_kind(n.kind())) {
+                        let ty = self.check_expr(&arg);
+                        if ty != Type::Error && !type_compatible(&Type::Bool, &ty) {
+                            self.type_mismatch(&arg, &Type::Bool, &ty, "E0003");
+                        }
+                    }
+                    return Type::Unit;
+                }
+                // A local of the same name shadows the function.
+                if !local {
+                    if let Some(ty) = self.check_instantiated_call(node, name) {
+                        return ty;
+                    }
+                    if let Some(ret) = self.functions.get(name).filter(|_| !self.generics.contains_key(name)).cloned() {
+                        for arg in kids.filter(|n| is_expr_kind(n.kind())) {
+                            self.check_expr(&arg);
+                        }
+                        return ret;
+                    }
+                }
+            }
+        }
+        for arg in kids.filter(|n| is_expr_kind(n.kind())) {
+            self.check_expr(&arg);
+        }
+        Type::Unknown
+    }
+
+    /// `recv.name(args)`: when `recv` is a type with an `impl`, `name` must be one of its methods, and the arguments
+    /// after the implicit `self` are checked against the method's parameters, which they must match in number.
+    fn check_method_call(&mut self, node: &SyntaxNode, callee: &SyntaxNode, args: impl Iterator<Item = SyntaxNode>) -> Type {
+        let mut parts = callee.children();
+        let receiver_ty = parts.next().map(|base| self.check_expr(&base)).unwrap_or(Type::Unknown);
+        let name_node = parts.find(|n| n.kind() == SyntaxKind::IdentNode);
+        let method = match (&receiver_ty, name_node.as_ref().and_then(find_ident_in)) {
+            (Type::Named(ty), Some(name)) if self.structs.contains_key(ty) || self.methods.contains_key(ty) => {
+                let sig = self.methods.get(ty).and_then(|methods| methods.get(&name)).map(|sig| (sig.inputs.clone(), sig.output.clone()));
+                if let (None, Some(name_node)) = (&sig, &name_node) {
+                    self.report(name_node, "E0048", &format!("no method `{name}` on `{ty}`"), None, Some(name.clone()));
+                }
+                sig
+            }
+            _ => None,
+        };
+        let Some((inputs, output)) = method else {
+            for arg in args.filter(|n| is_expr_kind(n.kind())) {
+                self.check_expr(&arg);
+            }
+            return Type::Unknown;
+        };
+        let args: Vec<SyntaxNode> = args.filter(|n| is_expr_kind(n.kind())).collect();
+        if args.len() != inputs.len() {
+            let name = name_node.as_ref().and_then(find_ident_in).unwrap_or_default();
+            let message = format!("`{name}` expects {} arguments, found {}", inputs.len(), args.len());
+            self.report(node, "E0051", &message, Some(inputs.len().to_string()), Some(args.len().to_string()));
+        }
+        for (arg, expected) in args.iter().zip(&inputs) {
+            let ty = self.check_expr(arg);
+            if ty != Type::Error && !coercible(expected, &ty) {
+                self.type_mismatch(arg, expected, &ty, "E0003");
+            }
+        }
+        for arg in args.iter().skip(inputs.len()) {
+            self.check_expr(arg);
+        }
+        output
+    }
+
+    /// `f::<A, B>(..)` on a generic `f`: checks the arguments and gives the return type with the type arguments
+    /// substituted for `f`'s parameters. `None` when there is nothing to substitute, leaving the call unchecked.
+    /// Type arguments must match `f`'s type parameters in number (`E0053`) and may not be given to a
+    /// non-generic function (`E0054`); the value arguments must match its parameters in number (`E0051`).
+    fn check_instantiated_call(&mut self, node: &SyntaxNode, name: &str) -> Option<Type> {
+        let type_args = node.children().find(|n| n.kind() == SyntaxKind::TypeArgList)?;
+        let args: Vec<Type> = type_args
+            .children()
+            .filter(|n| n.kind() == SyntaxKind::Type)
+            .map(|n| self.resolve_alias(type_from_node(&n, &self.consts)))
+            .collect();
+        let Some(sig) = self.generics.get(name) else {
+            if self.functions.contains_key(name) {
+                let message = format!("`{name}` is not generic but is given type arguments");
+                self.report(&type_args, "E0054", &message, Some("0".to_string()), Some(args.len().to_string()));
+            }
+            return None;
+        };
+        if args.len() != sig.params.len() {
+            let expected = sig.params.len();
+            let message = format!("`{name}` expects {expected} type arguments, found {}", args.len());
+            self.report(&type_args, "E0053", &message, Some(expected.to_string()), Some(args.len().to_string()));
+            return None;
+        }
+        let bindings: HashMap<String, Type> = sig.params.iter().cloned().zip(args).collect();
+        let inputs: Vec<Type> = sig.inputs.iter().map(|ty| substitute(ty, &bindings)).collect();
+        let output = substitute(&sig.output, &bindings);
+        let required = sig.required;
+        let values: Vec<SyntaxNode> = node.children().skip(1).filter(|n| is_expr_kind(n.kind())).collect();
+        if values.len() < required || values.len() > inputs.len() {
+            let message = format!("`{name}` expects {} arguments, found {}", inputs.len(), values.len());
+            self.report(node, "E0051", &message, Some(inputs.len().to_string()), Some(values.len().to_string()));
+        }
+        let mut inputs = inputs.into_iter();
+        for arg in values {
+            let ty = self.check_expr(&arg);
+            if let Some(expected) = inputs.next() {
+                if ty != Type::Error && !coercible(&expected, &ty) {
+                    self.type_mismatch(&arg, &expected, &ty, "E0003");
+                }
+            }
+        }
+        Some(output)
+    }
+
     fn check_if_expr(&mut self, node: &SyntaxNode) -> Type {
         let mut kids = node.children();
         let cond = kids.next();
@@ -216,6 +1140,32 @@ impl Checker {
         if !type_compatible(&then_ty, &else_ty) {
             self.type_mismatch(node, &then_ty, &else_ty, "E0006");
             Type::Error
+        } else if then_ty == Type::Never {
+            else_ty
+        } else {
+            then_ty
+        }
+    }
+
+    /// `if let <pattern> = <expr> { .. }`: pattern bindings are only visible in the then-branch.
+    fn check_if_let_expr(&mut self, node: &SyntaxNode) -> Type {
+        let mut kids = node.children();
+        let pattern = kids.next();
+        let scrutinee_ty = kids.next().map(|e| self.check_expr(&e)).unwrap_or(Type::Unknown);
+        let then_block = kids.next();
+        let else_block = kids.next();
+        self.enter_scope();
+        if let Some(pattern) = &pattern {
+            self.bind_pattern(pattern, scrutinee_ty, false);
+        }
+        let then_ty = then_block.map(|b| self.check_expr(&b)).unwrap_or(Type::Unit);
+        self.exit_scope();
+        let else_ty = else_block.map(|b| self.check_expr(&b)).unwrap_or(Type::Unit);
+        if !type_compatible(&then_ty, &else_ty) {
+            self.type_mismatch(node, &then_ty, &else_ty, "E0006");
+            Type::Error
+        } else if then_ty == Type::Never {
+            else_ty
         } else {
             then_ty
         }
@@ -223,24 +1173,49 @@ impl Checker {
 
     fn check_match_expr(&mut self, node: &SyntaxNode) -> Type {
         let mut kids = node.children();
-        let _scrutinee = kids.next().map(|e| self.check_expr(&e));
+        let scrutinee = kids.next().map(|e| self.check_expr(&e)).unwrap_or(Type::Unknown);
         let mut arm_type: Option<Type> = None;
         for arm in kids.filter(|n| n.kind() == SyntaxKind::MatchArm) {
-            if let Some(expr) = arm.children().find(|n| is_expr_kind(n.kind())) {
-                let ty = self.check_expr(&expr);
-                if let Some(existing) = &arm_type {
-                    if !type_compatible(existing, &ty) {
+            // The pattern's bindings are visible in the guard and the arm's body.
+            self.enter_scope();
+            if let Some(pattern) = arm.children().find(|n| n.kind() == SyntaxKind::Pattern) {
+                self.check_literal_pattern(&pattern, &scrutinee);
+                self.bind_pattern(&pattern, default_int(scrutinee.clone()), false);
+            }
+            if let Some(cond) = arm.children().find(|n| n.kind() == SyntaxKind::MatchGuard).and_then(|g| g.children().find(|n| is_expr_kind(n.kind()))) {
+                let cond_ty = self.check_expr(&cond);
+                if cond_ty != Type::Bool && cond_ty != Type::Error {
+                    self.type_mismatch(&cond, &Type::Bool, &cond_ty, "E0005");
+                }
+            }
+            let body_ty = arm.children().find(|n| is_expr_kind(n.kind())).map(|expr| self.check_expr(&expr));
+            self.exit_scope();
+            if let Some(ty) = body_ty {
+                match &arm_type {
+                    Some(existing) if !type_compatible(existing, &ty) => {
                         self.type_mismatch(&arm, existing, &ty, "E0007");
                         return Type::Error;
                     }
-                } else {
-                    arm_type = Some(ty);
+                    // A diverging arm does not decide the match's type.
+                    Some(existing) if *existing != Type::Never => {}
+                    _ => arm_type = Some(ty),
                 }
             }
         }
         arm_type.unwrap_or(Type::Unit)
     }
 
+    /// A literal pattern must have the scrutinee's type, e.g. no `"hi" =>` when matching an `i64`.
+    fn check_literal_pattern(&mut self, pattern: &SyntaxNode, scrutinee: &Type) {
+        let Some(literal) = pattern.children().find(|n| n.kind() == SyntaxKind::LiteralNode) else {
+            return;
+        };
+        let ty = literal_type(&literal);
+        if *scrutinee != Type::Error && !type_compatible(scrutinee, &ty) {
+            self.report(pattern, "E0037", "pattern type mismatch", Some(scrutinee.name()), Some(ty.name()));
+        }
+    }
+
     fn check_bin_expr(&mut self, node: &SyntaxNode) -> Type {
         let (op_kind, left, right) = match bin_parts(node) {
             Some(parts) => parts,
@@ -251,10 +1226,22 @@ impl Checker {
         if l == Type::Error || r == Type::Error {
             return Type::Error;
         }
+        if is_ordering(op_kind) && [&left, &right].into_iter().any(is_comparison) {
+            self.report(node, "E0036", "chained comparison is not allowed; use `&&`", None, None);
+            return Type::Error;
+        }
         match op_kind {
             SyntaxKind::Plus | SyntaxKind::Minus | SyntaxKind::Star | SyntaxKind::Slash | SyntaxKind::Percent => {
                 if is_numeric(&l) && type_compatible(&l, &r) {
-                    l
+                    if is_zero_divisor(op_kind, &right) {
+                        self.report(&right, "E0032", "division by zero", None, None);
+                    }
+                    // `x + 1` takes the concrete type of `x`
+                    if l == Type::IntLiteral {
+                        r
+                    } else {
+                        l
+                    }
                 } else {
                     self.type_mismatch(node, &l, &r, "E0003");
                     Type::Error
@@ -268,7 +1255,7 @@ impl Checker {
                     Type::Error
                 }
             }
-            SyntaxKind::Lt | SyntaxKind::Lte | SyntaxKind::Gt | SyntaxKind::Gte => {
+            _ if is_ordering(op_kind) => {
                 if is_numeric(&l) && type_compatible(&l, &r) {
                     Type::Bool
                 } else {
@@ -300,26 +1287,122 @@ impl Checker {
     }
 
     fn type_mismatch(&mut self, node: &SyntaxNode, expected: &Type, actual: &Type, code: &str) {
+        if let (Type::Array(want, Some(n)), Type::Array(got, Some(m))) = (expected, actual) {
+            if n != m && type_compatible(want, got) {
+                self.report(node, "E0041", "array length mismatch", Some(expected.name()), Some(actual.name()));
+                return;
+            }
+        }
         self.report(node, code, "type mismatch", Some(expected.name()), Some(actual.name()));
     }
 
     fn enter_scope(&mut self) {
         self.scopes.push(HashMap::new());
+        self.mut_bindings.push(Vec::new());
+        self.imports.push(Vec::new());
     }
 
     fn exit_scope(&mut self) {
         self.scopes.pop();
+        for binding in self.mut_bindings.pop().unwrap_or_default() {
+            self.report_needless_mut(binding);
+        }
+        for binding in self.imports.pop().unwrap_or_default() {
+            self.report_unused_import(binding);
+        }
     }
 
-    fn insert_var(&mut self, name: &str, ty: Type) {
+    fn insert_var(&mut self, name: Symbol, ty: Type) {
         if let Some(scope) = self.scopes.last_mut() {
-            scope.insert(name.to_string(), ty);
+            scope.insert(name, ty);
+        }
+        // A redeclaration in the same scope ends the previous binding.
+        let shadowed = self.mut_bindings.last_mut().and_then(|bindings| {
+            let idx = bindings.iter().position(|b| b.name == name)?;
+            Some(bindings.remove(idx))
+        });
+        if let Some(binding) = shadowed {
+            self.report_needless_mut(binding);
+        }
+        let shadowed_import = self.imports.last_mut().and_then(|imports| {
+            let idx = imports.iter().position(|b| b.name == name)?;
+            Some(imports.remove(idx))
+        });
+        if let Some(binding) = shadowed_import {
+            self.report_unused_import(binding);
+        }
+    }
+
+    fn declare_mut(&mut self, name: Symbol, node: &SyntaxNode, is_param: bool) {
+        if let Some(bindings) = self.mut_bindings.last_mut() {
+            bindings.push(MutBinding { name, node: node.clone(), is_param, assigned: false });
+        }
+    }
+
+    /// Index in `scopes` of the innermost scope that binds `name`.
+    fn binding_depth(&self, name: Symbol) -> Option<usize> {
+        self.scopes.iter().rposition(|scope| scope.contains_key(&name))
+    }
+
+    fn mark_assigned(&mut self, name: Symbol) {
+        // Only the innermost declaration of `name` is visible; it may not be `mut`.
+        let Some(depth) = self.binding_depth(name) else {
+            return;
+        };
+        if let Some(binding) = self.mut_bindings[depth].iter_mut().find(|b| b.name == name) {
+            binding.assigned = true;
+        }
+    }
+
+    fn mark_used(&mut self, name: Symbol) {
+        let Some(depth) = self.binding_depth(name) else {
+            return;
+        };
+        if let Some(binding) = self.imports[depth].iter_mut().find(|b| b.name == name) {
+            binding.used = true;
+        }
+    }
+
+    fn report_unused_import(&mut self, binding: ImportBinding) {
+        if !binding.used {
+            let name = self.interner.resolve(binding.name).to_string();
+            self.report(&binding.node, "W0006", "unused import", None, Some(name));
         }
     }
 
-    fn lookup_var(&self, name: &str) -> Option<Type> {
+    fn report_needless_mut(&mut self, binding: MutBinding) {
+        if binding.assigned {
+            return;
+        }
+        let message = if binding.is_param {
+            "parameter does not need to be mutable"
+        } else {
+            "variable does not need to be mutable"
+        };
+        let name = self.interner.resolve(binding.name).to_string();
+        self.report(&binding.node, "W0004", message, None, Some(name));
+    }
+
+    /// True when a `let` later in an enclosing block binds `name`, after the reference `node`.
+    fn defined_later(&self, name: Symbol, node: &SyntaxNode) -> bool {
+        let at = node.text_range().start();
+        self.block_lets.iter().flatten().any(|(bound, start)| *bound == name && *start > at)
+    }
+
+    /// True for a binding at the top level of a function body that reuses a parameter name,
+    /// unless an earlier binding there already shadowed it, so the parameter is warned about once.
+    fn shadows_param(&self, name: Symbol) -> bool {
+        match self.param_scope {
+            Some(depth) => {
+                self.scopes.len() == depth + 2 && self.scopes[depth].contains_key(&name) && !self.scopes[depth + 1].contains_key(&name)
+            }
+            None => false,
+        }
+    }
+
+    fn lookup_var(&self, name: Symbol) -> Option<Type> {
         for scope in self.scopes.iter().rev() {
-            if let Some(ty) = scope.get(name) {
+            if let Some(ty) = scope.get(&name) {
                 return Some(ty.clone());
             }
         }
@@ -327,7 +1410,89 @@ impl Checker {
     }
 }
 
-fn find_return_type(node: &SyntaxNode) -> Option<Type> {
+/// Functions carrying `@deprecated` or `@deprecated("note")`.
+fn deprecated_fns(root: &Root) -> HashMap<String, Option<String>> {
+    root.items()
+        .filter_map(|item| {
+            let Item::Fn(f) = item else {
+                return None;
+            };
+            let attr = f
+                .syntax()
+                .children()
+                .filter(|n| n.kind() == SyntaxKind::Attribute)
+                .find(|attr| find_ident_in(attr).as_deref() == Some("deprecated"))?;
+            let note = attr
+                .children()
+                .find(|n| n.kind() == SyntaxKind::LiteralNode)
+                .and_then(|lit| literal_value(&lit))
+                .and_then(|(_, text)| Some(text.strip_prefix('"')?.strip_suffix('"')?.to_string()));
+            let name = fn_name(f.syntax())?;
+            Some((name, note))
+        })
+        .collect()
+}
+
+/// Names of functions that never return, including the `panic` builtin. A function diverges when it is declared
+/// `-> never` or a top-level statement of its body always diverges. Calls count once their callee is known to
+/// diverge, so the set is grown until it stops changing.
+fn diverging_fns(root: &Root, consts: &HashMap<String, i128>) -> HashSet<String> {
+    let fns: Vec<(String, SyntaxNode)> = root
+        .items()
+        .filter_map(|item| match item {
+            Item::Fn(f) => Some((fn_name(f.syntax())?, f.syntax().clone())),
+            _ => None,
+        })
+        .collect();
+    let mut diverging: HashSet<String> =
+        fns.iter().filter(|(_, f)| find_return_type(f, consts) == Some(Type::Never)).map(|(name, _)| name.clone()).collect();
+    // The `panic(code)` builtin traps, unless the file defines its own `panic`.
+    if !fns.iter().any(|(name, _)| name == "panic") {
+        diverging.insert("panic".to_string());
+    }
+    loop {
+        let found: Vec<String> = fns
+            .iter()
+            .filter(|(name, f)| {
+                !diverging.contains(name)
+                    && f.children().find(|n| n.kind() == SyntaxKind::Block).is_some_and(|b| block_diverges(&b, &diverging))
+            })
+            .map(|(name, _)| name.clone())
+            .collect();
+        if found.is_empty() {
+            return diverging;
+        }
+        diverging.extend(found);
+    }
+}
+
+fn block_diverges(block: &SyntaxNode, diverging: &HashSet<String>) -> bool {
+    block
+        .children()
+        .find(|n| n.kind() == SyntaxKind::StmtList)
+        .is_some_and(|stmts| stmts.children().any(|stmt| expr_diverges(&stmt, diverging)))
+}
+
+/// True for a call to a diverging function, or an `if` whose branches all diverge.
+fn expr_diverges(node: &SyntaxNode, diverging: &HashSet<String>) -> bool {
+    match node.kind() {
+        SyntaxKind::ExprStmt | SyntaxKind::ParenExpr => node.children().next().is_some_and(|e| expr_diverges(&e, diverging)),
+        SyntaxKind::CallExpr => node
+            .children()
+            .next()
+            .filter(|callee| callee.kind() == SyntaxKind::IdentNode)
+            .and_then(|callee| find_ident_in(&callee))
+            .is_some_and(|name| diverging.contains(&name)),
+        SyntaxKind::Block => block_diverges(node, diverging),
+        SyntaxKind::IfExpr => {
+            let branches: Vec<_> = node.children().skip(1).collect();
+            branches.len() == 2 && branches.iter().all(|b| expr_diverges(b, diverging))
+        }
+        _ => false,
+    }
+}
+
+fn find_return_type(node: &SyntaxNode, consts: &HashMap<String, i128>) -> Option<Type> {
     let mut seen_arrow = false;
     for el in node.children_with_tokens() {
         match el {
@@ -335,7 +1500,7 @@ fn find_return_type(node: &SyntaxNode) -> Option<Type> {
                 seen_arrow = true;
             }
             SyntaxElement::Node(n) if seen_arrow && n.kind() == SyntaxKind::Type => {
-                return Some(type_from_node(&n));
+                return Some(type_from_node(&n, consts));
             }
             _ => {}
         }
@@ -343,72 +1508,245 @@ fn find_return_type(node: &SyntaxNode) -> Option<Type> {
     None
 }
 
+/// A function declaration's own name; its attributes, which come before it, have identifiers too.
+fn fn_name(node: &SyntaxNode) -> Option<String> {
+    node.children().find(|n| n.kind() == SyntaxKind::IdentNode).as_ref().and_then(find_ident_in)
+}
+
 fn find_ident_in(node: &SyntaxNode) -> Option<String> {
-    if let Some(name) = node.children_with_tokens().find_map(|e| match e {
-        SyntaxElement::Token(t) if t.kind() == SyntaxKind::Ident => Some(t.text().to_string()),
+    ident_token(node).map(|t| t.text().to_string())
+}
+
+/// The token [`find_ident_in`] reads; interning its text directly avoids allocating the name.
+fn ident_token(node: &SyntaxNode) -> Option<SyntaxToken> {
+    if let Some(token) = node.children_with_tokens().find_map(|e| match e {
+        SyntaxElement::Token(t) if t.kind() == SyntaxKind::Ident => Some(t),
         _ => None,
     }) {
-        return Some(name);
+        return Some(token);
     }
-    for child in node.children() {
-        if let Some(name) = find_ident_in(&child) {
-            return Some(name);
+    node.children().find_map(|child| ident_token(&child))
+}
+
+fn is_paren_list(node: &SyntaxNode) -> bool {
+    node.children_with_tokens()
+        .any(|e| matches!(e, SyntaxElement::Token(t) if t.kind() == SyntaxKind::LParen))
+}
+
+fn has_comma(node: &SyntaxNode) -> bool {
+    node.children_with_tokens()
+        .any(|e| matches!(e, SyntaxElement::Token(t) if t.kind() == SyntaxKind::Comma))
+}
+
+fn has_mut(node: &SyntaxNode) -> bool {
+    node.children_with_tokens()
+        .any(|e| matches!(e, SyntaxElement::Token(t) if t.kind() == SyntaxKind::KwMut))
+}
+
+/// `ty` with every alias it mentions expanded, or the name of the first alias in `visiting`
+/// that the expansion leads back to.
+fn expand_alias(ty: &Type, aliases: &HashMap<String, (SyntaxNode, Type)>, visiting: &mut Vec<String>) -> Result<Type, String> {
+    match ty {
+        Type::Named(name) => {
+            let Some((_, target)) = aliases.get(name) else {
+                return Ok(ty.clone());
+            };
+            if visiting.contains(name) {
+                return Err(name.clone());
+            }
+            visiting.push(name.clone());
+            let expanded = expand_alias(target, aliases, visiting);
+            visiting.pop();
+            expanded
         }
+        Type::Tuple(elems) => elems.iter().map(|ty| expand_alias(ty, aliases, visiting)).collect::<Result<_, _>>().map(Type::Tuple),
+        Type::Array(elem, len) => Ok(Type::Array(Box::new(expand_alias(elem, aliases, visiting)?), *len)),
+        other => Ok(other.clone()),
     }
-    None
 }
 
-fn find_type_in(node: &SyntaxNode) -> Option<Type> {
+/// The name of the type an `impl` block adds methods to.
+fn impl_type_name(block: &Impl) -> Option<String> {
+    block.syntax().children().find(|n| n.kind() == SyntaxKind::IdentNode).as_ref().and_then(find_ident_in)
+}
+
+/// `ty` with each generic parameter named in `bindings` replaced by its type argument.
+fn substitute(ty: &Type, bindings: &HashMap<String, Type>) -> Type {
+    match ty {
+        Type::Named(name) => bindings.get(name).cloned().unwrap_or_else(|| ty.clone()),
+        Type::Tuple(elems) => Type::Tuple(elems.iter().map(|ty| substitute(ty, bindings)).collect()),
+        Type::Array(elem, len) => Type::Array(Box::new(substitute(elem, bindings)), *len),
+        other => other.clone(),
+    }
+}
+
+fn find_type_in(node: &SyntaxNode, consts: &HashMap<String, i128>) -> Option<Type> {
     node.children()
         .find(|n| n.kind() == SyntaxKind::Type)
-        .map(|n| type_from_node(&n))
+        .map(|n| type_from_node(&n, consts))
 }
 
-fn type_from_node(node: &SyntaxNode) -> Type {
+/// The type a `Type` node names; `consts` gives the values of `const` names in array sizes.
+fn type_from_node(node: &SyntaxNode, consts: &HashMap<String, i128>) -> Type {
+    if is_paren_list(node) {
+        let elems: Vec<_> = node.children().filter(|n| n.kind() == SyntaxKind::Type).map(|n| type_from_node(&n, consts)).collect();
+        return match elems.len() {
+            0 => Type::Unit,
+            1 if !has_comma(node) => elems.into_iter().next().unwrap_or(Type::Unknown),
+            _ => Type::Tuple(elems),
+        };
+    }
+    if let Some(array) = node.children().find(|n| n.kind() == SyntaxKind::ArrayType) {
+        let elem = find_type_in(&array, consts).unwrap_or(Type::Unknown);
+        let len = array
+            .children()
+            .find(|n| is_expr_kind(n.kind()))
+            .and_then(|size| const_value(&size, consts))
+            .and_then(|n| usize::try_from(n).ok());
+        return Type::Array(Box::new(elem), len);
+    }
     let text = node.text().to_string();
     match text.trim() {
         "i64" => Type::I64,
         "i32" => Type::I32,
+        "u64" => Type::U64,
+        "i128" => Type::I128,
+        "u128" => Type::U128,
         "f64" => Type::F64,
+        "f32" => Type::F32,
         "bool" => Type::Bool,
         "string" => Type::String,
         "bytes" => Type::Bytes,
         "()" => Type::Unit,
+        "never" => Type::Never,
         other => Type::Named(other.to_string()),
     }
 }
 
-fn literal_type(node: &SyntaxNode) -> Type {
-    for el in node.children_with_tokens() {
-        if let SyntaxElement::Token(t) = el {
-            return match t.kind() {
-                SyntaxKind::Int => Type::I64,
-                SyntaxKind::Float => Type::F64,
-                SyntaxKind::String => Type::String,
-                SyntaxKind::Bytes => Type::Bytes,
-                SyntaxKind::KwTrue | SyntaxKind::KwFalse => Type::Bool,
-                _ => Type::Unknown,
-            };
+/// Values of the given `const` items whose initializers are constant expressions. A `const`
+/// may use one declared after it, so evaluation repeats until no new value is found.
+fn const_values(decls: &[SyntaxNode]) -> HashMap<String, i128> {
+    let mut values = HashMap::new();
+    loop {
+        let found: Vec<(String, i128)> = decls
+            .iter()
+            .filter_map(|decl| {
+                let name = find_ident_in(decl).filter(|name| !values.contains_key(name))?;
+                let init = find_expr_after_token(decl, SyntaxKind::Eq)?;
+                Some((name, const_value(&init, &values)?))
+            })
+            .collect();
+        if found.is_empty() {
+            return values;
         }
+        values.extend(found);
+    }
+}
+
+/// Evaluates an integer literal, a `const` name with a known value, or `+ - * /` on those.
+fn const_value(node: &SyntaxNode, consts: &HashMap<String, i128>) -> Option<i128> {
+    match node.kind() {
+        SyntaxKind::LiteralNode => match literal_value(node)? {
+            (SyntaxKind::Int, text) => int_literal_value(&text),
+            _ => None,
+        },
+        SyntaxKind::IdentNode => consts.get(&find_ident_in(node)?).copied(),
+        SyntaxKind::ParenExpr => const_value(&node.children().next()?, consts),
+        SyntaxKind::BinExpr => {
+            let (op, left, right) = bin_parts(node)?;
+            let (left, right) = (const_value(&left, consts)?, const_value(&right, consts)?);
+            match op {
+                SyntaxKind::Plus => left.checked_add(right),
+                SyntaxKind::Minus => left.checked_sub(right),
+                SyntaxKind::Star => left.checked_mul(right),
+                SyntaxKind::Slash => left.checked_div(right),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+fn literal_type(node: &SyntaxNode) -> Type {
+    match literal_value(node).map(|(kind, _)| kind) {
+        Some(SyntaxKind::Int) => Type::IntLiteral,
+        Some(SyntaxKind::Float) => Type::F64,
+        Some(SyntaxKind::String) => Type::String,
+        Some(SyntaxKind::Bytes) => Type::Bytes,
+        Some(SyntaxKind::KwTrue | SyntaxKind::KwFalse) => Type::Bool,
+        _ => Type::Unknown,
     }
-    Type::Unknown
+}
+
+fn is_zero_divisor(op: SyntaxKind, node: &SyntaxNode) -> bool {
+    if node.kind() != SyntaxKind::LiteralNode || !matches!(op, SyntaxKind::Slash | SyntaxKind::Percent) {
+        return false;
+    }
+    node.children_with_tokens().any(|el| match el {
+        SyntaxElement::Token(t) => {
+            match t.kind() {
+                SyntaxKind::Int => int_literal_value(t.text()) == Some(0),
+                SyntaxKind::Float => op == SyntaxKind::Percent && t.text().replace('_', "").parse::<f64>() == Ok(0.0),
+                _ => false,
+            }
+        }
+        _ => false,
+    })
 }
 
 fn is_numeric(ty: &Type) -> bool {
-    matches!(ty, Type::I64 | Type::I32 | Type::F64)
+    matches!(ty, Type::I64 | Type::I32 | Type::U64 | Type::I128 | Type::U128 | Type::F64 | Type::F32 | Type::IntLiteral)
+}
+
+fn is_integer(ty: &Type) -> bool {
+    matches!(ty, Type::I64 | Type::I32 | Type::U64 | Type::I128 | Type::U128 | Type::IntLiteral)
+}
+
+/// The concrete type of a binding: integer literals left unconstrained become `i64`.
+fn default_int(ty: Type) -> Type {
+    match ty {
+        Type::IntLiteral => Type::I64,
+        Type::Tuple(elems) => Type::Tuple(elems.into_iter().map(default_int).collect()),
+        Type::Array(elem, len) => Type::Array(Box::new(default_int(*elem)), len),
+        other => other,
+    }
 }
 
 fn type_compatible(a: &Type, b: &Type) -> bool {
     match (a, b) {
-        (Type::Unknown, _) | (_, Type::Unknown) => true,
+        (Type::Unknown, _) | (_, Type::Unknown) | (Type::Never, _) | (_, Type::Never) => true,
+        (Type::IntLiteral, other) | (other, Type::IntLiteral) => is_integer(other),
+        (Type::Tuple(xs), Type::Tuple(ys)) => xs.len() == ys.len() && xs.iter().zip(ys).all(|(x, y)| type_compatible(x, y)),
+        // `[T]` accepts an array of any length.
+        (Type::Array(x, n), Type::Array(y, m)) => type_compatible(x, y) && (n.is_none() || m.is_none() || n == m),
         _ => a == b,
     }
 }
 
+/// Whether a `value` can be stored where `target` is expected: a compatible type, or an
+/// implicit widening from `i32` to `i64` or `f32` to `f64`. Narrowing still needs a cast.
+/// Whether a `value` can be stored where `target` is expected. Only a diverging value fits a `never` slot.
+fn coercible(target: &Type, value: &Type) -> bool {
+    if *target == Type::Never {
+        return matches!(value, Type::Never | Type::Unknown | Type::Error);
+    }
+    matches!((target, value), (Type::I64, Type::I32) | (Type::F64, Type::F32)) || type_compatible(target, value)
+}
+
+fn is_ordering(op: SyntaxKind) -> bool {
+    matches!(op, SyntaxKind::Lt | SyntaxKind::Lte | SyntaxKind::Gt | SyntaxKind::Gte)
+}
+
+/// An unparenthesized `a < b` or `a == b`, as in the left operand of `a < b < c`.
+fn is_comparison(node: &SyntaxNode) -> bool {
+    node.kind() == SyntaxKind::BinExpr
+        && bin_parts(node).is_some_and(|(op, _, _)| is_ordering(op) || matches!(op, SyntaxKind::EqEq | SyntaxKind::Neq))
+}
+
 fn bin_parts(node: &SyntaxNode) -> Option<(SyntaxKind, SyntaxNode, SyntaxNode)> {
     let mut children = node.children();
     let left = children.next()?;
-    let right = children.nth(0)?;
+    let right = children.next()?;
     let mut op_kind = None;
     for el in node.children_with_tokens() {
         if let SyntaxElement::Token(t) = el {
@@ -441,17 +1779,31 @@ fn is_expr_kind(kind: SyntaxKind) -> bool {
     matches!(
         kind,
         SyntaxKind::BinExpr
+            | SyntaxKind::AssignExpr
             | SyntaxKind::CallExpr
             | SyntaxKind::MemberExpr
+            | SyntaxKind::PathExpr
+            | SyntaxKind::ClosureExpr
+            | SyntaxKind::StructLit
             | SyntaxKind::IfExpr
+            | SyntaxKind::IfLetExpr
             | SyntaxKind::MatchExpr
             | SyntaxKind::IdentNode
             | SyntaxKind::LiteralNode
             | SyntaxKind::ParenExpr
+            | SyntaxKind::TupleExpr
+            | SyntaxKind::ArrayExpr
+            | SyntaxKind::AwaitExpr
             | SyntaxKind::Block
+            | SyntaxKind::LabeledBlock
     )
 }
 
+/// The `'label` token directly under a `LabeledBlock` or `BreakStmt`.
+fn label_token(node: &SyntaxNode) -> Option<SyntaxToken> {
+    node.children_with_tokens().filter_map(|e| e.into_token()).find(|t| t.kind() == SyntaxKind::Label)
+}
+
 fn span_of(range: TextRange) -> Span {
     Span {
         start: range.start().into(),
@@ -0,0 +1,18 @@
+//! Exposes the wasmtime requirement from Cargo.toml as `WASMTIME_VERSION`, for `jalmt version --verbose`.
+
+use std::fs;
+
+fn main() {
+    println!("cargo:rerun-if-changed=Cargo.toml");
+    let manifest = fs::read_to_string("Cargo.toml").expect("read Cargo.toml");
+    // `wasmtime = "x"` or `wasmtime = { version = "x", ... }` under `[dependencies]`.
+    let version = manifest
+        .lines()
+        .skip_while(|line| line.trim() != "[dependencies]")
+        .skip(1)
+        .take_while(|line| !line.starts_with('['))
+        .find_map(|line| line.strip_prefix("wasmtime = "))
+        .and_then(|spec| spec.split('"').nth(1))
+        .expect("wasmtime dependency in Cargo.toml");
+    println!("cargo:rustc-env=WASMTIME_VERSION={version}");
+}
@@ -0,0 +1,57 @@
+//! The `v1` JSON shape of `jalmt check --json-format=v1`: parser, type, and effect
+//! diagnostics share one envelope, with stage-specific fields moved into `labels`.
+
+use jalm_parser::ParseError;
+use serde_json::{json, Value};
+
+/// One diagnostic in the v1 envelope; `position` orders the merged list by source offset.
+pub struct V1Diagnostic {
+    pub position: usize,
+    pub value: Value,
+}
+
+pub fn from_parse_error(source: &str, err: &ParseError) -> V1Diagnostic {
+    let related = err.related.iter().map(|span| json!({ "span": span_json(source, span.start, span.end) })).collect();
+    envelope(source, err.code.as_deref(), &err.message, (err.span.start, err.span.end), Vec::new(), related)
+}
+
+pub fn from_type_diagnostic(source: &str, diag: &jalm_typecheck::Diagnostic) -> V1Diagnostic {
+    let labels = [("expected", &diag.expected), ("actual", &diag.actual)]
+        .into_iter()
+        .filter_map(|(name, value)| value.as_ref().map(|value| label(name, value)))
+        .collect();
+    envelope(source, Some(&diag.code), &diag.message, (diag.span.start, diag.span.end), labels, Vec::new())
+}
+
+pub fn from_effect_diagnostic(source: &str, diag: &jalm_effectcheck::Diagnostic) -> V1Diagnostic {
+    let labels = vec![label("required", &diag.required)];
+    envelope(source, Some(&diag.code), &diag.message, (diag.span.start, diag.span.end), labels, Vec::new())
+}
+
+fn envelope(source: &str, code: Option<&str>, message: &str, (start, end): (usize, usize), labels: Vec<Value>, related: Vec<Value>) -> V1Diagnostic {
+    // Only `W` codes are warnings; a parse error without a code is still an error.
+    let severity = if code.is_some_and(|c| c.starts_with('W')) { "warning" } else { "error" };
+    V1Diagnostic {
+        position: start,
+        value: json!({
+            "code": code,
+            "severity": severity,
+            "message": message,
+            "span": span_json(source, start, end),
+            "labels": labels,
+            "related": related,
+        }),
+    }
+}
+
+fn label(name: &str, value: &str) -> Value {
+    json!({ "name": name, "value": value })
+}
+
+/// `{start, end, line, col}`: byte offsets, plus the 1-based line and character column of `start`.
+fn span_json(source: &str, start: usize, end: usize) -> Value {
+    let before = &source[..start.min(source.len())];
+    let line = before.matches('\n').count() + 1;
+    let col = before.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+    json!({ "start": start, "end": end, "line": line, "col": col })
+}
@@ -0,0 +1,626 @@
+//! Long-form explanations for diagnostic codes, printed by `jalmt explain <code>`.
+
+/// `(code, explanation)` for every code reported by the parser, checkers, and codegen.
+const EXPLANATIONS: &[(&str, &str)] = &[
+    (
+        "E0001",
+        "undefined variable
+
+A name is used that is not bound by a parameter, `let`, or `use` in scope.
+
+    fn f() -> i64 { y }
+
+Fix: declare the variable before using it, or correct the spelling.",
+    ),
+    (
+        "E0003",
+        "type mismatch
+
+A value's type differs from the type its context requires: a `let` annotation,
+an assignment target, a parameter default, or the other operand of a binary operator.
+
+    let flag: bool = 1;
+
+Fix: change the annotation or the value so both sides have the same type.",
+    ),
+    (
+        "E0004",
+        "return type mismatch
+
+A `return` value or a function body's final expression does not match the declared
+return type.
+
+    fn f() -> bool { 1 }
+
+Fix: return a value of the declared type, or change the `->` type.",
+    ),
+    (
+        "E0005",
+        "non-bool condition
+
+The condition of an `if` must be a `bool`.
+
+    if 1 { 2 } else { 3 }
+
+Fix: compare explicitly, e.g. `if x != 0 { ... }`.",
+    ),
+    (
+        "E0006",
+        "if branches differ
+
+Both branches of an `if` expression must produce the same type.
+
+    if c { 1 } else { true }
+
+Fix: make both branches return the same type.",
+    ),
+    (
+        "E0007",
+        "match arms differ
+
+Every arm of a `match` must produce the same type.
+
+    match x { 0 => 1, _ => false, }
+
+Fix: make all arms return the same type.",
+    ),
+    (
+        "E0032",
+        "division by zero
+
+A literal zero is used as the divisor of `/` or `%`.
+
+    let q = x / 0;
+
+Fix: divide by a non-zero value, or guard the division with a check.",
+    ),
+    (
+        "E0033",
+        "assignment in condition
+
+An `if` or `match` condition contains `=`, which is assignment, not comparison.
+
+    if x = 1 { ... }
+
+Fix: use `==` to compare.",
+    ),
+    (
+        "E0034",
+        "unterminated block comment
+
+A `/*` comment is never closed, so the rest of the file is part of the comment.
+
+    /* todo
+    fn main() {}
+
+Fix: close the comment with `*/`.",
+    ),
+    (
+        "E0035",
+        "tuple arity mismatch
+
+A tuple pattern has a different number of elements than the tuple it destructures.
+
+    let (a, b) = (1, 2, 3);
+
+Fix: list one sub-pattern per tuple element.",
+    ),
+    (
+        "E0036",
+        "chained comparison
+
+Comparisons do not chain: `a < b < c` would compare the `bool` result of `a < b` with `c`.
+
+    if lo < x < hi { ... }
+
+Fix: combine two comparisons with `&&`, e.g. `lo < x && x < hi`.",
+    ),
+    (
+        "E0037",
+        "pattern type mismatch
+
+A literal `match` pattern has a different type than the value being matched.
+
+    match n { \"hi\" => 1, _ => 0, }
+
+Fix: use patterns of the scrutinee's type, e.g. integer literals when matching an `i64`.",
+    ),
+    (
+        "E0038",
+        "returning a value from a unit function
+
+A function without `-> T` returns `()`, but a `return` gives it a value.
+
+    fn f() { return 5; }
+
+Fix: declare the return type, e.g. `fn f() -> i64`, or use a bare `return;`.",
+    ),
+    (
+        "E0039",
+        "unknown label
+
+A `break 'label` names a label that no enclosing block declares.
+
+    'outer: { break 'inner; }
+
+Fix: break to the label of an enclosing block, or correct its spelling.",
+    ),
+    (
+        "E0040",
+        "use before definition
+
+A name is used before the `let` that binds it later in the same or an enclosing block.
+
+    let y = z;
+    let z = 2;
+
+Fix: move the `let` above its first use.",
+    ),
+    (
+        "E0041",
+        "array length mismatch
+
+An array literal has a different number of elements than the fixed-size array type it is
+given to.
+
+    let a: [i64; 2] = [1, 2, 3];
+
+Fix: change the length in the type, use `[T]` for any length, or add or remove elements.",
+    ),
+    (
+        "E0042",
+        "array size must be a constant
+
+The `N` in `[T; N]` must be known at compile time: an integer literal, a `const` name, or
+arithmetic on those.
+
+    let n = 3;
+    let a: [i64; n] = [1, 2, 3];
+
+Fix: declare the size as a `const`, e.g. `const N: i64 = 3;`, or use `[T]` for any length.",
+    ),
+    (
+        "E0043",
+        "ambiguous struct literal in condition
+
+In an `if` or `match` head, `{` opens the body, so `Name { field: ... }` there would be read
+as a name followed by a block.
+
+    if Point { x: 1 }.valid { ... }
+
+Fix: wrap the struct literal in parentheses, e.g. `if (Point { x: 1 }).valid { ... }`.",
+    ),
+    (
+        "E0044",
+        "duplicate discriminant
+
+Two variants of an enum have the same value. A variant without `= N` takes the previous
+variant's value plus one, so an implicit value can collide with a later explicit one.
+
+    enum Color { Red = 1; Green; Blue = 2; }
+
+Fix: give each variant a distinct value.",
+    ),
+    (
+        "E0045",
+        "missing fields in struct initializer
+
+A struct literal without a `..base` spread leaves some declared fields unset.
+
+    struct Point { x: i64; y: i64; }
+    let p = Point { x: 1 };
+
+Fix: give every field, or copy the rest from another value with `..base`.",
+    ),
+    (
+        "E0046",
+        "cyclic type alias
+
+A `type` alias expands, directly or through other aliases, to a type that contains itself,
+so it never reaches a concrete type.
+
+    type A = B;
+    type B = (A, i64);
+
+Fix: break the cycle by naming a concrete type in one of the aliases.",
+    ),
+    (
+        "E0047",
+        "`pub` not allowed on a local item
+
+A declaration inside a function body, such as a `use`, is visible only in its block, so a
+visibility modifier on it has no effect.
+
+    fn f() -> i64 {
+        pub use math::abs;
+        abs(-1)
+    }
+
+Fix: drop the `pub`, or move the declaration to the top level of the file.",
+    ),
+    (
+        "E0048",
+        "no method on type
+
+A `.name(...)` call on a struct value must name a method, one taking `self`, in an `impl`
+block for that struct. A field of the same name does not count.
+
+    struct Point { x: i64; y: i64; }
+    impl Point {
+        fn norm(self) -> i64 { 0 }
+    }
+    fn f(p: Point) -> i64 { p.distance() }
+
+Fix: add the method to the type's `impl` block, or call one that exists.",
+    ),
+    (
+        "E0049",
+        "invalid byte escape
+
+A byte string contains an escape other than `\\xNN`, `\\\\`, `\\\"`, `\\n`, `\\t`, or `\\0`.
+
+    let b = b\"\\q\";
+
+Fix: use one of the supported escapes, e.g. `\\x71`.",
+    ),
+    (
+        "E0050",
+        "`let ... else` block does not diverge
+
+The `else` block of a `let ... else` runs when the pattern does not match, so it has no
+bindings to continue with and must leave the function or block.
+
+    let Some(x) = opt else { 0 };
+
+Fix: end the block with `return`, `break`, or a call such as `panic()`.",
+    ),
+    (
+        "E0051",
+        "argument count mismatch
+
+A method call passes one argument for each of the method's parameters after `self`; the
+receiver before the `.` is the `self` argument. A generic call `f::<T>(...)` passes one for
+each of `f`'s parameters, except trailing ones with defaults.
+
+    impl Point {
+        fn scale(self, by: i64) -> i64 { self.x * by }
+    }
+    fn f(p: Point) -> i64 { p.scale(2, 3) }
+
+Fix: pass exactly the parameters the method or function declares (after `self`).",
+    ),
+    (
+        "E0052",
+        "discriminant out of range
+
+Enum discriminants are stored as `i32`, so every variant's value, including one implied by
+the previous variant plus one, must lie between `-2147483648` and `2147483647`.
+
+    enum Color { Red = 3000000000; Green; }
+
+Fix: pick discriminants that fit in an `i32`.",
+    ),
+    (
+        "E0053",
+        "wrong number of type arguments
+
+A call `f::<A, B>(...)` gives one type argument for each of `f`'s type parameters.
+
+    fn id<T>(x: T) -> T { x }
+    let y = id::<i64, bool>(1);
+
+Fix: give exactly the type parameters `f` declares, e.g. `id::<i64>(1)`.",
+    ),
+    (
+        "E0054",
+        "type arguments on non-generic function
+
+A call names type arguments with `::<...>`, but the function has no type parameters to
+bind them to.
+
+    fn k(x: i64) -> i64 { x }
+    let y = k::<i64>(1);
+
+Fix: drop the `::<...>`, or declare the type parameters on the function.",
+    ),
+    (
+        "E0055",
+        "break outside loop
+
+A `break` without a label has nothing to leave: it is not inside a labeled block, and the
+language has no loops yet.
+
+    fn f() { break; }
+
+Fix: remove the `break`, or put it in a labeled block and name it: `'done: { break 'done; }`.",
+    ),
+    (
+        "E0056",
+        "unknown field in struct initializer
+
+A struct literal sets a field that the struct does not declare.
+
+    struct Point { x: i64; y: i64; }
+    let p = Point { x: 1, y: 2, w: 3 };
+
+Fix: remove the field, or correct its name to one of the declared fields.",
+    ),
+    (
+        "E1001",
+        "undeclared effect
+
+A function performs an effect that its `!{...}` set does not declare.
+
+    fn load() -> i64 { fs::read(1) }
+
+Fix: add the effect to the signature, e.g. `fn load() -> i64 !{fs}`.",
+    ),
+    (
+        "E1006",
+        "effect used in closure not declared by enclosing function
+
+Closures run with the effects of the function that contains them.
+
+    fn f() -> i64 { let load = |p| fs::read(p); 1 }
+
+Fix: declare the effect on the enclosing function.",
+    ),
+    (
+        "E1007",
+        "cyclic effect alias
+
+An `effect set` alias refers back to itself, directly or through other aliases.
+
+    effect set a = {io, b};
+    effect set b = {a};
+
+Fix: remove the cycle so every alias expands to built-in effects.",
+    ),
+    (
+        "E1008",
+        "unknown effect namespace
+
+Reported under `jalmt check --strict-effects`: a path `ns::member` names a namespace that is
+not an effect namespace (`fs`, `net`, `http`, `time`, `rand`, `log`, `ffi`), `std`, an item
+in the file, or an import.
+
+    fn f() !{net} { ner::connect(1); }
+
+Fix: correct the namespace, or `use` the module that provides it.",
+    ),
+    (
+        "E1009",
+        "await requires async context
+
+`await` suspends the running task, so it is only allowed in an `async fn`, which carries the
+`async` capability.
+
+    fn load() -> i64 { await fetch() }
+
+Fix: mark the function `async fn`, or call it from one.",
+    ),
+    (
+        "E2000",
+        "parse error during codegen
+
+The source has syntax errors, so no module was generated.
+
+Fix: run `jalmt parse <file>` and fix the reported errors.",
+    ),
+    (
+        "E2001",
+        "no functions found
+
+The program has no `fn` items to compile.
+
+Fix: define at least one function, usually `fn main() -> i64`.",
+    ),
+    (
+        "E2002",
+        "unsupported parameter type
+
+The wasm backend only lowers `i64`, `i32`, `f64`, `f32`, and `bool` parameters.
+
+    fn f(s: string) -> i64 { 0 }
+
+Fix: use a supported type for parameters of compiled functions.",
+    ),
+    (
+        "E2003",
+        "unsupported return type
+
+The wasm backend only lowers `i64`, `i32`, `f64`, `f32`, and `bool` returns.
+
+    fn f() -> string { \"\" }
+
+Fix: return a supported type, or omit `->` for a unit function.",
+    ),
+    (
+        "E2004",
+        "unknown local in codegen
+
+An identifier could not be mapped to a wasm local, e.g. a `let` inside an `if` body, or a
+path `a::b` is not a variant of an enum whose variants are all fieldless.
+
+Fix: declare the local at the top level of the function body.",
+    ),
+    (
+        "E2005",
+        "unknown function in codegen
+
+A call refers to a function that is not defined or imported in this file.
+
+    fn main() -> i64 { return missing(); }
+
+Fix: define the function, or declare it with `@extern(\"module\", \"field\")`.",
+    ),
+    (
+        "E2006",
+        "bodiless function without @extern
+
+A function ends in `;` but is not an import.
+
+    fn nope() -> i64;
+
+Fix: add a body, or mark it `@extern(\"module\", \"field\")`.",
+    ),
+    (
+        "E2007",
+        "128-bit integers not supported by the wasm backend
+
+`i128` and `u128` type-check but cannot be compiled yet.
+
+Fix: use `i64` in code that is compiled to wasm.",
+    ),
+    (
+        "E2008",
+        "wrong number of arguments
+
+A call passes more arguments than the function declares, or leaves out a parameter that
+has no default. The builtins `memcpy(dst, src, len)` and `memset(dst, value, len)` each
+take exactly three operands; `assert(cond)` takes one.
+
+    memcpy(dst, src);
+
+Fix: pass every parameter that has no default, and no more than the function declares.",
+    ),
+    (
+        "E2009",
+        "host calls disallowed by @no_host
+
+A module marked `@no_host;` calls an effectful host builtin such as `log::` or `http::`.
+
+    @no_host;
+    fn main() { log::info(1); }
+
+Fix: remove the host call, or drop `@no_host` if the module may import host functions.",
+    ),
+    (
+        "E2010",
+        "arrays not yet supported
+
+The wasm backend does not lower array types or array literals yet; they type-check only.
+
+    let a: [i64; 3] = [1, 2, 3];
+
+Fix: keep array code out of programs compiled to wasm for now.",
+    ),
+    (
+        "E2011",
+        "unsupported f64 remainder
+
+Wasm has no floating-point remainder instruction, so `%` on `f64` operands is not compiled.
+
+    fn f(a: f64, b: f64) -> f64 { a % b }
+
+Fix: use `%` on integer operands only.",
+    ),
+    (
+        "E2012",
+        "tuple value bound to a single name
+
+The wasm backend keeps a tuple's elements in separate locals, so a tuple value must be
+destructured where it is bound.
+
+    let t = divmod(17, 5);
+
+Fix: bind the elements, e.g. `let (q, r) = divmod(17, 5);`.",
+    ),
+    (
+        "E2013",
+        "unsupported expression
+
+The wasm backend has no lowering for this kind of expression yet: closures, `if let`,
+labeled blocks, and struct literals are checked but cannot be compiled.
+
+    fn main() -> i64 { if let (a, b) = (1, 2) { a + b } else { 0 } }
+
+Fix: rewrite the expression with `let`, `if`, and calls, or keep it out of compiled code.",
+    ),
+    (
+        "E2014",
+        "struct locals are not supported
+
+The wasm backend keeps a struct only as a parameter, one local per field, so a `let` bound
+to a struct literal cannot be compiled. Field reads and method calls on that local are not
+reported separately.
+
+    fn main() -> i64 { let p = P { x: 1, y: 2 }; p.sum() }
+
+Fix: pass the struct to a function as a parameter, or bind its fields to separate locals.",
+    ),
+    (
+        "E2015",
+        "generic functions cannot be exported
+
+Every `f::<T>` instantiation of a generic function becomes its own wasm function, so no
+single function could take the `@export` name.
+
+    @export(\"id\")
+    fn id<T>(x: T) -> T { x }
+
+Fix: export a non-generic wrapper that calls the instantiation you need, e.g.
+`@export(\"id\") fn id_i64(x: i64) -> i64 { id::<i64>(x) }`.",
+    ),
+    (
+        "W0004",
+        "unneeded `mut`
+
+A `mut` parameter or `let mut` binding is never assigned.
+
+    fn f(mut a: i64) -> i64 { a }
+
+Fix: remove `mut`.",
+    ),
+    (
+        "W0005",
+        "local binding shadows parameter
+
+A `let` at the top level of a function body reuses a parameter's name, hiding it.
+
+    fn f(x: i64) -> i64 { let x = x + 1; x }
+
+Fix: pick a different name; shadowing inside a nested block is not reported.",
+    ),
+    (
+        "W0006",
+        "unused import
+
+A name (or alias) brought in by `use` is never referenced in the file or block that imports it.
+
+    use std::math::max as m;
+
+Fix: remove the import, or use the name.",
+    ),
+    (
+        "W0007",
+        "explicit call to `main`
+
+`main` is the entry point the host runs; calling it from the program starts the whole
+program again and usually recurses without end.
+
+    fn retry() -> i64 { main() }
+
+Fix: move the shared logic into its own function and call that from `main` and elsewhere.",
+    ),
+    (
+        "W0008",
+        "use of deprecated function
+
+The called function is marked `@deprecated`; its note, if any, is shown after the message.
+
+    @deprecated(\"use g instead\")
+    fn f() -> i64 { 1 }
+    fn h() -> i64 { f() }
+
+Fix: switch to the replacement the note suggests.",
+    ),
+];
+
+/// The explanation for `code` (case-insensitive), if it is a known diagnostic code.
+pub fn explain(code: &str) -> Option<&'static str> {
+    EXPLANATIONS
+        .iter()
+        .find(|(known, _)| known.eq_ignore_ascii_case(code))
+        .map(|(_, text)| *text)
+}
@@ -1,15 +1,32 @@
-use clap::{Parser, Subcommand};
-use jalm_effectcheck::check as check_effects;
-use jalm_formatter::format_source;
-use jalm_parser::parse;
-use jalm_typecheck::check;
+mod diagnostics;
+mod explain;
+mod repl;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use jalm_codegen::CompileOptions;
+use jalm_effectcheck::CheckOptions;
+use jalm_formatter::{format_source_with_options, FormatOptions};
+use jalm_parser::{parse, parse_with_features, Parse};
+use jalm_syntax::{dump_tree, dump_tree_with_spans};
+use jalm_typecheck::return_type;
 use serde_json::json;
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::time::Instant;
+use wasmtime::Val;
+
+/// `wasmtime` requirement the runner is built against, read from Cargo.toml by build.rs.
+const WASMTIME_VERSION: &str = env!("WASMTIME_VERSION");
 
 #[derive(Parser)]
 #[command(name = "jalmt", version, about = "JaLM toolchain")]
 struct Cli {
+    /// Print nothing but errors; success or failure shows in the exit code.
+    #[arg(long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+    /// Also print each file processed and how long every stage took, to stderr.
+    #[arg(long, global = true)]
+    verbose: bool,
     #[command(subcommand)]
     command: Command,
 }
@@ -17,24 +34,159 @@ struct Cli {
 #[derive(Subcommand)]
 enum Command {
     Parse { file: PathBuf },
-    Fmt { file: PathBuf },
-    Check { file: PathBuf },
+    /// Print the syntax tree.
+    Ast {
+        file: PathBuf,
+        /// Append `@start..end` byte offsets to every node and token.
+        #[arg(long)]
+        spans: bool,
+    },
+    Fmt {
+        file: PathBuf,
+        /// Where to write the formatted source.
+        #[arg(long, value_enum, default_value_t = Emit::Files)]
+        emit: Emit,
+        /// Keep a block with a single short statement or expression on one line.
+        #[arg(long)]
+        compact_blocks: bool,
+    },
+    Check {
+        file: PathBuf,
+        /// Report only the first N diagnostics by source position.
+        #[arg(long)]
+        max_errors: Option<usize>,
+        /// Report `ns::` paths whose namespace is not a known effect, item, or import (E1008).
+        #[arg(long)]
+        strict_effects: bool,
+        /// Shape of the JSON output.
+        #[arg(long, value_enum, default_value_t = JsonFormat::V0)]
+        json_format: JsonFormat,
+        #[command(flatten)]
+        features: Features,
+    },
     New { name: String, #[arg(long)] dir: Option<PathBuf> },
-    Build { #[arg(long)] dir: Option<PathBuf> },
-    Test { #[arg(long)] dir: Option<PathBuf> },
-    Run { #[arg(long)] dir: Option<PathBuf> },
+    Build {
+        #[arg(long)]
+        dir: Option<PathBuf>,
+        /// What to produce from `src/main.jalm`.
+        #[arg(long, value_enum, default_value_t = Target::Wasm32)]
+        target: Target,
+        /// Embed a `jalm.sourcemap` section mapping instructions to source spans.
+        #[arg(long)]
+        debug: bool,
+        /// Check integer divisors for zero and call the imported `env.jalm_panic` instead of trapping.
+        #[arg(long)]
+        checked_arithmetic: bool,
+        #[command(flatten)]
+        features: Features,
+    },
+    Test {
+        #[arg(long)]
+        dir: Option<PathBuf>,
+        #[command(flatten)]
+        features: Features,
+    },
+    Run {
+        #[arg(long)]
+        dir: Option<PathBuf>,
+        #[command(flatten)]
+        features: Features,
+        /// Arguments after `--`; their count is passed to `main(argc: i64)` or `main(argc: i32)`.
+        #[arg(last = true)]
+        args: Vec<String>,
+    },
+    /// Read expressions and statements line by line, printing each expression's value.
+    Repl,
+    /// Explain a diagnostic code such as `E0004`.
+    Explain { code: String },
+    /// Print the toolchain version; with `--verbose`, also every compiler component and backend library.
+    Version,
+}
+
+/// `--features a,b`, shared by every command that checks or compiles source.
+#[derive(Args)]
+struct Features {
+    /// Features to enable; items marked `@cfg(feature = "name")` are dropped unless listed.
+    #[arg(long = "features", value_delimiter = ',')]
+    enabled: Vec<String>,
+}
+
+/// How much a command prints besides its result, from `--quiet` and `--verbose`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Verbosity {
+    Quiet,
+    Normal,
+    Verbose,
+}
+
+impl Verbosity {
+    /// Under `--verbose`, notes that `path` is being processed.
+    fn file(self, path: &Path) {
+        if self == Verbosity::Verbose {
+            eprintln!("file: {}", path.display());
+        }
+    }
+
+    /// Runs one stage of a command; under `--verbose`, prints how long it took.
+    fn stage<T>(self, name: &str, run: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let out = run();
+        if self == Verbosity::Verbose {
+            eprintln!("{name}: {:.2?}", start.elapsed());
+        }
+        out
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Emit {
+    /// Rewrite the file in place.
+    Files,
+    /// Print the formatted source and leave the file untouched.
+    Stdout,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum JsonFormat {
+    /// Separate `type_diagnostics` and `effect_diagnostics` lists, each in its own shape.
+    V0,
+    /// One `diagnostics` list from every stage, in a shared envelope.
+    V1,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Target {
+    /// Compile to `target/wasm32/main.wasm`.
+    Wasm32,
+    /// Parse and check only; nothing is written.
+    Check,
 }
 
 fn main() {
     let cli = Cli::parse();
+    let verbosity = match (cli.quiet, cli.verbose) {
+        (true, _) => Verbosity::Quiet,
+        (_, true) => Verbosity::Verbose,
+        _ => Verbosity::Normal,
+    };
     let result = match cli.command {
-        Command::Parse { file } => cmd_parse(&file),
-        Command::Fmt { file } => cmd_fmt(&file),
-        Command::Check { file } => cmd_check(&file),
+        Command::Parse { file } => cmd_parse(&file, verbosity),
+        Command::Ast { file, spans } => cmd_ast(&file, spans),
+        Command::Fmt { file, emit, compact_blocks } => {
+            cmd_fmt(&file, emit, &FormatOptions { compact_blocks, ..FormatOptions::default() })
+        }
+        Command::Check { file, max_errors, strict_effects, json_format, features } => {
+            cmd_check(&file, max_errors, &CheckOptions { strict_effects }, json_format, &features.enabled, verbosity)
+        }
         Command::New { name, dir } => cmd_new(&name, dir.as_deref()),
-        Command::Build { dir } => cmd_build(dir.as_deref()),
-        Command::Test { dir } => cmd_test(dir.as_deref()),
-        Command::Run { dir } => cmd_run(dir.as_deref()),
+        Command::Build { dir, target, debug, checked_arithmetic, features } => {
+            cmd_build(dir.as_deref(), target, &CompileOptions { debug, checked_arithmetic, features: features.enabled }, verbosity)
+        }
+        Command::Test { dir, features } => cmd_test(dir.as_deref(), &features.enabled, verbosity),
+        Command::Run { dir, args, features } => cmd_run(dir.as_deref(), &args, &features.enabled, verbosity),
+        Command::Repl => repl::run(),
+        Command::Explain { code } => cmd_explain(&code),
+        Command::Version => cmd_version(verbosity == Verbosity::Verbose),
     };
 
     if let Err(err) = result {
@@ -43,38 +195,131 @@ fn main() {
     }
 }
 
-fn cmd_parse(path: &Path) -> Result<(), String> {
+/// Prints the parse errors as JSON; under `--quiet`, only when there are any.
+fn cmd_parse(path: &Path, verbosity: Verbosity) -> Result<(), String> {
+    verbosity.file(path);
     let source = read_file(path)?;
-    let parsed = parse(&source);
+    let parsed = verbosity.stage("parse", || parse(&source));
+    if verbosity == Verbosity::Quiet && parsed.is_ok() {
+        return Ok(());
+    }
     let diag = json!({
-        "errors": parsed.errors,
+        "errors": parsed.errors(),
     });
     println!("{}", serde_json::to_string_pretty(&diag).unwrap());
     Ok(())
 }
 
-fn cmd_fmt(path: &Path) -> Result<(), String> {
+fn cmd_explain(code: &str) -> Result<(), String> {
+    let text = explain::explain(code).ok_or_else(|| format!("unknown diagnostic code `{code}`"))?;
+    println!("{}: {text}", code.to_ascii_uppercase());
+    Ok(())
+}
+
+fn cmd_version(verbose: bool) -> Result<(), String> {
+    println!("jalmt {}", env!("CARGO_PKG_VERSION"));
+    if verbose {
+        let components = [
+            ("jalm_syntax", jalm_syntax::VERSION),
+            ("jalm_parser", jalm_parser::VERSION),
+            ("jalm_ast", jalm_ast::VERSION),
+            ("jalm_formatter", jalm_formatter::VERSION),
+            ("jalm_typecheck", jalm_typecheck::VERSION),
+            ("jalm_effectcheck", jalm_effectcheck::VERSION),
+            ("jalm_codegen", jalm_codegen::VERSION),
+            ("wasm-encoder", jalm_codegen::WASM_ENCODER_VERSION),
+            ("wasmtime", WASMTIME_VERSION),
+        ];
+        for (name, version) in components {
+            println!("  {name} {version}");
+        }
+        println!("  wasm features {}", jalm_codegen::TARGET_FEATURES.join(", "));
+    }
+    Ok(())
+}
+
+fn cmd_ast(path: &Path, spans: bool) -> Result<(), String> {
     let source = read_file(path)?;
-    match format_source(&source) {
+    let root = parse(&source).syntax();
+    let tree = if spans { dump_tree_with_spans(&root) } else { dump_tree(&root) };
+    print!("{tree}");
+    Ok(())
+}
+
+fn cmd_fmt(path: &Path, emit: Emit, options: &FormatOptions) -> Result<(), String> {
+    let source = read_file(path)?;
+    match format_source_with_options(&source, options) {
         Ok(formatted) => {
-            if formatted != source {
+            if emit == Emit::Stdout {
+                println!("{formatted}");
+            } else if formatted != source {
                 fs::write(path, formatted).map_err(|e| format!("failed to write {}: {e}", path.display()))?;
             }
             Ok(())
         }
-        Err(err) => Err(format!("format error: {err:?}")),
+        Err(err) => Err(format!("format error: {err}")),
     }
 }
 
-fn cmd_check(path: &Path) -> Result<(), String> {
+/// Prints the diagnostics as JSON. Under `--quiet`, a file without errors prints nothing, and
+/// one with errors fails the command after printing them; warnings alone do not fail it.
+fn cmd_check(
+    path: &Path,
+    max_errors: Option<usize>,
+    options: &CheckOptions,
+    json_format: JsonFormat,
+    features: &[String],
+    verbosity: Verbosity,
+) -> Result<(), String> {
+    verbosity.file(path);
     let source = read_file(path)?;
-    let tc = check(&source);
-    let ec = check_effects(&source);
-    let diag = json!({
-        "type_diagnostics": tc.diagnostics,
-        "effect_diagnostics": ec.diagnostics,
-    });
+    let parsed = verbosity.stage("parse", || parse_with_features(&source, features));
+    let mut tc = verbosity.stage("typecheck", || jalm_typecheck::check_parsed(&parsed));
+    let mut ec = verbosity.stage("effectcheck", || jalm_effectcheck::check_parsed_with_options(&parsed, options));
+    let failed = !parsed.is_ok() || tc.has_errors() || !ec.diagnostics.is_empty();
+    if verbosity == Verbosity::Quiet && !failed {
+        return Ok(());
+    }
+    let diag = if json_format == JsonFormat::V1 {
+        let mut all: Vec<_> = parsed.errors().iter().map(|e| diagnostics::from_parse_error(&source, e)).collect();
+        all.extend(tc.diagnostics.iter().map(|d| diagnostics::from_type_diagnostic(&source, d)));
+        all.extend(ec.diagnostics.iter().map(|d| diagnostics::from_effect_diagnostic(&source, d)));
+        all.sort_by_key(|d| d.position);
+        let omitted = max_errors.map_or(0, |max| all.len().saturating_sub(max));
+        all.truncate(all.len() - omitted);
+        let mut diag = json!({
+            "version": 1,
+            "diagnostics": all.into_iter().map(|d| d.value).collect::<Vec<_>>(),
+        });
+        if omitted > 0 {
+            diag["note"] = json!(format!("... and {omitted} more"));
+        }
+        diag
+    } else {
+        let mut omitted = 0;
+        if let Some(max) = max_errors {
+            // Both lists are sorted by position, so the first `max` of the merged order are a prefix of each.
+            let mut starts: Vec<(usize, bool)> = tc.diagnostics.iter().map(|d| (d.span.start, false)).collect();
+            starts.extend(ec.diagnostics.iter().map(|d| (d.span.start, true)));
+            starts.sort_unstable();
+            omitted = starts.len().saturating_sub(max);
+            let effects_kept = starts.iter().take(max).filter(|(_, effect)| *effect).count();
+            tc.diagnostics.truncate(max.min(starts.len()) - effects_kept);
+            ec.diagnostics.truncate(effects_kept);
+        }
+        let mut diag = json!({
+            "type_diagnostics": tc.diagnostics,
+            "effect_diagnostics": ec.diagnostics,
+        });
+        if omitted > 0 {
+            diag["note"] = json!(format!("... and {omitted} more"));
+        }
+        diag
+    };
     println!("{}", serde_json::to_string_pretty(&diag).unwrap());
+    if verbosity == Verbosity::Quiet {
+        return Err(format!("check failed for {}", path.display()));
+    }
     Ok(())
 }
 
@@ -115,22 +360,25 @@ fn cmd_new(name: &str, dir: Option<&Path>) -> Result<(), String> {
     Ok(())
 }
 
-fn cmd_build(dir: Option<&Path>) -> Result<(), String> {
+fn cmd_build(dir: Option<&Path>, target: Target, options: &CompileOptions, verbosity: Verbosity) -> Result<(), String> {
     let root = dir.unwrap_or_else(|| Path::new("."));
-    let source = read_file(&root.join("src/main.jalm"))?;
-    let parsed = parse(&source);
-    if !parsed.errors.is_empty() {
-        return Err("parse errors in src/main.jalm".to_string());
-    }
-    let tc = check(&source);
-    let ec = check_effects(&source);
-    if !tc.diagnostics.is_empty() || !ec.diagnostics.is_empty() {
-        return Err("check failed for src/main.jalm".to_string());
+    let path = root.join("src/main.jalm");
+    verbosity.file(&path);
+    let source = read_file(&path)?;
+    let parsed = check_source(&source, "src/main.jalm", &options.features, verbosity)?;
+    match target {
+        Target::Check => Ok(()),
+        Target::Wasm32 => {
+            let wasm = verbosity.stage("codegen", || compile_main(&parsed, options))?;
+            let out_dir = root.join("target/wasm32");
+            fs::create_dir_all(&out_dir).map_err(|e| format!("failed to create {}: {e}", out_dir.display()))?;
+            let out = out_dir.join("main.wasm");
+            fs::write(&out, wasm).map_err(|e| format!("failed to write {}: {e}", out.display()))
+        }
     }
-    Ok(())
 }
 
-fn cmd_test(dir: Option<&Path>) -> Result<(), String> {
+fn cmd_test(dir: Option<&Path>, features: &[String], verbosity: Verbosity) -> Result<(), String> {
     let root = dir.unwrap_or_else(|| Path::new("."));
     let entries = fs::read_dir(root.join("tests")).map_err(|e| format!("read tests: {e}"))?;
     for entry in entries {
@@ -139,36 +387,91 @@ fn cmd_test(dir: Option<&Path>) -> Result<(), String> {
         if path.extension().and_then(|s| s.to_str()) != Some("jalm") {
             continue;
         }
+        verbosity.file(&path);
         let source = read_file(&path)?;
-        let parsed = parse(&source);
-        if !parsed.errors.is_empty() {
-            return Err(format!("parse errors in {}", path.display()));
-        }
-        let tc = check(&source);
-        let ec = check_effects(&source);
-        if !tc.diagnostics.is_empty() || !ec.diagnostics.is_empty() {
-            return Err(format!("check failed for {}", path.display()));
-        }
+        check_source(&source, &path.display().to_string(), features, verbosity)?;
     }
     Ok(())
 }
 
-fn cmd_run(dir: Option<&Path>) -> Result<(), String> {
+/// Runs `main` and prints the value it returns as its declared type, unless `--quiet`.
+fn cmd_run(dir: Option<&Path>, args: &[String], features: &[String], verbosity: Verbosity) -> Result<(), String> {
     let root = dir.unwrap_or_else(|| Path::new("."));
-    let source = read_file(&root.join("src/main.jalm"))?;
-    let parsed = parse(&source);
-    if !parsed.errors.is_empty() {
-        return Err("parse errors in src/main.jalm".to_string());
-    }
-    let tc = check(&source);
-    let ec = check_effects(&source);
-    if !tc.diagnostics.is_empty() || !ec.diagnostics.is_empty() {
-        return Err("check failed for src/main.jalm".to_string());
+    let path = root.join("src/main.jalm");
+    verbosity.file(&path);
+    let source = read_file(&path)?;
+    let parsed = check_source(&source, "src/main.jalm", features, verbosity)?;
+    let ty = return_type(&parsed, "main").unwrap_or_default();
+    let options = CompileOptions { features: features.to_vec(), ..CompileOptions::default() };
+    let wasm = verbosity.stage("codegen", || compile_main(&parsed, &options))?;
+    let value = verbosity.stage("run", || run_wasm_main(&wasm, args))?;
+    if let Some(value) = value.and_then(|v| show_value(&v, &ty)).filter(|_| verbosity != Verbosity::Quiet) {
+        println!("{value}");
     }
-    println!("run: ok (no runtime yet)");
     Ok(())
 }
 
+/// Parses and checks `source` with `features` enabled, returning the parse; `label` names the
+/// file in the error.
+fn check_source(source: &str, label: &str, features: &[String], verbosity: Verbosity) -> Result<Parse, String> {
+    let parsed = verbosity.stage("parse", || parse_with_features(source, features));
+    if !parsed.is_ok() {
+        return Err(format!("parse errors in {label}"));
+    }
+    let tc = verbosity.stage("typecheck", || jalm_typecheck::check_parsed(&parsed));
+    let ec = verbosity.stage("effectcheck", || jalm_effectcheck::check_parsed(&parsed));
+    if tc.has_errors() || !ec.diagnostics.is_empty() {
+        return Err(format!("check failed for {label}"));
+    }
+    Ok(parsed)
+}
+
+fn compile_main(parsed: &Parse, options: &CompileOptions) -> Result<Vec<u8>, String> {
+    jalm_codegen::compile_parsed(parsed, options).map_err(|diags| {
+        let lines: Vec<String> = diags.iter().map(|d| format!("{}: {}", d.code, d.message)).collect();
+        format!("codegen failed for src/main.jalm:\n{}", lines.join("\n"))
+    })
+}
+
+/// Runs the module's exported `main`, passing the argument count as its `argc` in whichever
+/// integer type it declares, and returns its first result, if it has one.
+fn run_wasm_main(wasm: &[u8], args: &[String]) -> Result<Option<Val>, String> {
+    use wasmtime::{Engine, Instance, Module, Store, ValType};
+
+    let engine = Engine::default();
+    let module = Module::new(&engine, wasm).map_err(|e| format!("invalid module: {e}"))?;
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[]).map_err(|e| format!("instantiate: {e}"))?;
+    let main = instance
+        .get_func(&mut store, "main")
+        .ok_or_else(|| "no exported `main` function".to_string())?;
+    let ty = main.ty(&store);
+    let params: Vec<_> = ty.params().collect();
+    let params = match params.as_slice() {
+        [] => Vec::new(),
+        [ValType::I64] => vec![Val::I64(args.len() as i64)],
+        [ValType::I32] => vec![Val::I32(args.len() as i32)],
+        _ => return Err(format!("`main` must take zero parameters or an integer `argc`, found {} parameters", params.len())),
+    };
+    let mut results = vec![Val::I64(0); ty.results().len()];
+    main.call(&mut store, &params, &mut results).map_err(|e| format!("run: {e}"))?;
+    Ok(results.into_iter().next())
+}
+
+/// `value` as a literal of type `ty`, or `None` for a type the repl does not print.
+fn show_value(value: &Val, ty: &str) -> Option<String> {
+    match (ty, value) {
+        ("bool", Val::I32(v)) => Some((*v != 0).to_string()),
+        ("i32", Val::I32(v)) => Some(v.to_string()),
+        ("i64", Val::I64(v)) => Some(v.to_string()),
+        ("u64", Val::I64(v)) => Some((*v as u64).to_string()),
+        // `{:?}` keeps the `.0` of a whole number, so the value reads back as a float.
+        ("f64", Val::F64(bits)) => Some(format!("{:?}", f64::from_bits(*bits))),
+        ("f32", Val::F32(bits)) => Some(format!("{:?}", f32::from_bits(*bits))),
+        _ => None,
+    }
+}
+
 fn read_file(path: &Path) -> Result<String, String> {
     fs::read_to_string(path).map_err(|e| format!("failed to read {}: {e}", path.display()))
 }
@@ -0,0 +1,151 @@
+//! `jalmt repl`: reads items, statements, and expressions one line at a time.
+//!
+//! Each input is checked, compiled, and run once as the body of a generated `fn main`, after
+//! the accepted items and the accepted bindings. A bare expression gives `main` the type the
+//! checker infers for it, and its value is printed. A `let` keeps the value its initializer
+//! produced, as the declaration `let name: T = value;` in front of later inputs, so the
+//! initializer is not run again. Other statements run and are then forgotten.
+
+use jalm_codegen::CompileOptions;
+use jalm_parser::{parse, parse_expr, Parse};
+use jalm_syntax::SyntaxKind;
+use jalm_typecheck::body_type;
+use std::io::{BufRead, IsTerminal, Write};
+
+/// The types of the values the repl prints and keeps in bindings.
+const VALUE_TYPES: &[&str] = &["bool", "i32", "i64", "u64", "f64", "f32"];
+
+#[derive(Default)]
+pub struct Session {
+    items: Vec<String>,
+    /// Accepted bindings as declarations of their values, e.g. `let x: i64 = 2;`.
+    bindings: Vec<String>,
+}
+
+impl Session {
+    /// Checks and runs one line of input; returns the printed value of an expression, `None`
+    /// for an item, a statement, or a unit value, or the errors that rejected the input.
+    /// Rejected input is forgotten.
+    pub fn eval(&mut self, input: &str) -> Result<Option<String>, Vec<String>> {
+        if parse_expr(input).is_ok() {
+            return self.run(input);
+        }
+        let as_items = parse(input);
+        if as_items.is_ok() && as_items.syntax().children().next().is_some() {
+            check(&self.program(&[input], "()", ""))?;
+            self.items.push(input.to_string());
+            return Ok(None);
+        }
+        let stmt = if input.ends_with(';') || input.ends_with('}') { input.to_string() } else { format!("{input};") };
+        let Some((name, is_mut)) = let_name(&stmt) else {
+            self.run(&stmt)?;
+            return Ok(None);
+        };
+        let body = format!("{stmt}\n{name}");
+        let ty = self.main_type(&body);
+        if !VALUE_TYPES.contains(&ty.as_str()) {
+            return Err(vec![format!("error: cannot keep a binding of type `{ty}`")]);
+        }
+        let Some(value) = self.run(&body)? else {
+            return Ok(None);
+        };
+        let keyword = if is_mut { "let mut" } else { "let" };
+        self.bindings.push(format!("{keyword} {name}: {ty} = {value};"));
+        Ok(None)
+    }
+
+    /// The type of `body`'s tail, which `main` returns; `()` when it is not known.
+    fn main_type(&self, body: &str) -> String {
+        body_type(&parse(&self.program(&[], "()", body)), "main").unwrap_or_else(|| "()".to_string())
+    }
+
+    /// Runs `body` as `main`, typed by its tail; returns the printed value.
+    fn run(&self, body: &str) -> Result<Option<String>, Vec<String>> {
+        let ty = self.main_type(body);
+        let source = self.program(&[], &ty, body);
+        let parsed = check(&source)?;
+        let wasm = jalm_codegen::compile_parsed(&parsed, &CompileOptions::default())
+            .map_err(|diags| diags.iter().map(|d| format!("error[{}]: {}", d.code, d.message)).collect::<Vec<_>>())?;
+        let value = crate::run_wasm_main(&wasm, &[]).map_err(|e| vec![format!("error: {e}")])?;
+        Ok(value.and_then(|v| crate::show_value(&v, &ty)))
+    }
+
+    /// The kept items plus `extra_items`, then `main` returning `ret` from the kept bindings and `body`.
+    fn program(&self, extra_items: &[&str], ret: &str, body: &str) -> String {
+        let mut source = String::new();
+        for item in self.items.iter().map(String::as_str).chain(extra_items.iter().copied()) {
+            source.push_str(item);
+            source.push('\n');
+        }
+        if ret == "()" {
+            source.push_str("fn main() {\n");
+        } else {
+            source.push_str(&format!("fn main() -> {ret} {{\n"));
+        }
+        for binding in &self.bindings {
+            source.push_str(binding);
+            source.push('\n');
+        }
+        source.push_str(body);
+        source.push_str("\n}\n");
+        source
+    }
+}
+
+/// The name a `let name = ...;` statement binds, and whether it is `mut`; `None` for any
+/// other statement, including a `let` with a pattern.
+fn let_name(stmt: &str) -> Option<(String, bool)> {
+    let parsed = parse(&format!("fn main() {{\n{stmt}\n}}"));
+    let let_stmt = parsed.syntax().descendants().find(|n| n.kind() == SyntaxKind::LetStmt)?;
+    let pattern = let_stmt.children().find(|n| n.kind() == SyntaxKind::Pattern)?;
+    let name = pattern.children().find(|n| n.kind() == SyntaxKind::IdentNode).filter(|_| pattern.children().count() == 1)?;
+    let is_mut = let_stmt.children_with_tokens().any(|e| e.kind() == SyntaxKind::KwMut);
+    Some((name.text().to_string().trim().to_string(), is_mut))
+}
+
+/// Parses `source`, or returns its parse, type, and effect errors; warnings are not shown.
+fn check(source: &str) -> Result<Parse, Vec<String>> {
+    let parsed = parse(source);
+    if !parsed.is_ok() {
+        return Err(parsed.errors().iter().map(|e| format!("error: {}", e.message)).collect());
+    }
+    let errors: Vec<String> = jalm_typecheck::check_parsed(&parsed)
+        .diagnostics
+        .iter()
+        .filter(|d| !d.is_warning())
+        .map(|d| format!("error[{}]: {}", d.code, d.message))
+        .chain(jalm_effectcheck::check_parsed(&parsed).diagnostics.iter().map(|d| format!("error[{}]: {}", d.code, d.message)))
+        .collect();
+    if errors.is_empty() {
+        Ok(parsed)
+    } else {
+        Err(errors)
+    }
+}
+
+/// Runs the loop over stdin until end of input; a prompt is shown only on a terminal.
+pub fn run() -> Result<(), String> {
+    let stdin = std::io::stdin();
+    let interactive = stdin.is_terminal();
+    let mut session = Session::default();
+    let mut stdout = std::io::stdout();
+    loop {
+        if interactive {
+            print!("jalm> ");
+            stdout.flush().map_err(|e| format!("write prompt: {e}"))?;
+        }
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).map_err(|e| format!("read input: {e}"))? == 0 {
+            return Ok(());
+        }
+        let input = line.trim();
+        if input.is_empty() {
+            continue;
+        }
+        match session.eval(input) {
+            Ok(Some(value)) => println!("{value}"),
+            Ok(None) => {}
+            Err(errors) => errors.iter().for_each(|e| eprintln!("{e}")),
+        }
+    }
+}
@@ -27,3 +27,334 @@ fn check_reports_diagnostics_json() {
     cmd.arg("check").arg(&file);
     cmd.assert().success().stdout(predicate::str::contains("type_diagnostics"));
 }
+
+#[test]
+fn check_max_errors_truncates_in_source_order() {
+    let temp = TempDir::new().unwrap();
+    let file = temp.path().join("main.jalm");
+    fs::write(&file, "fn main() -> i64 {\n  fs::read(a);\n  let x = b + c;\n  d + e\n}").unwrap();
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("jalmt"));
+    cmd.arg("check").arg(&file).args(["--max-errors", "3"]);
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let diag: serde_json::Value = serde_json::from_slice(&output).unwrap();
+    let effects = diag["effect_diagnostics"].as_array().unwrap();
+    let types = diag["type_diagnostics"].as_array().unwrap();
+    assert_eq!(effects.len() + types.len(), 3);
+    assert_eq!(effects.len(), 1);
+    assert_eq!(types[0]["actual"], "a");
+    assert_eq!(types[1]["actual"], "b");
+    assert_eq!(diag["note"], "... and 3 more");
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("jalmt"));
+    cmd.arg("check").arg(&file);
+    cmd.assert().success().stdout(predicate::str::contains("note").not());
+}
+
+#[test]
+fn run_forwards_argument_count_to_main() {
+    let temp = TempDir::new().unwrap();
+    fs::create_dir_all(temp.path().join("src")).unwrap();
+    fs::write(
+        temp.path().join("src/main.jalm"),
+        "fn main(argc: i64) -> i64 {\n  return argc;\n}\n",
+    )
+    .unwrap();
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("jalmt"));
+    cmd.arg("run").arg("--dir").arg(temp.path()).args(["--", "a", "b", "c"]);
+    cmd.assert().success().stdout("3\n");
+}
+
+#[test]
+fn run_converts_arguments_and_result_by_main_signature() {
+    let temp = TempDir::new().unwrap();
+    fs::create_dir_all(temp.path().join("src")).unwrap();
+    let run = |source: &str, expected: &str| {
+        fs::write(temp.path().join("src/main.jalm"), source).unwrap();
+        let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("jalmt"));
+        cmd.arg("run").arg("--dir").arg(temp.path()).args(["--", "a", "b"]);
+        cmd.assert().success().stdout(expected.to_string());
+    };
+    run("fn main(argc: i32) -> i32 {\n  argc + 1\n}\n", "3\n");
+    run("fn main(argc: i64) -> bool {\n  argc == 2\n}\n", "true\n");
+    run("fn main() -> f64 {\n  1.5\n}\n", "1.5\n");
+    run("fn main() {\n}\n", "");
+}
+
+#[test]
+fn fmt_emit_stdout_leaves_file_untouched() {
+    let temp = TempDir::new().unwrap();
+    let file = temp.path().join("main.jalm");
+    let source = "fn f(a:i64)->i64{a}";
+    fs::write(&file, source).unwrap();
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("jalmt"));
+    cmd.arg("fmt").arg(&file).arg("--emit=stdout");
+    cmd.assert().success().stdout("fn f(a: i64) -> i64 {\n  a\n}\n");
+    assert_eq!(fs::read_to_string(&file).unwrap(), source);
+}
+
+#[test]
+fn ast_spans_flag_appends_offsets() {
+    let temp = TempDir::new().unwrap();
+    let file = temp.path().join("main.jalm");
+    fs::write(&file, "fn main() {}").unwrap();
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("jalmt"));
+    cmd.arg("ast").arg(&file);
+    cmd.assert().success().stdout(predicate::str::contains("Ident 'main'\n"));
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("jalmt"));
+    cmd.arg("ast").arg("--spans").arg(&file);
+    cmd.assert().success().stdout(predicate::str::contains("Ident 'main' @3..7\n"));
+}
+
+#[test]
+fn version_verbose_lists_components() {
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("jalmt"));
+    cmd.arg("version").arg("--verbose");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::starts_with("jalmt "))
+        .stdout(predicate::str::contains("jalm_parser "))
+        .stdout(predicate::str::contains("jalm_codegen "))
+        .stdout(predicate::str::contains("wasm-encoder 0.38"))
+        .stdout(predicate::str::contains("wasm features multi-value, bulk-memory"));
+}
+
+#[test]
+fn build_target_selects_output() {
+    let temp = TempDir::new().unwrap();
+    fs::create_dir_all(temp.path().join("src")).unwrap();
+    fs::write(temp.path().join("src/main.jalm"), "fn main() -> i64 {\n  return 1;\n}\n").unwrap();
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("jalmt"));
+    cmd.arg("build").arg("--dir").arg(temp.path()).args(["--target", "check"]);
+    cmd.assert().success();
+    assert!(!temp.path().join("target").exists());
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("jalmt"));
+    cmd.arg("build").arg("--dir").arg(temp.path()).args(["--target", "wasm32"]);
+    cmd.assert().success();
+    let wasm = fs::read(temp.path().join("target/wasm32/main.wasm")).unwrap();
+    assert!(wasm.starts_with(b"\0asm"));
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("jalmt"));
+    cmd.arg("build").arg("--dir").arg(temp.path()).args(["--target", "x86"]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid value 'x86' for '--target <TARGET>'"))
+        .stderr(predicate::str::contains("wasm32, check"));
+}
+
+#[test]
+fn build_features_include_gated_functions() {
+    let temp = TempDir::new().unwrap();
+    fs::create_dir_all(temp.path().join("src")).unwrap();
+    fs::write(
+        temp.path().join("src/main.jalm"),
+        "@cfg(feature = \"extra\")\n@export(\"extra_entry\")\nfn extra() -> i64 {\n  return undefined_in_default_builds;\n}\n\nfn main() -> i64 {\n  return 0;\n}\n",
+    )
+    .unwrap();
+    let exports_extra = || {
+        let wasm = fs::read(temp.path().join("target/wasm32/main.wasm")).unwrap();
+        wasm.windows(b"extra_entry".len()).any(|w| w == b"extra_entry")
+    };
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("jalmt"));
+    cmd.arg("build").arg("--dir").arg(temp.path());
+    cmd.assert().success();
+    assert!(!exports_extra());
+
+    // Enabled, the gated function is checked like any other.
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("jalmt"));
+    cmd.arg("build").arg("--dir").arg(temp.path()).args(["--features", "extra"]);
+    cmd.assert().failure().stderr(predicate::str::contains("check failed"));
+
+    fs::write(
+        temp.path().join("src/main.jalm"),
+        "@cfg(feature = \"extra\")\n@export(\"extra_entry\")\nfn extra() -> i64 {\n  return 2;\n}\n\nfn main() -> i64 {\n  return 0;\n}\n",
+    )
+    .unwrap();
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("jalmt"));
+    cmd.arg("build").arg("--dir").arg(temp.path()).args(["--features", "other,extra"]);
+    cmd.assert().success();
+    assert!(exports_extra());
+}
+
+#[test]
+fn features_gate_items_in_check_test_and_run() {
+    let temp = TempDir::new().unwrap();
+    fs::create_dir_all(temp.path().join("src")).unwrap();
+    fs::create_dir_all(temp.path().join("tests")).unwrap();
+    let gated = "@cfg(feature = \"extra\")\nfn extra() -> i64 {\n  return missing;\n}\n\nfn main() -> i64 {\n  return 3;\n}\n";
+    fs::write(temp.path().join("src/main.jalm"), gated).unwrap();
+    fs::write(temp.path().join("tests/gated.jalm"), gated).unwrap();
+    let file = temp.path().join("src/main.jalm");
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("jalmt"));
+    cmd.arg("check").arg(&file);
+    cmd.assert().success().stdout(predicate::str::contains("E0001").not());
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("jalmt"));
+    cmd.arg("check").arg(&file).args(["--features", "extra"]);
+    cmd.assert().success().stdout(predicate::str::contains("E0001"));
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("jalmt"));
+    cmd.arg("test").arg("--dir").arg(temp.path());
+    cmd.assert().success();
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("jalmt"));
+    cmd.arg("test").arg("--dir").arg(temp.path()).args(["--features", "extra"]);
+    cmd.assert().failure().stderr(predicate::str::contains("check failed"));
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("jalmt"));
+    cmd.arg("run").arg("--dir").arg(temp.path());
+    cmd.assert().success().stdout("3\n");
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("jalmt"));
+    cmd.arg("run").arg("--dir").arg(temp.path()).args(["--features", "extra"]);
+    cmd.assert().failure().stderr(predicate::str::contains("check failed"));
+}
+
+#[test]
+fn explain_describes_known_codes() {
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("jalmt"));
+    cmd.arg("explain").arg("E0001");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::starts_with("E0001: undefined variable\n"));
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("jalmt"));
+    cmd.arg("explain").arg("E9999");
+    cmd.assert().failure().stderr(predicate::str::contains("unknown diagnostic code `E9999`"));
+}
+
+#[test]
+fn build_debug_embeds_source_map() {
+    let temp = TempDir::new().unwrap();
+    fs::create_dir_all(temp.path().join("src")).unwrap();
+    fs::write(temp.path().join("src/main.jalm"), "fn main() -> i64 {\n  return 1 + 2;\n}\n").unwrap();
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("jalmt"));
+    cmd.arg("build").arg("--dir").arg(temp.path());
+    cmd.assert().success();
+    let wasm = fs::read(temp.path().join("target/wasm32/main.wasm")).unwrap();
+    assert!(jalm_codegen::read_source_map(&wasm).is_none());
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("jalmt"));
+    cmd.arg("build").arg("--dir").arg(temp.path()).arg("--debug");
+    cmd.assert().success();
+    let wasm = fs::read(temp.path().join("target/wasm32/main.wasm")).unwrap();
+    let entries = jalm_codegen::read_source_map(&wasm).unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!((entries[0].span.start, entries[0].span.end), (28, 33));
+}
+
+#[test]
+fn repl_keeps_bindings_between_lines() {
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("jalmt"));
+    cmd.arg("repl").write_stdin("let x = 2;\nx + 3\ny + 1\nfn double(n: i64) -> i64 { n * 2 }\ndouble(x)\n");
+    cmd.assert()
+        .success()
+        .stdout("5\n4\n")
+        .stderr(predicate::str::contains("error[E0001]: undefined variable"));
+}
+
+#[test]
+fn repl_prints_values_by_their_type() {
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("jalmt"));
+    cmd.arg("repl").write_stdin("1 < 2
+let half = 1.0 / 2.0;
+half * 3.0
+let mut n = 4;
+let big = n > 3;
+big
+let u: () = ();
+");
+    cmd.assert()
+        .success()
+        .stdout("true\n1.5\ntrue\n")
+        .stderr(predicate::str::contains("error: cannot keep a binding of type `()`"));
+}
+
+#[test]
+fn check_json_format_v1_shares_one_envelope() {
+    let temp = TempDir::new().unwrap();
+    let file = temp.path().join("main.jalm");
+    fs::write(&file, "fn main() -> i64 {\n  let flag: bool = 1;\n  fs::read(2);\n  0\n}\n\nuse std::io\n").unwrap();
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("jalmt"));
+    cmd.arg("check").arg(&file).arg("--json-format=v1");
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let diag: serde_json::Value = serde_json::from_slice(&output).unwrap();
+    assert_eq!(
+        diag,
+        serde_json::json!({
+            "version": 1,
+            "diagnostics": [
+                {
+                    "code": "E0003",
+                    "severity": "error",
+                    "message": "type mismatch",
+                    "span": { "start": 21, "end": 40, "line": 2, "col": 3 },
+                    "labels": [{ "name": "expected", "value": "bool" }, { "name": "actual", "value": "i64" }],
+                    "related": [],
+                },
+                {
+                    "code": "E1001",
+                    "severity": "error",
+                    "message": "undeclared effect",
+                    "span": { "start": 43, "end": 47, "line": 3, "col": 3 },
+                    "labels": [{ "name": "required", "value": "fs" }],
+                    "related": [],
+                },
+                {
+                    "code": "W0006",
+                    "severity": "warning",
+                    "message": "unused import",
+                    "span": { "start": 72, "end": 74, "line": 7, "col": 10 },
+                    "labels": [{ "name": "actual", "value": "io" }],
+                    "related": [],
+                },
+                {
+                    "code": null,
+                    "severity": "error",
+                    "message": "expected `;`",
+                    "span": { "start": 75, "end": 75, "line": 8, "col": 1 },
+                    "labels": [],
+                    "related": [],
+                },
+            ],
+        })
+    );
+}
+
+#[test]
+fn quiet_and_verbose_control_output() {
+    let temp = TempDir::new().unwrap();
+    fs::create_dir_all(temp.path().join("src")).unwrap();
+    fs::write(temp.path().join("src/main.jalm"), "fn main() -> i64 {\n  return 7;\n}\n").unwrap();
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("jalmt"));
+    cmd.arg("--quiet").arg("run").arg("--dir").arg(temp.path());
+    cmd.assert().success().stdout("").stderr("");
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("jalmt"));
+    cmd.arg("run").arg("--dir").arg(temp.path()).arg("--verbose");
+    cmd.assert()
+        .success()
+        .stdout("7\n")
+        .stderr(predicate::str::contains("file: ").and(predicate::str::contains("typecheck: ")).and(predicate::str::contains("codegen: ")));
+
+    let file = temp.path().join("bad.jalm");
+    fs::write(&file, "fn main() -> i64 {\n  missing\n}\n").unwrap();
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("jalmt"));
+    cmd.arg("check").arg(&file).arg("--quiet");
+    cmd.assert().failure().stdout(predicate::str::contains("E0001")).stderr(predicate::str::contains("check failed"));
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("jalmt"));
+    cmd.arg("check").arg(temp.path().join("src/main.jalm")).arg("--quiet");
+    cmd.assert().success().stdout("");
+
+    let mut cmd = Command::new(assert_cmd::cargo::cargo_bin!("jalmt"));
+    cmd.args(["--quiet", "--verbose", "version"]);
+    cmd.assert().failure();
+}